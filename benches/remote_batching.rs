@@ -0,0 +1,93 @@
+//! 远程消息批量发送的吞吐量对比
+//!
+//! 在内存管道上收发相同数量的消息，分别逐条发送和按不同批次大小发送：
+//!
+//! ```text
+//! cargo bench --bench remote_batching
+//! ```
+
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tokio::io::duplex;
+use protoactor::{Message, Pid};
+use protoactor::remote::{
+    read_frame, write_frame, Frame, FrameFlags, FrameType, MessageSerializer, DEFAULT_MAX_FRAME_SIZE,
+};
+
+const MESSAGES: usize = 100_000;
+
+#[derive(Serialize, Deserialize)]
+struct Ping {
+    sequence: u64,
+}
+
+fn serializer() -> MessageSerializer {
+    let serializer = MessageSerializer::new();
+    serializer.register_serde::<Ping>("bench.Ping");
+    serializer
+}
+
+fn target() -> Pid {
+    Pid {
+        address: "127.0.0.1:8090".to_string(),
+        id: "bench".to_string(),
+    }
+}
+
+/// `batch_size` 为 1 时逐条发送
+async fn run(batch_size: usize) -> Duration {
+    let (mut client, mut server) = duplex(1 << 20);
+
+    let receiver = tokio::spawn(async move {
+        let serializer = serializer();
+        let mut received = 0;
+        while received < MESSAGES {
+            let frame = read_frame(&mut server, DEFAULT_MAX_FRAME_SIZE).await.unwrap();
+            received += match frame.frame_type {
+                FrameType::Batch => serializer.deserialize_batch(&frame.payload).await.unwrap().len(),
+                _ => {
+                    serializer.deserialize(&frame.payload).await.unwrap();
+                    1
+                }
+            };
+        }
+    });
+
+    let serializer = serializer();
+    let target = target();
+    let start = Instant::now();
+
+    if batch_size <= 1 {
+        for sequence in 0..MESSAGES as u64 {
+            let data = serializer.serialize(&Message::new(Ping { sequence }), &target).await.unwrap();
+            write_frame(&mut client, &Frame::new(FrameType::User, data)).await.unwrap();
+        }
+    } else {
+        for first in (0..MESSAGES).step_by(batch_size) {
+            let batch: Vec<_> = (first..(first + batch_size).min(MESSAGES))
+                .map(|sequence| (target.clone(), Message::new(Ping { sequence: sequence as u64 })))
+                .collect();
+            let data = serializer.serialize_batch(&batch).await.unwrap();
+            let frame = Frame::new(FrameType::Batch, data).with_flags(FrameFlags::BATCHED);
+            write_frame(&mut client, &frame).await.unwrap();
+        }
+    }
+
+    receiver.await.unwrap();
+    start.elapsed()
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    for batch_size in [1, 16, 128, 1000] {
+        let elapsed = runtime.block_on(run(batch_size));
+        let label = if batch_size == 1 { "off".to_string() } else { batch_size.to_string() };
+        println!(
+            "batching {:>5}: {:>10.0} msg/s ({:?})",
+            label,
+            MESSAGES as f64 / elapsed.as_secs_f64(),
+            elapsed
+        );
+    }
+}
@@ -2,6 +2,8 @@ use std::sync::Arc;
 use tokio::sync::mpsc;
 use crate::message::Message;
 use crate::errors::SendError;
+use crate::dispatcher::{BackpressureController, BackpressureSignal};
+use crate::process::Pid;
 use rand::Rng;
 
 /// ActorRef represents a reference to an actor that can receive messages
@@ -9,8 +11,12 @@ use rand::Rng;
 pub struct ActorRef {
     /// The unique identifier of this actor
     id: String,
+    /// Address of the remote node hosting this actor, `None` for local actors
+    address: Option<String>,
     /// The sender half of the actor's message channel
     sender: Arc<mpsc::Sender<Message>>,
+    /// Backpressure controller shared with the receiving mailbox, which releases a slot per processed message
+    backpressure: Option<Arc<BackpressureController>>,
 }
 
 impl ActorRef {
@@ -18,7 +24,19 @@ impl ActorRef {
     pub fn new(id: String, sender: mpsc::Sender<Message>) -> Self {
         Self {
             id,
+            address: None,
             sender: Arc::new(sender),
+            backpressure: None,
+        }
+    }
+
+    /// Creates a proxy for an actor on another node; messages sent to it are fed to `sender`
+    pub fn remote(pid: Pid, sender: mpsc::Sender<Message>) -> Self {
+        Self {
+            id: pid.id,
+            address: Some(pid.address),
+            sender: Arc::new(sender),
+            backpressure: None,
         }
     }
 
@@ -27,9 +45,65 @@ impl ActorRef {
         &self.id
     }
 
+    /// Returns the address of the remote node hosting this actor, `None` for local actors
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+
     /// Sends a message to this actor
+    ///
+    /// Fails with `MailboxFull` when the receiver's backpressure controller rejects the message.
     pub async fn send(&self, msg: Message) -> Result<(), SendError> {
-        self.sender.send(msg).await.map_err(|_| SendError::MailboxFull)
+        self.acquire_slot()?;
+        self.sender.send(msg).await.map_err(|_| {
+            self.release_slot();
+            SendError::MailboxFull
+        })
+    }
+
+    /// Returns the number of messages waiting in this actor's mailbox
+    pub fn mailbox_size(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// Attaches the backpressure controller shared with the receiving mailbox
+    pub fn with_backpressure(mut self, controller: Arc<BackpressureController>) -> Self {
+        self.backpressure = Some(controller);
+        self
+    }
+
+    /// Tries to send a message without waiting and reports the receiver's pressure.
+    ///
+    /// Fails with `MailboxFull` when the message was not enqueued because the mailbox is full.
+    /// The receiving mailbox releases the acquired slot once the message is processed.
+    pub fn try_send(&self, msg: Message) -> Result<BackpressureSignal, SendError> {
+        let signal = self.acquire_slot()?;
+        self.sender.try_send(msg).map_err(|e| {
+            self.release_slot();
+            match e {
+                mpsc::error::TrySendError::Full(_) => SendError::MailboxFull,
+                mpsc::error::TrySendError::Closed(_) => SendError::MailboxClosed,
+            }
+        })?;
+        Ok(signal)
+    }
+
+    /// Takes a slot from the backpressure controller, if one is attached
+    fn acquire_slot(&self) -> Result<BackpressureSignal, SendError> {
+        match &self.backpressure {
+            Some(controller) => match controller.try_acquire() {
+                BackpressureSignal::Rejected => Err(SendError::MailboxFull),
+                signal => Ok(signal),
+            },
+            None => Ok(BackpressureSignal::Ok),
+        }
+    }
+
+    /// Returns a slot taken for a message that was never enqueued
+    fn release_slot(&self) {
+        if let Some(controller) = &self.backpressure {
+            controller.release();
+        }
     }
 
     /// Stops this actor
@@ -47,7 +121,9 @@ impl ActorRef {
         let (sender, _receiver) = mpsc::channel(100); // Specify a buffer size, e.g., 100
         Self {
             id: random_id,
+            address: None,
             sender: Arc::new(sender),
+            backpressure: None,
         }
     }
 }
@@ -56,13 +132,14 @@ impl std::fmt::Debug for ActorRef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ActorRef")
             .field("id", &self.id)
+            .field("address", &self.address)
             .finish()
     }
 }
 
 impl PartialEq for ActorRef {
     fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+        self.id == other.id && self.address == other.address
     }
 }
 
@@ -71,6 +148,7 @@ impl Eq for ActorRef {}
 impl std::hash::Hash for ActorRef {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.id.hash(state);
+        self.address.hash(state);
     }
 }
 
@@ -82,7 +160,72 @@ impl Default for ActorRef {
         let (sender, _receiver) = mpsc::channel(100);
         Self {
             id: random_id,
+            address: None,
             sender: Arc::new(sender),
+            backpressure: None,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::dispatcher::{BackpressureChanged, BackpressureConfig};
+    use crate::eventstream::EventStream;
+
+    fn controller(max_queue_size: usize) -> BackpressureController {
+        BackpressureController::new(BackpressureConfig {
+            max_queue_size,
+            high_watermark: 0.5,
+            low_watermark: 0.25,
+            pressure_window: Duration::from_millis(0),
+            sampling_interval: Duration::from_millis(10),
+        })
+    }
+
+    #[test]
+    fn test_try_send_rejects_when_mailbox_full() {
+        let (sender, mut receiver) = mpsc::channel(8);
+        let controller = Arc::new(controller(2));
+        let actor = ActorRef::new("a".to_string(), sender).with_backpressure(Arc::clone(&controller));
+
+        assert!(actor.try_send(Message::new(1u32)).is_ok());
+        assert!(actor.try_send(Message::new(2u32)).is_ok());
+        assert!(matches!(actor.try_send(Message::new(3u32)), Err(SendError::MailboxFull)));
+
+        // The mailbox frees a slot once it has processed a message
+        receiver.try_recv().unwrap();
+        controller.release();
+        assert!(actor.try_send(Message::new(4u32)).is_ok());
+    }
+
+    #[test]
+    fn test_try_send_returns_slot_when_channel_full() {
+        let (sender, _receiver) = mpsc::channel(1);
+        let controller = Arc::new(controller(4));
+        let actor = ActorRef::new("a".to_string(), sender).with_backpressure(Arc::clone(&controller));
+
+        assert!(actor.try_send(Message::new(1u32)).is_ok());
+        assert!(matches!(actor.try_send(Message::new(2u32)), Err(SendError::MailboxFull)));
+        assert_eq!(controller.current_load(), 0.25);
+    }
+
+    #[test]
+    fn test_slow_down_is_published_to_event_stream() {
+        let event_stream = Arc::new(EventStream::new());
+        let mut events = event_stream.subscribe::<BackpressureChanged>();
+        let controller = controller(4).with_source("a").with_event_stream(Arc::clone(&event_stream));
+        let (sender, _receiver) = mpsc::channel(8);
+        let actor = ActorRef::new("a".to_string(), sender).with_backpressure(Arc::new(controller));
+
+        assert_eq!(actor.try_send(Message::new(1u32)).unwrap(), BackpressureSignal::Ok);
+        assert_eq!(actor.try_send(Message::new(2u32)).unwrap(), BackpressureSignal::SlowDown);
+
+        let event = events.try_recv().unwrap();
+        let event = event.downcast_ref::<BackpressureChanged>().unwrap();
+        assert_eq!(event.source, "a");
+        assert_eq!(event.previous, BackpressureSignal::Ok);
+        assert_eq!(event.current, BackpressureSignal::SlowDown);
+    }
+}
@@ -1,15 +1,13 @@
-use crate::actor::Actor;
-use crate::props::Props;
-use crate::supervision::SupervisorStrategy;
+use std::marker::PhantomData;
+use std::time::Duration;
+use crate::actor::{Actor, Props};
+use crate::supervisor::SupervisorStrategy;
 use crate::middleware::Middleware;
-use crate::dispatcher::Dispatcher;
+use crate::dispatcher::{DispatcherRegistry, Priority};
 
 pub struct ActorBuilder<A: Actor> {
-    props: Props<A>,
-    supervisor: Option<Box<dyn SupervisorStrategy>>,
-    middleware: Vec<Box<dyn Middleware>>,
-    mailbox_size: Option<usize>,
-    dispatcher: Option<Box<dyn Dispatcher>>,
+    props: Props,
+    _actor: PhantomData<fn() -> A>,
 }
 
 impl<A: Actor> ActorBuilder<A> {
@@ -19,47 +17,57 @@ impl<A: Actor> ActorBuilder<A> {
     {
         Self {
             props: Props::new(producer),
-            supervisor: None,
-            middleware: Vec::new(),
-            mailbox_size: None,
-            dispatcher: None,
+            _actor: PhantomData,
         }
     }
 
     pub fn with_supervisor<S: SupervisorStrategy + 'static>(mut self, supervisor: S) -> Self {
-        self.supervisor = Some(Box::new(supervisor));
+        self.props = self.props.with_supervisor(Box::new(supervisor));
         self
     }
 
     pub fn with_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
-        self.middleware.push(Box::new(middleware));
+        self.props = self.props.with_middleware(Box::new(middleware));
         self
     }
 
     pub fn with_mailbox_size(mut self, size: usize) -> Self {
-        self.mailbox_size = Some(size);
+        self.props = self.props.with_mailbox_size(size);
         self
     }
 
-    pub fn with_dispatcher<D: Dispatcher + 'static>(mut self, dispatcher: D) -> Self {
-        self.dispatcher = Some(Box::new(dispatcher));
+    pub fn with_dispatcher(mut self, dispatcher_id: impl Into<String>) -> Self {
+        self.props = self.props.with_dispatcher(dispatcher_id);
         self
     }
 
-    pub fn build(self) -> Props<A> {
-        let mut props = self.props;
-        if let Some(supervisor) = self.supervisor {
-            props = props.with_strategy(supervisor);
-        }
-        for middleware in self.middleware {
-            props = props.with_middleware(middleware);
-        }
-        if let Some(size) = self.mailbox_size {
-            props = props.with_mailbox_size(size);
-        }
-        if let Some(dispatcher) = self.dispatcher {
-            props = props.with_dispatcher(dispatcher);
-        }
-        props
+    pub fn with_pinned_dispatcher(self) -> Self {
+        self.with_dispatcher(DispatcherRegistry::PINNED)
+    }
+
+    pub fn with_blocking_dispatcher(self) -> Self {
+        self.with_dispatcher(DispatcherRegistry::BLOCKING)
+    }
+
+    pub fn with_priority_dispatcher(self) -> Self {
+        self.with_dispatcher(DispatcherRegistry::PRIORITY)
+    }
+
+    pub fn with_worker_pool_dispatcher(self) -> Self {
+        self.with_dispatcher(DispatcherRegistry::WORKER_POOL)
+    }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.props = self.props.with_priority(priority);
+        self
+    }
+
+    pub fn with_batching(mut self, batch_size: usize, max_wait: Duration) -> Self {
+        self.props = self.props.with_batching(batch_size, max_wait);
+        self
+    }
+
+    pub fn build(self) -> Props {
+        self.props
     }
-} 
\ No newline at end of file
+} 
@@ -1,125 +1,246 @@
-use std::sync::{Arc, Mutex};
-use crate::{Actor, Context, Message, SystemMessage, ChildStats};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use crate::actor::{Actor, ActorRef};
+use crate::context::Context;
+use crate::errors::SendError;
+use crate::mailbox::MessageInvoker;
+use crate::message::{Message, SystemMessage};
 
+/// Actor 的运行单元，持有 Actor 实例和上下文，邮箱循环经由它交付消息
 pub(crate) struct ActorCell {
-    actor: Arc<Mutex<Box<dyn Actor>>>,
+    actor: tokio::sync::Mutex<Box<dyn Actor>>,
     context: Context,
-    state: ActorState,
-    child_stats: Arc<DashMap<Pid, ChildStats>>,
+    state: Mutex<ActorState>,
+    watchers: Mutex<Vec<ActorRef>>,
+    stopped: tokio::sync::Notify,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub(crate) enum ActorState {
     Starting,
     Running,
-    Stopping,
+    Suspended,
     Stopped,
 }
 
 impl ActorCell {
-    pub fn new(actor: Box<dyn Actor>, context: Context) -> Self {
+    pub(crate) fn new(actor: Box<dyn Actor>, context: Context) -> Self {
         Self {
-            actor: Arc::new(Mutex::new(actor)),
+            actor: tokio::sync::Mutex::new(actor),
             context,
-            state: ActorState::Starting,
-            child_stats: Arc::new(DashMap::new()),
+            state: Mutex::new(ActorState::Starting),
+            watchers: Mutex::new(Vec::new()),
+            stopped: tokio::sync::Notify::new(),
         }
     }
 
-    pub async fn handle_message(&mut self, msg: Message) {
-        if self.state == ActorState::Running {
-            let mut actor = self.actor.lock().unwrap();
-            actor.receive(&mut self.context, msg).await;
-        }
+    pub(crate) fn self_ref(&self) -> &ActorRef {
+        self.context.self_ref()
     }
 
-    pub async fn handle_system_message(&mut self, msg: SystemMessage) {
-        match msg {
-            SystemMessage::Stop => self.stop().await,
-            SystemMessage::Restart => self.restart().await,
-            SystemMessage::Resume => self.resume().await,
-            SystemMessage::Suspend => self.suspend().await,
-            SystemMessage::Failure(error) => self.handle_failure(error).await,
-            SystemMessage::Watch(pid) => self.watch(pid),
-            SystemMessage::Unwatch(pid) => self.unwatch(&pid),
-            SystemMessage::Terminated(pid) => self.handle_terminated(pid).await,
+    pub(crate) fn state(&self) -> ActorState {
+        *self.state.lock()
+    }
+
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.state() == ActorState::Stopped
+    }
+
+    /// 等待 Actor 停止
+    pub(crate) async fn stopped(&self) {
+        loop {
+            // 先登记再检查状态，避免错过检查之后的通知
+            let notified = self.stopped.notified();
+            if self.is_stopped() {
+                return;
+            }
+            notified.await;
         }
     }
 
-    async fn stop(&mut self) {
-        if self.state != ActorState::Stopped {
-            self.state = ActorState::Stopping;
-            let mut actor = self.actor.lock().unwrap();
-            actor.stopped(&mut self.context).await;
-            self.state = ActorState::Stopped;
+    /// 调用 `started`，失败时 Actor 直接进入停止状态，不再处理消息
+    pub(crate) async fn start(&self) -> Result<(), SendError> {
+        let mut actor = self.actor.lock().await;
+        match actor.started(&self.context).await {
+            Ok(()) => {
+                *self.state.lock() = ActorState::Running;
+                Ok(())
+            }
+            Err(e) => {
+                *self.state.lock() = ActorState::Stopped;
+                self.stopped.notify_waiters();
+                Err(e)
+            }
         }
     }
 
-    async fn restart(&mut self) {
-        self.stop().await;
-        self.state = ActorState::Starting;
-        let mut actor = self.actor.lock().unwrap();
-        actor.started(&mut self.context).await;
-        self.state = ActorState::Running;
+    /// 停止 Actor 并通知本地监视者，重复调用无效
+    pub(crate) async fn stop(&self) {
+        {
+            let mut state = self.state.lock();
+            if *state == ActorState::Stopped {
+                return;
+            }
+            *state = ActorState::Stopped;
+        }
+        self.stopped.notify_waiters();
+
+        let mut actor = self.actor.lock().await;
+        if let Err(e) = actor.stopping(&self.context).await {
+            log::error!("Actor {} failed while stopping: {}", self.id(), e);
+        }
+        if let Err(e) = actor.stopped(&self.context).await {
+            log::error!("Failed to stop actor {}: {}", self.id(), e);
+        }
+        drop(actor);
+
+        let watchers = std::mem::take(&mut *self.watchers.lock());
+        for watcher in watchers {
+            self.notify_terminated(&watcher).await;
+        }
     }
 
-    fn resume(&mut self) {
-        self.state = ActorState::Running;
+    async fn restart(&self) -> Result<(), SendError> {
+        let mut actor = self.actor.lock().await;
+        if let Err(e) = actor.stopped(&self.context).await {
+            log::error!("Failed to stop actor {} for restart: {}", self.id(), e);
+        }
+        actor.started(&self.context).await?;
+        *self.state.lock() = ActorState::Running;
+        Ok(())
     }
 
-    fn suspend(&mut self) {
-        self.state = ActorState::Stopping;
+    async fn notify_terminated(&self, watcher: &ActorRef) {
+        let terminated = Message::new(SystemMessage::Terminated(self.self_ref().clone()));
+        if let Err(e) = watcher.send(terminated).await {
+            log::warn!("Failed to notify {} of {} termination: {}", watcher.id(), self.id(), e);
+        }
     }
 
-    fn watch(&mut self, pid: Pid) {
-        self.context.watchers.insert(pid);
+    /// 运行中的 Actor 一次处理一批用户消息
+    async fn deliver_batch(&self, msgs: Vec<Message>) -> Result<(), SendError> {
+        if msgs.is_empty() {
+            return Ok(());
+        }
+        if self.state() != ActorState::Running {
+            return Err(SendError::DeadLetter);
+        }
+        let mut actor = self.actor.lock().await;
+        actor.receive_batch(&self.context, msgs).await
     }
+}
 
-    fn unwatch(&mut self, pid: &Pid) {
-        self.context.watchers.remove(pid);
+#[async_trait]
+impl MessageInvoker for ActorCell {
+    fn id(&self) -> &str {
+        self.self_ref().id()
     }
 
-    async fn handle_terminated(&mut self, pid: Pid) {
-        // Notify watchers
-        for watcher in &self.context.watchers {
-            self.context.send(watcher, Message {
-                payload: Box::new(SystemMessage::Terminated(pid.clone())),
-                sender: Some(self.context.self_pid.clone()),
-                header: None,
-            }).await.ok();
+    async fn invoke_system(&self, msg: SystemMessage) -> Result<(), SendError> {
+        match msg {
+            SystemMessage::Stop => self.stop().await,
+            SystemMessage::Restart => return self.restart().await,
+            SystemMessage::Suspend => {
+                let mut state = self.state.lock();
+                if *state == ActorState::Running {
+                    *state = ActorState::Suspended;
+                }
+            }
+            SystemMessage::Resume => {
+                let mut state = self.state.lock();
+                if *state == ActorState::Suspended {
+                    *state = ActorState::Running;
+                }
+            }
+            SystemMessage::Watch(watcher) => {
+                // 已停止的 Actor 立即回复 Terminated
+                if self.is_stopped() {
+                    self.notify_terminated(&watcher).await;
+                } else {
+                    self.watchers.lock().push(watcher);
+                }
+            }
+            SystemMessage::Unwatch(watcher) => {
+                self.watchers.lock().retain(|w| w != &watcher);
+            }
+            SystemMessage::Terminated(who) => {
+                // 被监视的 Actor 终止，作为普通消息交给 Actor 处理
+                return self.deliver_batch(vec![Message::new(SystemMessage::Terminated(who))]).await;
+            }
+            SystemMessage::Failure(e) => {
+                log::error!("Actor {} reported failure: {}", self.id(), e);
+            }
+            SystemMessage::Stopped | SystemMessage::Started | SystemMessage::Restarting => {}
         }
+        Ok(())
     }
 
-    async fn handle_failure(&mut self, error: SendError) {
-        if let Some(ref strategy) = self.context.supervisor_strategy {
-            let stats = self.child_stats
-                .entry(self.context.self_pid.clone())
-                .or_insert_with(ChildStats::default);
-            
-            stats.record_failure();
-            
-            let directive = strategy.handle_failure(
-                &mut self.context,
-                &self.context.self_pid,
-                &ProtoError::SendError(error),
-                &stats,
-            ).await;
+    async fn invoke_user(&self, msg: Message) -> Result<(), SendError> {
+        // 经由 ActorRef 发送的系统消息与用户消息共用通道
+        let msg = match msg.payload.downcast::<SystemMessage>() {
+            Ok(system) => return self.invoke_system(*system).await,
+            Err(payload) => Message { payload, ..msg },
+        };
+        if self.state() != ActorState::Running {
+            return Err(SendError::DeadLetter);
+        }
+        let mut actor = self.actor.lock().await;
+        actor.receive(&self.context, msg).await
+    }
 
-            match directive {
-                SupervisorDirective::Resume => self.resume(),
-                SupervisorDirective::Restart => self.restart().await,
-                SupervisorDirective::Stop => self.stop().await,
-                SupervisorDirective::Escalate => {
-                    // 将错误上报给父 actor
-                    if let Some(ref parent) = self.context.parent {
-                        self.context.send(parent, Message {
-                            payload: Box::new(SystemMessage::Failure(error)),
-                            sender: Some(self.context.self_pid.clone()),
-                            header: None,
-                        }).await.ok();
-                    }
+    /// 用户消息合并后交给 `Actor::receive_batch`，系统消息在批内按原顺序处理
+    async fn invoke_batch(&self, msgs: Vec<Message>) -> Result<(), SendError> {
+        let mut pending = Vec::with_capacity(msgs.len());
+        for msg in msgs {
+            match msg.payload.downcast::<SystemMessage>() {
+                Ok(system) => {
+                    let delivered = self.deliver_batch(std::mem::take(&mut pending)).await;
+                    self.invoke_system(*system).await?;
+                    delivered?;
                 }
+                Err(payload) => pending.push(Message { payload, ..msg }),
             }
         }
+        self.deliver_batch(pending).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    struct Recorder {
+        batches: mpsc::UnboundedSender<Vec<u32>>,
+    }
+
+    #[async_trait]
+    impl Actor for Recorder {
+        async fn receive(&mut self, ctx: &Context, msg: Message) -> Result<(), SendError> {
+            self.receive_batch(ctx, vec![msg]).await
+        }
+
+        async fn receive_batch(&mut self, _ctx: &Context, msgs: Vec<Message>) -> Result<(), SendError> {
+            let values = msgs.iter().filter_map(|m| m.payload.downcast_ref::<u32>().copied()).collect();
+            let _ = self.batches.send(values);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_stops_at_stop_message() {
+        let (sender, _receiver) = mpsc::channel(8);
+        let self_ref = ActorRef::new("cell".to_string(), sender.clone());
+        let (batches, mut delivered) = mpsc::unbounded_channel();
+        let cell = ActorCell::new(Box::new(Recorder { batches }), Context::with_sender(self_ref, None, sender));
+        cell.start().await.unwrap();
+
+        let msgs = vec![Message::new(1u32), Message::new(2u32), Message::system_stop(), Message::new(3u32)];
+        assert!(cell.invoke_batch(msgs).await.is_err());
+
+        // Messages before Stop arrive as one batch, messages after it are dead letters
+        assert_eq!(delivered.recv().await.unwrap(), vec![1, 2]);
+        assert!(delivered.try_recv().is_err());
+        assert!(cell.is_stopped());
     }
-} 
\ No newline at end of file
+}
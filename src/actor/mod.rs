@@ -4,6 +4,7 @@ mod lifecycle;
 mod decorators;
 mod props;
 mod builder;
+mod cell;
 mod mock_actor;
 pub use actor_ref::ActorRef;
 pub use basic_actors::{ForwardActor, BatchActor};
@@ -11,6 +12,7 @@ pub use lifecycle::{ActorLifecycle, LifecycleAware};
 pub use decorators::{ThrottleDecorator, RetryDecorator};
 pub use props::Props;
 pub use mock_actor::MockActor;
+pub(crate) use cell::ActorCell;
 
 use async_trait::async_trait;
 use crate::context::Context;
@@ -21,6 +23,14 @@ use crate::message::Message;
 pub trait Actor: Send + 'static {
     async fn receive(&mut self, ctx: &Context, msg: Message) -> Result<(), SendError>;
 
+    /// 一次处理邮箱交付的一批消息，默认逐条调用 `receive`
+    async fn receive_batch(&mut self, ctx: &Context, msgs: Vec<Message>) -> Result<(), SendError> {
+        for msg in msgs {
+            self.receive(ctx, msg).await?;
+        }
+        Ok(())
+    }
+
     async fn started(&mut self, _ctx: &Context) -> Result<(), SendError> {
         Ok(())
     }
@@ -58,4 +68,34 @@ mod tests {
         let actor_ref = ActorRef::new();
         assert!(actor_ref.is_valid()); // 验证 ActorRef 是否有效
     }
-} 
\ No newline at end of file
+
+    struct Recorder {
+        received: Vec<u32>,
+        fail_on: u32,
+    }
+
+    #[async_trait]
+    impl Actor for Recorder {
+        async fn receive(&mut self, _ctx: &Context, msg: Message) -> Result<(), SendError> {
+            let value = *msg.payload.downcast_ref::<u32>().unwrap();
+            if value == self.fail_on {
+                return Err(SendError::MailboxClosed);
+            }
+            self.received.push(value);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_receive_batch_delivers_in_order() {
+        let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+        let ctx = Context::with_sender(ActorRef::new("recorder".to_string(), sender.clone()), None, sender);
+        let mut actor = Recorder { received: Vec::new(), fail_on: 3 };
+
+        let batch = (0..5u32).map(Message::new).collect();
+        assert!(actor.receive_batch(&ctx, batch).await.is_err());
+
+        // Messages after the failing one are not delivered
+        assert_eq!(actor.received, vec![0, 1, 2]);
+    }
+}
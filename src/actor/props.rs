@@ -1,4 +1,8 @@
+use std::sync::Arc;
+use std::time::Duration;
 use super::Actor;
+use crate::dispatcher::{BackpressureConfig, Dispatcher, DispatcherRegistry, Priority};
+use crate::errors::SpawnError;
 use crate::middleware::Middleware;
 use crate::supervisor::SupervisorStrategy;
 
@@ -17,6 +21,16 @@ pub struct Props {
     
     // 邮箱配置
     mailbox_size: usize,
+
+    // 调度优先级
+    priority: Priority,
+
+    // 邮箱背压配置
+    backpressure: Option<BackpressureConfig>,
+
+    // 单次交付的最大消息数与凑批等待时间
+    batch_size: usize,
+    batch_max_wait: Duration,
 }
 
 impl Props {
@@ -31,6 +45,10 @@ impl Props {
             supervisor_strategy: None,
             dispatcher_id: "default".to_string(),
             mailbox_size: 1000,
+            priority: Priority::default(),
+            backpressure: None,
+            batch_size: 1,
+            batch_max_wait: Duration::from_millis(0),
         }
     }
 
@@ -49,11 +67,58 @@ impl Props {
         self
     }
 
+    /// 使用独占线程的调度器运行该 Actor
+    pub fn with_pinned_dispatcher(self) -> Self {
+        self.with_dispatcher(DispatcherRegistry::PINNED)
+    }
+
+    /// 使用阻塞线程池运行该 Actor
+    pub fn with_blocking_dispatcher(self) -> Self {
+        self.with_dispatcher(DispatcherRegistry::BLOCKING)
+    }
+
+    /// 使用优先级调度器运行该 Actor，优先级由 `with_priority` 设置
+    pub fn with_priority_dispatcher(self) -> Self {
+        self.with_dispatcher(DispatcherRegistry::PRIORITY)
+    }
+
+    /// 使用支持工作窃取的线程池运行该 Actor
+    pub fn with_worker_pool_dispatcher(self) -> Self {
+        self.with_dispatcher(DispatcherRegistry::WORKER_POOL)
+    }
+
     pub fn with_mailbox_size(mut self, size: usize) -> Self {
         self.mailbox_size = size;
         self
     }
 
+    /// 设置该 Actor 邮箱在优先级调度器中的优先级
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 邮箱按该配置向发送方报告背压，队列满时拒绝消息
+    pub fn with_backpressure(mut self, config: BackpressureConfig) -> Self {
+        self.backpressure = Some(config);
+        self
+    }
+
+    /// 每次最多向 `Actor::receive_batch` 交付 `batch_size` 条消息，批次未满时最多等待 `max_wait`
+    pub fn with_batching(mut self, batch_size: usize, max_wait: Duration) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.batch_max_wait = max_wait;
+        self
+    }
+
+    pub(crate) fn backpressure(&self) -> Option<&BackpressureConfig> {
+        self.backpressure.as_ref()
+    }
+
+    pub(crate) fn priority(&self) -> Priority {
+        self.priority
+    }
+
     pub(crate) fn create_actor(&self) -> Box<dyn Actor> {
         (self.actor_producer)()
     }
@@ -62,6 +127,29 @@ impl Props {
         &self.middleware
     }
 
+    pub(crate) fn mailbox_size(&self) -> usize {
+        self.mailbox_size
+    }
+
+    pub(crate) fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    pub(crate) fn batch_max_wait(&self) -> Duration {
+        self.batch_max_wait
+    }
+
+    pub(crate) fn dispatcher_id(&self) -> &str {
+        &self.dispatcher_id
+    }
+
+    /// 在创建 Actor 时按名称解析调度器
+    pub(crate) fn resolve_dispatcher(&self, registry: &DispatcherRegistry) -> Result<Arc<dyn Dispatcher>, SpawnError> {
+        registry
+            .get(&self.dispatcher_id)
+            .ok_or_else(|| SpawnError::DispatcherNotFound(self.dispatcher_id.clone()))
+    }
+
     pub(crate) fn get_supervisor(&self) -> Option<&Box<dyn SupervisorStrategy>> {
         self.supervisor_strategy.as_ref()
     }
@@ -1,5 +1,7 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::UdpSocket;
+use super::{Cluster, ClusterError};
 
 pub struct Discovery {
     socket: UdpSocket,
@@ -1,5 +1,6 @@
 use thiserror::Error;
 use std::io;
+use crate::SendError;
 
 #[derive(Debug, Error)]
 pub enum ClusterError {
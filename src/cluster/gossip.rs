@@ -1,4 +1,10 @@
+use std::sync::Arc;
+use async_trait::async_trait;
 use rand::seq::SliceRandom;
+use crate::{Actor, Context, Message, SendError};
+use super::Cluster;
+use super::membership::Member;
+use super::messages::GossipState;
 
 pub struct GossipActor {
     cluster: Arc<Cluster>,
@@ -1,6 +1,10 @@
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::sync::Arc;
+use std::time::Instant;
+use async_trait::async_trait;
+use crate::{Actor, Context, Message, SendError};
+use super::{Cluster, FailureDetector};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MemberStatus {
@@ -18,6 +22,13 @@ pub struct Member {
     pub labels: HashMap<String, String>,
 }
 
+impl Member {
+    /// 分区分配中的权重，取自 `weight` 标签，默认为 1
+    pub fn weight(&self) -> u64 {
+        self.labels.get("weight").and_then(|w| w.parse().ok()).unwrap_or(1)
+    }
+}
+
 pub struct MembershipActor {
     cluster: Arc<Cluster>,
     failure_detector: FailureDetector,
@@ -29,6 +29,20 @@ pub enum ClusterMessage {
     PartitionRebalance {
         partitions: Vec<Partition>,
     },
+    // 分区迁移：源节点准备迁出，目标节点复制数据，最后广播所有权切换
+    PrepareMigration {
+        partition_id: String,
+        to: String,
+    },
+    CopyPartitionData {
+        partition_id: String,
+        from: String,
+    },
+    SwitchPartitionOwnership {
+        partition_id: String,
+        from: String,
+        to: String,
+    },
 
     // Gossip 消息
     GossipState {
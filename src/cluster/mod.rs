@@ -1,12 +1,21 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use dashmap::DashMap;
-use crate::{Actor, Context, Message, Pid, SendError};
+use crate::Context;
 
 pub mod membership;
 pub mod partition;
 pub mod discovery;
 pub mod gossip;
+pub mod messages;
+mod error;
+mod failure_detector;
+
+pub use error::ClusterError;
+pub use failure_detector::FailureDetector;
 
 use membership::{Member, MemberStatus};
 use partition::{Partition, PartitionActor};
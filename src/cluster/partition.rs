@@ -1,9 +1,13 @@
 use std::collections::HashMap;
-use consistent_hash_ring::ConsistentHashRing;
-use protoactor::prelude::*;
-use protoactor::cluster::messages::ClusterMessage;
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use crate::{Actor, ActorSystem, Context, Message, Pid, Props, SendError};
+use crate::cluster::Cluster;
+use crate::cluster::messages::ClusterMessage;
+use crate::routing::HashRing;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Partition {
     pub id: String,
     pub owner: String,
@@ -11,39 +15,60 @@ pub struct Partition {
     pub actors: HashMap<String, Pid>,
 }
 
+/// 各节点上分区 Actor 的固定标识，迁移消息发送到这里
+pub const PARTITION_ACTOR_ID: &str = "$partition";
+
+/// 在分区中放置一个 actor，回复其 `Pid`
+#[derive(Debug, Clone)]
+pub struct PlaceActor {
+    pub id: String,
+}
+
+/// 按成员权重重新分配分区
+#[derive(Debug, Clone, Copy)]
+pub struct Rebalance;
+
 pub struct PartitionActor {
     cluster: Arc<Cluster>,
     partition: Partition,
-    ring: ConsistentHashRing<String>,
+    ring: HashRing,
+    system: Arc<ActorSystem>,
+    // 放置到本节点的 actor 按它创建
+    props: Arc<Props>,
 }
 
 impl PartitionActor {
-    pub fn new(cluster: Arc<Cluster>, partition: Partition) -> Self {
-        let mut ring = ConsistentHashRing::new();
-        ring.add_node(partition.owner.clone());
+    pub fn new(cluster: Arc<Cluster>, partition: Partition, system: Arc<ActorSystem>, props: Arc<Props>) -> Self {
+        let mut ring = HashRing::new();
+        ring.add_node(&partition.owner);
         for replica in &partition.replicas {
-            ring.add_node(replica.clone());
+            ring.add_node(replica);
         }
 
         Self {
             cluster,
             partition,
             ring,
+            system,
+            props,
         }
     }
 
     async fn handle_actor_placement(&mut self, actor_id: String) -> Result<Pid, SendError> {
-        let node = self.ring.get_node(&actor_id)
+        let node = self.ring.get_node(actor_id.as_bytes())
+            .map(str::to_string)
             .ok_or(SendError::NoRoutee)?;
             
         if node == self.partition.owner {
             // 在本地创建 actor
-            let props = Props::new(move || {
-                // 创建 actor 实例
-                Box::new(YourActor::new())
-            });
-            
-            let pid = ctx.spawn(props)?;
+            let actor = self.system.spawn(&self.props).map_err(|e| {
+                log::error!("Failed to place actor {}: {}", actor_id, e);
+                SendError::NoRoutee
+            })?;
+            let pid = Pid {
+                address: self.partition.owner.clone(),
+                id: actor.id().to_string(),
+            };
             self.partition.actors.insert(actor_id, pid.clone());
             Ok(pid)
         } else {
@@ -51,17 +76,23 @@ impl PartitionActor {
             let member = self.cluster.get_member(&node)
                 .ok_or(SendError::NoRoutee)?;
                 
-            let remote_pid = RemotePid::new(member.host, member.port, actor_id);
+            let remote_pid = Pid {
+                address: format!("{}:{}", member.host, member.port),
+                id: actor_id,
+            };
             Ok(remote_pid)
         }
     }
 
-    async fn handle_rebalance(&mut self) {
+    async fn handle_rebalance(&mut self) -> Result<(), SendError> {
         // 计算理想分布
         let members = self.cluster.get_members();
         let total_weight: u64 = members.iter()
             .map(|m| m.weight())
             .sum();
+        if total_weight == 0 {
+            return Ok(());
+        }
 
         // 计算每个节点应该拥有的分区数量
         let mut target_distribution = HashMap::new();
@@ -71,17 +102,26 @@ impl PartitionActor {
             target_distribution.insert(member.id.clone(), target);
         }
 
+        // 每个节点当前拥有的分区数量
+        let mut current_distribution: HashMap<String, usize> = HashMap::new();
+        for partition in self.cluster.partitions.iter() {
+            *current_distribution.entry(partition.owner.clone()).or_default() += 1;
+        }
+
         // 计算需要移动的分区
         let mut moves = Vec::new();
-        for (partition_id, partition) in self.cluster.partitions.iter() {
+        for partition in self.cluster.partitions.iter() {
             let current_owner = partition.owner.clone();
-            let current_count = self.cluster.get_member_partition_count(&current_owner);
+            let current_count = current_distribution.get(&current_owner).copied().unwrap_or(0);
             
             if let Some(&target_count) = target_distribution.get(&current_owner) {
                 if current_count > target_count {
                     // 寻找负载最小的节点
-                    if let Some(new_owner) = self.find_least_loaded_member(&target_distribution) {
-                        moves.push((partition_id, current_owner, new_owner));
+                    if let Some(new_owner) = find_least_loaded_member(&target_distribution, &current_distribution) {
+                        // 计入已安排的移动，避免所有分区都移到同一个节点
+                        current_distribution.insert(current_owner.clone(), current_count - 1);
+                        *current_distribution.entry(new_owner.clone()).or_default() += 1;
+                        moves.push((partition.key().clone(), current_owner, new_owner));
                     }
                 }
             }
@@ -91,6 +131,7 @@ impl PartitionActor {
         for (partition_id, from, to) in moves {
             self.migrate_partition(partition_id, from, to).await?;
         }
+        Ok(())
     }
 
     async fn migrate_partition(
@@ -123,20 +164,60 @@ impl PartitionActor {
 
         Ok(())
     }
+
+    /// 把集群消息发送给成员节点上的分区 Actor
+    async fn send_to_member(&self, member_id: &str, msg: ClusterMessage) -> Result<(), SendError> {
+        let member = self.cluster.get_member(member_id)
+            .ok_or(SendError::NoRoutee)?;
+        let remote = self.system.remote()
+            .ok_or(SendError::ConnectionFailed)?;
+        let target = Pid {
+            address: format!("{}:{}", member.host, member.port),
+            id: PARTITION_ACTOR_ID.to_string(),
+        };
+        remote.send(&target, Message::new(msg)).await
+    }
+
+    async fn broadcast_to_cluster(&self, msg: ClusterMessage) -> Result<(), SendError> {
+        for member in self.cluster.get_members() {
+            self.send_to_member(&member.id, msg.clone()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// 当前分区数量比目标少得最多的节点
+fn find_least_loaded_member(
+    target_distribution: &HashMap<String, usize>,
+    current_distribution: &HashMap<String, usize>,
+) -> Option<String> {
+    target_distribution
+        .iter()
+        .map(|(id, &target)| {
+            let current = current_distribution.get(id).copied().unwrap_or(0);
+            (id, target as i64 - current as i64)
+        })
+        .filter(|(_, deficit)| *deficit > 0)
+        .max_by_key(|(_, deficit)| *deficit)
+        .map(|(id, _)| id.clone())
 }
 
 #[async_trait]
 impl Actor for PartitionActor {
-    async fn receive(&mut self, ctx: &Context, msg: Message) -> Result<(), SendError> {
-        match msg {
-            Message::PlaceActor { id } => {
-                self.handle_actor_placement(id).await?;
-            }
-            Message::Rebalance => {
-                self.handle_rebalance().await;
+    async fn receive(&mut self, _ctx: &Context, msg: Message) -> Result<(), SendError> {
+        let msg = match msg.payload.downcast::<PlaceActor>() {
+            Ok(place) => {
+                let pid = self.handle_actor_placement(place.id).await?;
+                if let Some(sender) = msg.sender {
+                    sender.send(Message::new(pid)).await?;
+                }
+                return Ok(());
             }
-            _ => {}
+            Err(payload) => Message { payload, ..msg },
+        };
+        if msg.payload.is::<Rebalance>() {
+            return self.handle_rebalance().await;
         }
         Ok(())
     }
-} 
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
 use std::time::Duration;
-use crate::dispatcher::ThreadPoolDispatcher;
+use crate::dispatcher::{DispatcherRegistry, ThreadPoolDispatcher};
 
 #[derive(Clone)]
 pub struct SystemConfig {
@@ -15,7 +15,8 @@ impl Default for SystemConfig {
         Self {
             host: "127.0.0.1".to_string(),
             port: 0,
-            dispatcher: ThreadPoolDispatcher::new(num_cpus::get()),
+            // 注册为默认调度器，指标标签与注册名一致
+            dispatcher: ThreadPoolDispatcher::with_name(DispatcherRegistry::DEFAULT, num_cpus::get()),
             deadletter_timeout: Duration::from_secs(5),
             shutdown_timeout: Duration::from_secs(10),
         }
@@ -1,6 +1,6 @@
 use tokio::sync::mpsc;
 use crate::actor::{Actor, ActorRef};
-use crate::message::Message;
+use crate::message::{Message, SystemMessage};
 use crate::errors::SendError;
 use std::sync::Arc;
 use crate::dispatcher::Dispatcher;
@@ -39,6 +39,17 @@ impl Context {
         }
     }
 
+    /// Creates a Context for an actor whose mailbox is the given channel
+    pub(crate) fn with_sender(self_ref: ActorRef, parent: Option<ActorRef>, sender: mpsc::Sender<Message>) -> Self {
+        Self {
+            self_ref,
+            parent,
+            children: Vec::new(),
+            sender,
+            stopping: false,
+        }
+    }
+
     /// Returns a reference to self as an actor
     pub fn self_ref(&self) -> &ActorRef {
         &self.self_ref
@@ -49,6 +60,16 @@ impl Context {
         target.send(msg).await
     }
 
+    /// Watches another actor, a `Terminated` message is delivered when it stops
+    pub async fn watch(&self, target: &ActorRef) -> Result<(), SendError> {
+        target.send(Message::new(SystemMessage::Watch(self.self_ref.clone()))).await
+    }
+
+    /// Stops watching another actor
+    pub async fn unwatch(&self, target: &ActorRef) -> Result<(), SendError> {
+        target.send(Message::new(SystemMessage::Unwatch(self.self_ref.clone()))).await
+    }
+
     /// Spawns a new child actor
     pub async fn spawn<A: Actor>(&mut self, actor: A) -> Result<ActorRef, SendError> {
         // TODO: Implement actor spawning logic
@@ -1,5 +1,5 @@
-use crate::actor::ActorRef;
-use crate::errors::SendError;
+use crate::actor::{ActorRef, Props};
+use crate::errors::{SendError, SpawnError};
 use crate::message::Message;
 use crate::system::ActorSystem;
 use std::sync::Arc;
@@ -13,6 +13,10 @@ impl RootContext {
         Self { system }
     }
 
+    pub fn spawn(&self, props: &Props) -> Result<ActorRef, SpawnError> {
+        self.system.spawn(props)
+    }
+
     pub async fn send(&self, target: &ActorRef, msg: Message) -> Result<(), SendError> {
         target.send(msg).await
     }
@@ -0,0 +1,246 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TryRecvError;
+use crate::actor::ActorCell;
+use crate::errors::SendError;
+use crate::mailbox::MessageInvoker;
+use crate::message::Message;
+use crate::metrics::DispatcherMetrics;
+use super::{measure, BackpressureController, Priority};
+
+/// 交给调度器运行的 Actor 邮箱，消息经由 `ActorCell` 交付给 Actor
+pub struct ActorMailbox {
+    cell: Arc<ActorCell>,
+    receiver: mpsc::Receiver<Message>,
+    backpressure: Option<Arc<BackpressureController>>,
+    batch_size: usize,
+    batch_max_wait: Duration,
+    /// 每次交付在阻塞线程上执行
+    blocking: bool,
+    /// 按邮箱调度的调度器使用的优先级
+    priority: Priority,
+}
+
+impl ActorMailbox {
+    pub(crate) fn new(cell: Arc<ActorCell>, receiver: mpsc::Receiver<Message>) -> Self {
+        Self {
+            cell,
+            receiver,
+            backpressure: None,
+            batch_size: 1,
+            batch_max_wait: Duration::from_millis(0),
+            blocking: false,
+            priority: Priority::default(),
+        }
+    }
+
+    /// 发送方占用的背压位置在消息处理后归还
+    pub(crate) fn with_backpressure(mut self, backpressure: Option<Arc<BackpressureController>>) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    pub(crate) fn with_batching(mut self, batch_size: usize, batch_max_wait: Duration) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.batch_max_wait = batch_max_wait;
+        self
+    }
+
+    pub(crate) fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// 邮箱循环留在异步运行时上，每次交付通过 `spawn_blocking` 执行
+    pub(crate) fn on_blocking_threads(mut self) -> Self {
+        self.blocking = true;
+        self
+    }
+
+    /// 所属 Actor 的标识
+    pub fn id(&self) -> &str {
+        self.cell.id()
+    }
+
+    pub(crate) fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub(crate) fn backpressure(&self) -> Option<Arc<BackpressureController>> {
+        self.backpressure.clone()
+    }
+
+    pub(crate) fn invoker(&self) -> Arc<dyn MessageInvoker> {
+        self.cell.clone()
+    }
+
+    /// 启动 Actor 后把收到的消息转交给 `forward`，由调度器自己的队列交付，Actor 停止后返回
+    ///
+    /// 转交失败的消息被丢弃并归还其背压位置，转交成功的消息由处理方归还。
+    pub(crate) async fn forward<F>(mut self, mut forward: F)
+    where
+        F: FnMut(Message) -> Result<(), SendError> + Send,
+    {
+        if let Err(e) = self.cell.start().await {
+            log::error!("Failed to start actor {}: {}", self.id(), e);
+            return;
+        }
+
+        loop {
+            let msg = tokio::select! {
+                msg = self.receiver.recv() => msg,
+                _ = self.cell.stopped() => None,
+            };
+            let msg = match msg {
+                Some(msg) => msg,
+                None => break,
+            };
+            if let Err(e) = forward(msg) {
+                log::warn!("Dropping message for actor {}: {}", self.id(), e);
+                self.release(1);
+            }
+        }
+
+        self.cell.stop().await;
+    }
+
+    /// 启动 Actor 并交付消息，Actor 停止后返回；指标按消息记录，与 Actor 存活时间无关
+    pub(crate) async fn run(mut self, metrics: Option<Arc<DispatcherMetrics>>) {
+        if let Err(e) = self.cell.start().await {
+            log::error!("Failed to start actor {}: {}", self.id(), e);
+            return;
+        }
+
+        while let Some(batch) = self.next_batch().await {
+            let count = batch.len();
+            if let Some(metrics) = &metrics {
+                for _ in 0..count {
+                    metrics.record_task_queued();
+                }
+            }
+            let result = if self.blocking {
+                measure(metrics.as_deref(), count, self.invoke_blocking(batch)).await
+            } else {
+                measure(metrics.as_deref(), count, Self::invoke(&self.cell, self.batch_size, batch)).await
+            };
+            if let Err(e) = result {
+                log::error!("Actor {} failed to handle message: {}", self.id(), e);
+            }
+            self.release(count);
+            if self.cell.is_stopped() {
+                break;
+            }
+        }
+
+        self.cell.stop().await;
+    }
+
+    /// 启用批量交付时整批交给 Actor，否则逐条交付
+    async fn invoke(cell: &ActorCell, batch_size: usize, batch: Vec<Message>) -> Result<(), SendError> {
+        if batch_size > 1 {
+            return cell.invoke_batch(batch).await;
+        }
+        for msg in batch {
+            cell.invoke_user(msg).await?;
+        }
+        Ok(())
+    }
+
+    /// 在阻塞线程上交付，阻塞线程仍处于运行时上下文中，可以驱动处理函数内的 IO 与定时器
+    fn invoke_blocking(&self, batch: Vec<Message>) -> impl Future<Output = Result<(), SendError>> {
+        let cell = Arc::clone(&self.cell);
+        let batch_size = self.batch_size;
+        let handle = tokio::task::spawn_blocking(move || {
+            Handle::current().block_on(Self::invoke(&cell, batch_size, batch))
+        });
+        async move {
+            match handle.await {
+                Ok(result) => result,
+                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                Err(_) => Err(SendError::MailboxClosed),
+            }
+        }
+    }
+
+    /// 归还已处理消息占用的背压位置
+    fn release(&self, count: usize) {
+        if let Some(controller) = &self.backpressure {
+            for _ in 0..count {
+                controller.release();
+            }
+        }
+    }
+
+    /// 等待下一条消息，启用批量交付时在 `batch_max_wait` 内继续凑批
+    async fn next_batch(&mut self) -> Option<Vec<Message>> {
+        let first = self.receiver.recv().await?;
+        let mut batch = Vec::with_capacity(self.batch_size);
+        batch.push(first);
+
+        let deadline = tokio::time::Instant::now() + self.batch_max_wait;
+        while batch.len() < self.batch_size {
+            match self.receiver.try_recv() {
+                Ok(msg) => batch.push(msg),
+                Err(TryRecvError::Empty) => {
+                    match tokio::time::timeout_at(deadline, self.receiver.recv()).await {
+                        Ok(Some(msg)) => batch.push(msg),
+                        _ => break,
+                    }
+                }
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        Some(batch)
+    }
+}
+
+/// 创建未启动的 Actor 邮箱及其引用，供调度器测试使用
+#[cfg(test)]
+pub(crate) fn test_mailbox<A: crate::actor::Actor>(actor: A) -> (ActorMailbox, crate::actor::ActorRef) {
+    let (sender, receiver) = mpsc::channel(16);
+    let actor_ref = crate::actor::ActorRef::new(uuid::Uuid::new_v4().to_string(), sender.clone());
+    let context = crate::context::Context::with_sender(actor_ref.clone(), None, sender);
+    let cell = Arc::new(ActorCell::new(Box::new(actor), context));
+    (ActorMailbox::new(cell, receiver), actor_ref)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use crate::actor::Actor;
+    use crate::context::Context;
+    use crate::dispatcher::{Dispatcher, ThreadPoolDispatcher};
+
+    struct Noop;
+
+    #[async_trait]
+    impl Actor for Noop {
+        async fn receive(&mut self, _ctx: &Context, _msg: Message) -> Result<(), SendError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_metrics_count_messages_not_actor_lifetime() {
+        let dispatcher = ThreadPoolDispatcher::with_name("mailbox-test", 1);
+        let (mailbox, actor) = test_mailbox(Noop);
+        let running = dispatcher.attach(mailbox);
+
+        futures::executor::block_on(async {
+            actor.send(Message::new(1)).await.unwrap();
+            actor.send(Message::new(2)).await.unwrap();
+            actor.stop().await;
+            running.await.unwrap();
+        });
+
+        // Two user messages and the Stop message, each recorded on its own
+        let stats = dispatcher.metrics().unwrap().snapshot();
+        assert_eq!(stats.completed_tasks, 3);
+        assert_eq!(stats.active_tasks, 0);
+        assert_eq!(stats.queued_tasks, 0);
+    }
+}
@@ -1,7 +1,7 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
-use crate::SendError;
+use crate::eventstream::EventStream;
 
 #[derive(Debug, Clone)]
 pub struct BackpressureConfig {
@@ -24,59 +24,209 @@ impl Default for BackpressureConfig {
     }
 }
 
+/// 发送方收到的背压信号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressureSignal {
+    /// 正常发送
+    Ok = 0,
+    /// 队列持续高于高水位，发送方应降速
+    SlowDown = 1,
+    /// 队列已满，消息被拒绝
+    Rejected = 2,
+}
+
+impl BackpressureSignal {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => BackpressureSignal::SlowDown,
+            2 => BackpressureSignal::Rejected,
+            _ => BackpressureSignal::Ok,
+        }
+    }
+}
+
+/// 背压状态变化时发布到事件流的事件
+#[derive(Debug, Clone)]
+pub struct BackpressureChanged {
+    pub source: String,
+    pub previous: BackpressureSignal,
+    pub current: BackpressureSignal,
+    pub load: f64,
+}
+
+/// 根据队列水位计算背压信号
+///
+/// 邮箱与调度器在入队时调用 `try_acquire`，处理完一条消息后调用 `release`。
 pub struct BackpressureController {
+    source: String,
     config: BackpressureConfig,
     current_size: AtomicUsize,
-    pressure_start: RwLock<Option<Instant>>,
-    is_pressured: AtomicBool,
+    pressure_start: Mutex<Option<Instant>>,
+    signal: AtomicU8,
+    event_stream: Option<Arc<EventStream>>,
 }
 
 impl BackpressureController {
     pub fn new(config: BackpressureConfig) -> Self {
         Self {
+            source: String::new(),
             config,
             current_size: AtomicUsize::new(0),
-            pressure_start: RwLock::new(None),
-            is_pressured: AtomicBool::new(false),
+            pressure_start: Mutex::new(None),
+            signal: AtomicU8::new(BackpressureSignal::Ok as u8),
+            event_stream: None,
         }
     }
 
-    pub async fn try_acquire(&self) -> Result<(), SendError> {
-        let current = self.current_size.load(Ordering::Relaxed);
-        
-        if current >= self.config.max_queue_size {
-            return Err(SendError::MailboxFull);
-        }
+    /// 设置事件中标识压力来源的名称
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = source.into();
+        self
+    }
 
-        if current as f64 >= self.config.max_queue_size as f64 * self.config.high_watermark {
-            let mut pressure_start = self.pressure_start.write().await;
-            if pressure_start.is_none() {
-                *pressure_start = Some(Instant::now());
-            }
+    /// 将状态变化发布到事件流
+    pub fn with_event_stream(mut self, event_stream: Arc<EventStream>) -> Self {
+        self.event_stream = Some(event_stream);
+        self
+    }
 
-            if pressure_start.unwrap().elapsed() > self.config.pressure_window {
-                self.is_pressured.store(true, Ordering::Release);
-                return Err(SendError::BackPressure);
+    /// 尝试占用一个队列位置，返回 `Rejected` 时不占用
+    pub fn try_acquire(&self) -> BackpressureSignal {
+        // 检查与占用必须是同一次原子操作，否则并发发送方会越过上限
+        let mut current = self.current_size.load(Ordering::Relaxed);
+        loop {
+            if current >= self.config.max_queue_size {
+                return self.transition(BackpressureSignal::Rejected, current);
+            }
+            match self.current_size.compare_exchange_weak(current, current + 1, Ordering::AcqRel, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(actual) => current = actual,
             }
-        } else if current as f64 <= self.config.max_queue_size as f64 * self.config.low_watermark {
-            *self.pressure_start.write().await = None;
-            self.is_pressured.store(false, Ordering::Release);
         }
 
-        self.current_size.fetch_add(1, Ordering::Relaxed);
-        Ok(())
+        let current = current + 1;
+        let signal = self.evaluate(current);
+        self.transition(signal, current)
     }
 
+    /// 释放一个队列位置
     pub fn release(&self) {
-        self.current_size.fetch_sub(1, Ordering::Relaxed);
+        let previous = self.current_size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| Some(n.saturating_sub(1)))
+            .unwrap_or(0);
+        let current = previous.saturating_sub(1);
+        let signal = self.evaluate(current);
+        self.transition(signal, current);
+    }
+
+    /// 当前信号
+    pub fn signal(&self) -> BackpressureSignal {
+        BackpressureSignal::from_u8(self.signal.load(Ordering::Acquire))
     }
 
     pub fn is_under_pressure(&self) -> bool {
-        self.is_pressured.load(Ordering::Acquire)
+        self.signal() != BackpressureSignal::Ok
     }
 
     pub fn current_load(&self) -> f64 {
         let current = self.current_size.load(Ordering::Relaxed);
         current as f64 / self.config.max_queue_size as f64
     }
-} 
\ No newline at end of file
+
+    /// 高水位需持续 pressure_window 才进入降速，降到低水位以下才恢复
+    fn evaluate(&self, current: usize) -> BackpressureSignal {
+        let max = self.config.max_queue_size as f64;
+        let load = current as f64;
+
+        if current >= self.config.max_queue_size {
+            return BackpressureSignal::Rejected;
+        }
+
+        let mut pressure_start = self.pressure_start.lock().unwrap();
+        if load >= max * self.config.high_watermark {
+            let started = *pressure_start.get_or_insert_with(Instant::now);
+            if started.elapsed() >= self.config.pressure_window {
+                return BackpressureSignal::SlowDown;
+            }
+        } else if load <= max * self.config.low_watermark {
+            *pressure_start = None;
+            return BackpressureSignal::Ok;
+        }
+
+        // 处于两个水位之间时保持已有的降速状态
+        match self.signal() {
+            BackpressureSignal::Ok => BackpressureSignal::Ok,
+            _ => BackpressureSignal::SlowDown,
+        }
+    }
+
+    fn transition(&self, signal: BackpressureSignal, current: usize) -> BackpressureSignal {
+        let previous = BackpressureSignal::from_u8(self.signal.swap(signal as u8, Ordering::AcqRel));
+        if previous != signal {
+            log::debug!("Backpressure on {} changed from {:?} to {:?}", self.source, previous, signal);
+            if let Some(event_stream) = &self.event_stream {
+                event_stream.publish(BackpressureChanged {
+                    source: self.source.clone(),
+                    previous,
+                    current: signal,
+                    load: current as f64 / self.config.max_queue_size as f64,
+                });
+            }
+        }
+        signal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> BackpressureController {
+        BackpressureController::new(BackpressureConfig {
+            max_queue_size: 10,
+            high_watermark: 0.8,
+            low_watermark: 0.5,
+            pressure_window: Duration::from_millis(0),
+            sampling_interval: Duration::from_millis(10),
+        })
+    }
+
+    #[test]
+    fn test_signal_follows_watermarks() {
+        let controller = controller();
+        let signals: Vec<_> = (0..10).map(|_| controller.try_acquire()).collect();
+        assert_eq!(signals[6], BackpressureSignal::Ok);
+        assert_eq!(signals[7], BackpressureSignal::SlowDown);
+
+        // Full queues reject without taking a slot
+        assert_eq!(controller.try_acquire(), BackpressureSignal::Rejected);
+        assert_eq!(controller.current_load(), 1.0);
+
+        // Stays slowed down between the watermarks, recovers below the low one
+        for _ in 0..3 {
+            controller.release();
+        }
+        assert_eq!(controller.signal(), BackpressureSignal::SlowDown);
+        for _ in 0..2 {
+            controller.release();
+        }
+        assert_eq!(controller.signal(), BackpressureSignal::Ok);
+    }
+
+    #[test]
+    fn test_concurrent_acquire_never_exceeds_capacity() {
+        let controller = Arc::new(controller());
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let controller = Arc::clone(&controller);
+                std::thread::spawn(move || {
+                    (0..10).filter(|_| controller.try_acquire() != BackpressureSignal::Rejected).count()
+                })
+            })
+            .collect();
+
+        let acquired: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(acquired, 10);
+        assert_eq!(controller.current_load(), 1.0);
+    }
+}
@@ -1,52 +1,304 @@
-use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
-use crate::{Context, Message, SendError};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures::future::BoxFuture;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Instant};
+use crate::errors::SendError;
+use crate::mailbox::MessageInvoker;
+use crate::message::Message;
 
-pub(crate) struct BatchProcessor {
-    max_batch_size: usize,
-    max_wait_time: Duration,
-    sender: mpsc::UnboundedSender<(Context, Message)>,
+enum BatchCommand<C> {
+    Message(C, Message),
+    Flush,
+    Shutdown(oneshot::Sender<()>),
 }
 
-impl BatchProcessor {
-    pub fn new(max_batch_size: usize, max_wait_time: Duration) -> Self {
-        let (sender, mut receiver) = mpsc::unbounded_channel();
-        
-        tokio::spawn(async move {
-            let mut batch = Vec::with_capacity(max_batch_size);
-            let mut last_process = Instant::now();
+/// 批次的交付目标，按 `key` 分组
+trait BatchTarget: Send + 'static {
+    fn key(&self) -> String;
+
+    fn deliver(&self, msgs: Vec<Message>) -> BoxFuture<'_, Result<(), SendError>>;
+}
 
-            while let Some((ctx, msg)) = receiver.recv().await {
-                batch.push((ctx, msg));
+impl BatchTarget for Arc<dyn MessageInvoker> {
+    fn key(&self) -> String {
+        self.id().to_string()
+    }
+
+    fn deliver(&self, msgs: Vec<Message>) -> BoxFuture<'_, Result<(), SendError>> {
+        self.invoke_batch(msgs)
+    }
+}
 
-                let should_process = batch.len() >= max_batch_size || 
-                    last_process.elapsed() >= max_wait_time;
+/// 按目标 Actor 分组的待处理消息，组间保持首次到达的顺序
+struct PendingBatch<C> {
+    groups: Vec<(C, Vec<Message>)>,
+    index: HashMap<String, usize>,
+    len: usize,
+}
 
-                if should_process {
-                    Self::process_batch(&mut batch).await;
-                    last_process = Instant::now();
-                }
+impl<C> PendingBatch<C> {
+    fn new() -> Self {
+        Self {
+            groups: Vec::new(),
+            index: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, key: &str, ctx: C, msg: Message) {
+        match self.index.get(key) {
+            Some(&i) => self.groups[i].1.push(msg),
+            None => {
+                self.index.insert(key.to_string(), self.groups.len());
+                self.groups.push((ctx, vec![msg]));
             }
-        });
+        }
+        self.len += 1;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn take(&mut self) -> Vec<(C, Vec<Message>)> {
+        self.index.clear();
+        self.len = 0;
+        std::mem::take(&mut self.groups)
+    }
+}
+
+/// 批量调度器，批次达到上限或等待超时（以先到者为准）时刷新
+///
+/// 同一 Actor 的消息在一次调度中通过 `receive_batch` 交付；关闭时保证刷新剩余消息。
+pub struct BatchProcessor {
+    max_batch_size: usize,
+    max_wait_time: Duration,
+    sender: mpsc::UnboundedSender<BatchCommand<Arc<dyn MessageInvoker>>>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl BatchProcessor {
+    pub fn new(max_batch_size: usize, max_wait_time: Duration) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(Self::run(receiver, max_batch_size.max(1), max_wait_time));
 
         Self {
             max_batch_size,
             max_wait_time,
             sender,
+            handle: Mutex::new(Some(handle)),
         }
     }
 
-    async fn process_batch(batch: &mut Vec<(Context, Message)>) {
-        for (ctx, msg) in batch.drain(..) {
-            if let Err(e) = ctx.receive_message(msg).await {
-                log::error!("Failed to process batched message: {:?}", e);
+    async fn run<C: BatchTarget>(
+        mut receiver: mpsc::UnboundedReceiver<BatchCommand<C>>,
+        max_batch_size: usize,
+        max_wait_time: Duration,
+    ) {
+        let mut pending = PendingBatch::new();
+        // 批次中第一条消息到达时开始计时
+        let mut deadline: Option<Instant> = None;
+
+        loop {
+            let command = match deadline {
+                Some(at) => tokio::select! {
+                    command = receiver.recv() => command,
+                    _ = sleep_until(at) => {
+                        Self::process_batch(&mut pending).await;
+                        deadline = None;
+                        continue;
+                    }
+                },
+                None => receiver.recv().await,
+            };
+
+            match command {
+                Some(BatchCommand::Message(ctx, msg)) => {
+                    if pending.is_empty() {
+                        deadline = Some(Instant::now() + max_wait_time);
+                    }
+                    let key = ctx.key();
+                    pending.push(&key, ctx, msg);
+
+                    if pending.len >= max_batch_size {
+                        Self::process_batch(&mut pending).await;
+                        deadline = None;
+                    }
+                }
+                Some(BatchCommand::Flush) => {
+                    Self::process_batch(&mut pending).await;
+                    deadline = None;
+                }
+                Some(BatchCommand::Shutdown(done)) => {
+                    Self::process_batch(&mut pending).await;
+                    let _ = done.send(());
+                    break;
+                }
+                // 所有发送端都已释放
+                None => {
+                    Self::process_batch(&mut pending).await;
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn process_batch<C: BatchTarget>(pending: &mut PendingBatch<C>) {
+        for (ctx, msgs) in pending.take() {
+            if let Err(e) = ctx.deliver(msgs).await {
+                log::error!("Failed to process batched messages: {:?}", e);
             }
         }
     }
 
-    pub fn send(&self, ctx: Context, msg: Message) -> Result<(), SendError> {
+    pub fn send(&self, invoker: Arc<dyn MessageInvoker>, msg: Message) -> Result<(), SendError> {
         self.sender
-            .send((ctx, msg))
-            .map_err(|_| SendError::MailboxFull)
+            .send(BatchCommand::Message(invoker, msg))
+            .map_err(|_| SendError::MailboxClosed)
     }
-} 
\ No newline at end of file
+
+    /// 立即刷新当前批次
+    pub fn flush(&self) -> Result<(), SendError> {
+        self.sender
+            .send(BatchCommand::Flush)
+            .map_err(|_| SendError::MailboxClosed)
+    }
+
+    /// 刷新剩余消息后停止
+    pub async fn shutdown(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.sender.send(BatchCommand::Shutdown(done_tx)).is_ok() {
+            let _ = done_rx.await;
+        }
+        let handle = self.handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            let _ = handle.await;
+        }
+    }
+
+    pub fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    pub fn max_wait_time(&self) -> Duration {
+        self.max_wait_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_batch_groups_by_actor() {
+        let mut pending = PendingBatch::new();
+        pending.push("a", "a", Message::new(1));
+        pending.push("b", "b", Message::new(2));
+        pending.push("a", "a", Message::new(3));
+        assert_eq!(pending.len, 3);
+
+        let groups = pending.take();
+        let sizes: Vec<_> = groups.iter().map(|(key, msgs)| (*key, msgs.len())).collect();
+        assert_eq!(sizes, vec![("a", 2), ("b", 1)]);
+        assert!(pending.is_empty());
+    }
+
+    /// 记录每次交付的批次
+    #[derive(Clone)]
+    struct Recorder {
+        key: &'static str,
+        delivered: Arc<Mutex<Vec<Vec<i32>>>>,
+    }
+
+    impl BatchTarget for Recorder {
+        fn key(&self) -> String {
+            self.key.to_string()
+        }
+
+        fn deliver(&self, msgs: Vec<Message>) -> BoxFuture<'_, Result<(), SendError>> {
+            let values = msgs.iter().map(|m| *m.payload.downcast_ref::<i32>().unwrap()).collect();
+            self.delivered.lock().unwrap().push(values);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn start(max_batch_size: usize) -> (mpsc::UnboundedSender<BatchCommand<Recorder>>, Recorder, JoinHandle<()>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(BatchProcessor::run(receiver, max_batch_size, Duration::from_millis(10)));
+        let recorder = Recorder { key: "a", delivered: Arc::new(Mutex::new(Vec::new())) };
+        (sender, recorder, handle)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timer_flushes_partial_batch() {
+        let (sender, recorder, _handle) = start(3);
+        for i in 0..2 {
+            sender.send(BatchCommand::Message(recorder.clone(), Message::new(i))).unwrap();
+        }
+
+        tokio::time::sleep(Duration::from_millis(9)).await;
+        assert!(recorder.delivered.lock().unwrap().is_empty());
+
+        // The batch is below max_batch_size, so only max_wait_time flushes it
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        assert_eq!(*recorder.delivered.lock().unwrap(), vec![vec![0, 1]]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_flushes_pending_messages() {
+        let (sender, recorder, handle) = start(3);
+        sender.send(BatchCommand::Message(recorder.clone(), Message::new(7))).unwrap();
+
+        let (done_tx, done_rx) = oneshot::channel();
+        sender.send(BatchCommand::Shutdown(done_tx)).unwrap();
+        let started = Instant::now();
+        done_rx.await.unwrap();
+        handle.await.unwrap();
+
+        // Flushed immediately, without waiting for the timer
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(*recorder.delivered.lock().unwrap(), vec![vec![7]]);
+    }
+
+    /// 记录每次批量交付的 Actor 邮箱
+    struct BatchInvoker {
+        delivered: Mutex<Vec<Vec<i32>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageInvoker for BatchInvoker {
+        fn id(&self) -> &str {
+            "batch"
+        }
+
+        async fn invoke_system(&self, _msg: crate::message::SystemMessage) -> Result<(), SendError> {
+            Ok(())
+        }
+
+        async fn invoke_user(&self, msg: Message) -> Result<(), SendError> {
+            self.invoke_batch(vec![msg]).await
+        }
+
+        async fn invoke_batch(&self, msgs: Vec<Message>) -> Result<(), SendError> {
+            let values = msgs.iter().map(|m| *m.payload.downcast_ref::<i32>().unwrap()).collect();
+            self.delivered.lock().unwrap().push(values);
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_processor_delivers_through_invoke_batch() {
+        let invoker = Arc::new(BatchInvoker { delivered: Mutex::new(Vec::new()) });
+        let processor = BatchProcessor::new(2, Duration::from_millis(10));
+        for i in 0..3 {
+            processor.send(invoker.clone(), Message::new(i)).unwrap();
+        }
+        processor.shutdown().await;
+
+        // One full batch, then the remainder flushed on shutdown
+        assert_eq!(*invoker.delivered.lock().unwrap(), vec![vec![0, 1], vec![2]]);
+    }
+}
@@ -0,0 +1,122 @@
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use tokio::runtime::{Handle, Runtime};
+use tokio::task::JoinHandle;
+use crate::metrics::DispatcherMetrics;
+use super::{ActorMailbox, Dispatcher, DispatcherRegistry};
+
+/// 通过 `spawn_blocking` 在有界线程池中运行阻塞型 Actor 的消息处理，避免占用共享运行时的工作线程
+#[derive(Clone)]
+pub struct BlockingDispatcher {
+    runtime: Arc<Runtime>,
+    metrics: Arc<DispatcherMetrics>,
+}
+
+impl BlockingDispatcher {
+    pub fn new(max_threads: usize) -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .max_blocking_threads(max_threads.max(1))
+            .thread_name("blocking-dispatcher")
+            .enable_all()
+            .build()
+            .unwrap();
+
+        Self {
+            runtime: Arc::new(runtime),
+            metrics: Arc::new(DispatcherMetrics::new(DispatcherRegistry::BLOCKING, 0)),
+        }
+    }
+}
+
+impl Default for BlockingDispatcher {
+    fn default() -> Self {
+        Self::new(num_cpus::get() * 4)
+    }
+}
+
+impl Dispatcher for BlockingDispatcher {
+    fn schedule(&self, f: BoxFuture<'static, ()>) -> JoinHandle<()> {
+        // 阻塞线程仍处于运行时上下文中，可以驱动 future 内的 IO 与定时器
+        self.runtime.spawn_blocking(move || Handle::current().block_on(f))
+    }
+
+    /// 邮箱循环在运行时上等待消息，只有处理消息时才占用阻塞线程，
+    /// 因此 Actor 数量不受 `max_threads` 限制
+    fn attach(&self, mailbox: ActorMailbox) -> JoinHandle<()> {
+        self.runtime.spawn(mailbox.on_blocking_threads().run(Some(Arc::clone(&self.metrics))))
+    }
+
+    fn metrics(&self) -> Option<Arc<DispatcherMetrics>> {
+        Some(Arc::clone(&self.metrics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use crate::actor::Actor;
+    use crate::context::Context;
+    use crate::dispatcher::DispatcherExt;
+    use crate::dispatcher::actor_mailbox::test_mailbox;
+    use crate::errors::SendError;
+    use crate::message::Message;
+
+    #[test]
+    fn test_blocking_actors_run_concurrently_off_the_workers() {
+        let dispatcher = BlockingDispatcher::new(2);
+        let (tx, rx) = mpsc::channel();
+        let (name_tx, name_rx) = mpsc::channel();
+
+        // 第一个任务阻塞等待第二个任务，二者必须同时占用阻塞线程
+        let waiting = dispatcher.spawn(async move {
+            let name = std::thread::current().name().unwrap_or_default().to_string();
+            name_tx.send(name).unwrap();
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        });
+        let signalling = dispatcher.spawn(async move {
+            tx.send(()).unwrap();
+        });
+
+        futures::executor::block_on(signalling).unwrap();
+        futures::executor::block_on(waiting).unwrap();
+        assert_eq!(name_rx.recv().unwrap(), "blocking-dispatcher");
+    }
+
+    struct Echo(mpsc::Sender<usize>, usize);
+
+    #[async_trait::async_trait]
+    impl Actor for Echo {
+        async fn receive(&mut self, _ctx: &Context, _msg: Message) -> Result<(), SendError> {
+            let _ = self.0.send(self.1);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_more_actors_than_blocking_threads() {
+        let dispatcher = BlockingDispatcher::new(1);
+        let (tx, rx) = mpsc::channel();
+        let actors: Vec<_> = (0..3)
+            .map(|id| {
+                let (mailbox, actor) = test_mailbox(Echo(tx.clone(), id));
+                dispatcher.attach(mailbox);
+                actor
+            })
+            .collect();
+
+        // 三个 Actor 共用唯一的阻塞线程，空闲的邮箱不占用它
+        futures::executor::block_on(async {
+            for actor in &actors {
+                actor.send(Message::new(())).await.unwrap();
+            }
+        });
+        let mut handled: Vec<usize> = (0..3)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        handled.sort();
+        assert_eq!(handled, vec![0, 1, 2]);
+    }
+}
@@ -1,8 +1,20 @@
+use std::time::Duration;
+
+/// 工作线程选择策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleStrategy {
+    RoundRobin,
+    LeastBusy,
+    Random,
+}
+
 #[derive(Debug, Clone)]
 pub struct SchedulerConfig {
     // 基本配置
     pub strategy: ScheduleStrategy,
     pub worker_count: usize,
+    // 每个邮箱单次调度处理的最大消息数
+    pub throughput: usize,
     
     // 负载均衡配置
     pub max_concurrent_tasks: usize,
@@ -24,6 +36,7 @@ impl Default for SchedulerConfig {
         Self {
             strategy: ScheduleStrategy::RoundRobin,
             worker_count: num_cpus::get(),
+            throughput: 100,
             max_concurrent_tasks: 1000,
             task_queue_size: 10000,
             round_robin_batch_size: 10,
@@ -1,38 +1,56 @@
 use std::sync::Arc;
 use dashmap::DashMap;
-use crate::SendError;
+use super::{
+    BlockingDispatcher, Dispatcher, PinnedDispatcher, PriorityDispatcher, SchedulerConfig, ThreadPoolDispatcher,
+    WorkerPoolDispatcher,
+};
 
 pub struct DispatcherRegistry {
     dispatchers: DashMap<String, Arc<dyn Dispatcher>>,
 }
 
 impl DispatcherRegistry {
+    pub const DEFAULT: &'static str = "default";
+    pub const SINGLE_THREAD: &'static str = "single-thread";
+    pub const PINNED: &'static str = "pinned";
+    pub const BLOCKING: &'static str = "blocking";
+    pub const PRIORITY: &'static str = "priority";
+    pub const WORKER_POOL: &'static str = "worker-pool";
+
     pub fn new() -> Self {
-        let mut registry = Self {
+        Self::with_default(Arc::new(ThreadPoolDispatcher::with_name(Self::DEFAULT, num_cpus::get())))
+    }
+
+    /// 以给定调度器作为默认调度器，避免额外构建再被覆盖的线程池
+    pub fn with_default(dispatcher: Arc<dyn Dispatcher>) -> Self {
+        let registry = Self {
             dispatchers: DashMap::new(),
         };
 
-        // 注册默认调度器
-        registry.register_default_dispatchers();
+        registry.dispatchers.insert(Self::DEFAULT.to_string(), dispatcher);
+        registry.register_builtin_dispatchers();
         registry
     }
 
-    fn register_default_dispatchers(&mut self) {
-        // 默认线程池调度器
-        let default_config = ThreadPoolConfig::default();
-        let default_dispatcher = Arc::new(ThreadPoolDispatcher::new(default_config));
-        self.dispatchers.insert("default".to_string(), default_dispatcher);
-
+    fn register_builtin_dispatchers(&self) {
         // 单线程调度器
-        let single_thread_config = ThreadPoolConfig {
-            load_balancing: LoadBalancing {
-                worker_count: 1,
-                ..Default::default()
-            },
-            ..Default::default()
-        };
-        let single_thread_dispatcher = Arc::new(ThreadPoolDispatcher::new(single_thread_config));
-        self.dispatchers.insert("single-thread".to_string(), single_thread_dispatcher);
+        let single_thread_dispatcher = Arc::new(ThreadPoolDispatcher::with_name(Self::SINGLE_THREAD, 1));
+        self.dispatchers.insert(Self::SINGLE_THREAD.to_string(), single_thread_dispatcher);
+
+        // 每个 Actor 独占线程的调度器
+        self.dispatchers.insert(Self::PINNED.to_string(), Arc::new(PinnedDispatcher::default()));
+
+        // 阻塞型 Actor 使用的有界阻塞线程池
+        self.dispatchers.insert(Self::BLOCKING.to_string(), Arc::new(BlockingDispatcher::default()));
+
+        // 按 `Props::with_priority` 调度邮箱的优先级调度器
+        self.dispatchers.insert(Self::PRIORITY.to_string(), Arc::new(PriorityDispatcher::default()));
+
+        // 按策略分配邮箱并支持工作窃取的线程池
+        self.dispatchers.insert(
+            Self::WORKER_POOL.to_string(),
+            Arc::new(WorkerPoolDispatcher::new(SchedulerConfig::default())),
+        );
     }
 
     pub fn register(&self, name: &str, dispatcher: Arc<dyn Dispatcher>) {
@@ -43,7 +61,70 @@ impl DispatcherRegistry {
         self.dispatchers.get(name).map(|d| d.clone())
     }
 
+    /// 所有已注册的调度器及其名称
+    pub fn entries(&self) -> Vec<(String, Arc<dyn Dispatcher>)> {
+        self.dispatchers
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    pub fn default_dispatcher(&self) -> Arc<dyn Dispatcher> {
+        self.get(Self::DEFAULT).expect("default dispatcher is always registered")
+    }
+
     pub fn remove(&self, name: &str) {
-        self.dispatchers.remove(name);
+        // 默认调度器不可移除
+        if name != Self::DEFAULT {
+            self.dispatchers.remove(name);
+        }
+    }
+}
+
+impl Default for DispatcherRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatcher::DispatcherExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_builtin_dispatchers_schedule() {
+        let registry = DispatcherRegistry::new();
+        let ran = Arc::new(AtomicBool::new(false));
+
+        let dispatcher = registry.get(DispatcherRegistry::SINGLE_THREAD).unwrap();
+        let flag = Arc::clone(&ran);
+        let handle = dispatcher.spawn(async move {
+            flag.store(true, Ordering::SeqCst);
+        });
+        futures::executor::block_on(handle).unwrap();
+
+        assert!(ran.load(Ordering::SeqCst));
+        assert!(registry.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_default_dispatcher_cannot_be_removed() {
+        let registry = DispatcherRegistry::new();
+        registry.remove(DispatcherRegistry::DEFAULT);
+        assert!(registry.get(DispatcherRegistry::DEFAULT).is_some());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_with_default_keeps_given_dispatcher() {
+        let dispatcher: Arc<dyn Dispatcher> = Arc::new(ThreadPoolDispatcher::with_name("system", 1));
+        let registry = DispatcherRegistry::with_default(Arc::clone(&dispatcher));
+
+        assert!(Arc::ptr_eq(&registry.default_dispatcher(), &dispatcher));
+        assert!(registry.get(DispatcherRegistry::PINNED).is_some());
+        assert!(registry.get(DispatcherRegistry::BLOCKING).is_some());
+        assert!(registry.get(DispatcherRegistry::PRIORITY).is_some());
+        assert!(registry.get(DispatcherRegistry::WORKER_POOL).is_some());
+    }
+}
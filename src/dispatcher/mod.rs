@@ -1,36 +1,129 @@
-use async_trait::async_trait;
+mod actor_mailbox;
+mod backpressure;
+mod batch;
+mod blocking;
+mod config;
+mod factory;
+mod monitor;
+mod pinned;
+mod priority;
+mod strategy;
+mod worker;
+mod worker_pool;
+
+pub use actor_mailbox::ActorMailbox;
+pub use backpressure::{BackpressureChanged, BackpressureConfig, BackpressureController, BackpressureSignal};
+pub use batch::BatchProcessor;
+pub use blocking::BlockingDispatcher;
+pub use config::{ScheduleStrategy, SchedulerConfig};
+pub use factory::DispatcherRegistry;
+pub use monitor::{DispatcherMonitor, SampleDispatchers};
+pub use pinned::PinnedDispatcher;
+pub use priority::{Priority, PriorityDispatcher};
+pub use worker_pool::WorkerPoolDispatcher;
+
+use futures::FutureExt;
+use futures::future::BoxFuture;
 use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::task::JoinHandle;
+use crate::errors::SendError;
+use crate::metrics::DispatcherMetrics;
 
-#[async_trait]
+/// 调度器特征，接收装箱的 future 以保持对象安全
 pub trait Dispatcher: Send + Sync {
-    async fn schedule<F>(&self, f: F) -> JoinHandle<()>
+    fn schedule(&self, f: BoxFuture<'static, ()>) -> JoinHandle<()>;
+
+    /// 运行 Actor 的邮箱，默认作为一个任务调度，逐条交付并按消息记录指标
+    fn attach(&self, mailbox: ActorMailbox) -> JoinHandle<()> {
+        self.schedule(Box::pin(mailbox.run(self.metrics())))
+    }
+
+    /// 调度器指标，未采集指标的调度器返回 None
+    fn metrics(&self) -> Option<Arc<DispatcherMetrics>> {
+        None
+    }
+}
+
+/// 为所有调度器提供泛型的调度入口
+pub trait DispatcherExt: Dispatcher {
+    fn spawn<F>(&self, f: F) -> JoinHandle<()>
     where
-        F: Future<Output = ()> + Send + 'static;
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.schedule(Box::pin(f))
+    }
+}
+
+impl<D: Dispatcher + ?Sized> DispatcherExt for D {}
+
+/// 记录一次交付的执行与完成情况，`count` 条消息平摊处理时间，panic 计为失败后继续传播
+pub(crate) async fn measure<F>(metrics: Option<&DispatcherMetrics>, count: usize, f: F) -> Result<(), SendError>
+where
+    F: Future<Output = Result<(), SendError>>,
+{
+    let metrics = match metrics {
+        Some(metrics) => metrics,
+        None => return f.await,
+    };
+
+    for _ in 0..count {
+        metrics.record_task_started();
+    }
+    let started = Instant::now();
+    let result = AssertUnwindSafe(f).catch_unwind().await;
+    let elapsed = started.elapsed() / count.max(1) as u32;
+    let success = matches!(result, Ok(Ok(())));
+    for _ in 0..count {
+        metrics.record_task_completed(elapsed, success);
+    }
+    match result {
+        Ok(result) => result,
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
 }
 
+#[derive(Clone)]
 pub struct ThreadPoolDispatcher {
-    pool: tokio::runtime::Runtime,
+    pool: Arc<tokio::runtime::Runtime>,
+    metrics: Arc<DispatcherMetrics>,
 }
 
 impl ThreadPoolDispatcher {
     pub fn new(threads: usize) -> Self {
+        Self::with_name("thread-pool", threads)
+    }
+
+    /// 创建线程池调度器，名称用作指标标签
+    pub fn with_name(name: &str, threads: usize) -> Self {
         let pool = tokio::runtime::Builder::new_multi_thread()
             .worker_threads(threads)
+            .thread_name(name)
             .enable_all()
             .build()
             .unwrap();
-            
-        Self { pool }
+
+        Self {
+            pool: Arc::new(pool),
+            metrics: Arc::new(DispatcherMetrics::new(name, 0)),
+        }
+    }
+}
+
+impl Default for ThreadPoolDispatcher {
+    fn default() -> Self {
+        Self::new(num_cpus::get())
     }
 }
 
-#[async_trait]
 impl Dispatcher for ThreadPoolDispatcher {
-    async fn schedule<F>(&self, f: F) -> JoinHandle<()>
-    where
-        F: Future<Output = ()> + Send + 'static,
-    {
+    fn schedule(&self, f: BoxFuture<'static, ()>) -> JoinHandle<()> {
         self.pool.spawn(f)
     }
-} 
\ No newline at end of file
+
+    fn metrics(&self) -> Option<Arc<DispatcherMetrics>> {
+        Some(Arc::clone(&self.metrics))
+    }
+}
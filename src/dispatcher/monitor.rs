@@ -1,60 +1,133 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use metrics::gauge;
+use tokio::task::JoinHandle;
 use tokio::time::interval;
-use crate::metrics::MetricsCollector;
+use crate::actor::Actor;
+use crate::context::Context;
+use crate::errors::SendError;
+use crate::message::Message;
+use super::DispatcherRegistry;
 
-pub(crate) struct DispatcherMonitor {
-    dispatcher: Arc<dyn Dispatcher>,
-    metrics_collector: Arc<dyn MetricsCollector>,
+/// 触发一次采样的消息
+#[derive(Debug, Clone, Copy)]
+pub struct SampleDispatchers;
+
+/// 定期采样所有已注册调度器指标的 Actor
+///
+/// 实时指标由调度器直接上报，监控 Actor 负责计算吞吐率等需要两次采样才能得出的值。
+pub struct DispatcherMonitor {
+    registry: Arc<DispatcherRegistry>,
     check_interval: Duration,
+    last_sample: HashMap<String, (Instant, u64)>,
+    ticker: Option<JoinHandle<()>>,
 }
 
 impl DispatcherMonitor {
-    pub fn new(
-        dispatcher: Arc<dyn Dispatcher>,
-        metrics_collector: Arc<dyn MetricsCollector>,
-        check_interval: Duration,
-    ) -> Self {
+    pub fn new(registry: Arc<DispatcherRegistry>, check_interval: Duration) -> Self {
         Self {
-            dispatcher,
-            metrics_collector,
+            registry,
             check_interval,
+            last_sample: HashMap::new(),
+            ticker: None,
         }
     }
 
-    pub async fn start(self) {
-        let mut interval = interval(self.check_interval);
-
-        loop {
-            interval.tick().await;
-            
-            let metrics = self.dispatcher.metrics();
-            
-            // 收集指标
-            self.metrics_collector.record_gauge(
-                "dispatcher_active_threads",
-                metrics.active_threads as f64,
-            );
-            
-            self.metrics_collector.record_gauge(
-                "dispatcher_queued_tasks",
-                metrics.queued_tasks as f64,
-            );
-            
-            self.metrics_collector.record_counter(
-                "dispatcher_completed_tasks",
-                metrics.completed_tasks as u64,
-            );
-            
-            self.metrics_collector.record_counter(
-                "dispatcher_failed_tasks",
-                metrics.failed_tasks as u64,
-            );
-            
-            self.metrics_collector.record_histogram(
-                "dispatcher_processing_time",
-                metrics.avg_processing_time.as_secs_f64(),
+    fn sample(&mut self) {
+        let now = Instant::now();
+        for (name, dispatcher) in self.registry.entries() {
+            let stats = match dispatcher.metrics() {
+                Some(metrics) => metrics.snapshot(),
+                None => continue,
+            };
+
+            let finished = stats.completed_tasks + stats.failed_tasks;
+            if let Some((at, previous)) = self.last_sample.insert(name.clone(), (now, finished)) {
+                let elapsed = now.duration_since(at).as_secs_f64();
+                if elapsed > 0.0 {
+                    let throughput = finished.saturating_sub(previous) as f64 / elapsed;
+                    gauge!("dispatcher_throughput", throughput, "dispatcher" => name.clone());
+                }
+            }
+
+            gauge!(
+                "dispatcher_avg_processing_time_seconds",
+                stats.avg_processing_time.as_secs_f64(),
+                "dispatcher" => name.clone()
             );
+
+            if !stats.worker_utilization.is_empty() {
+                let avg = stats.worker_utilization.iter().sum::<f64>() / stats.worker_utilization.len() as f64;
+                gauge!("dispatcher_avg_worker_utilization", avg, "dispatcher" => name.clone());
+            }
+
+            log::trace!("Dispatcher {} stats: {:?}", name, stats);
         }
     }
-} 
\ No newline at end of file
+}
+
+#[async_trait]
+impl Actor for DispatcherMonitor {
+    async fn receive(&mut self, _ctx: &Context, msg: Message) -> Result<(), SendError> {
+        if msg.payload.is::<SampleDispatchers>() {
+            self.sample();
+        }
+        Ok(())
+    }
+
+    async fn started(&mut self, ctx: &Context) -> Result<(), SendError> {
+        let self_ref = ctx.self_ref().clone();
+        let check_interval = self.check_interval;
+
+        self.ticker = Some(tokio::spawn(async move {
+            let mut interval = interval(check_interval);
+            loop {
+                interval.tick().await;
+                if self_ref.send(Message::new(SampleDispatchers)).await.is_err() {
+                    break;
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    async fn stopping(&mut self, _ctx: &Context) -> Result<(), SendError> {
+        if let Some(ticker) = self.ticker.take() {
+            ticker.abort();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+    use crate::actor::ActorRef;
+
+    #[test]
+    fn test_tick_samples_registered_dispatchers() {
+        let registry = Arc::new(DispatcherRegistry::new());
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let mut monitor = DispatcherMonitor::new(Arc::clone(&registry), Duration::from_millis(10));
+
+        runtime.block_on(async {
+            let (sender, mut mailbox) = mpsc::channel(4);
+            let ctx = Context::with_sender(ActorRef::new("monitor".to_string(), sender.clone()), None, sender);
+            monitor.started(&ctx).await.unwrap();
+
+            // The ticker's first message drives one sample
+            let tick = mailbox.recv().await.unwrap();
+            monitor.receive(&ctx, tick).await.unwrap();
+            monitor.stopping(&ctx).await.unwrap();
+        });
+
+        let mut sampled: Vec<String> = monitor.last_sample.keys().cloned().collect();
+        sampled.sort();
+        let mut registered: Vec<String> = registry.entries().into_iter().map(|(name, _)| name).collect();
+        registered.sort();
+        assert_eq!(sampled, registered);
+    }
+}
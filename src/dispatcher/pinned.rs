@@ -0,0 +1,154 @@
+use futures::future::BoxFuture;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc as std_mpsc;
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use crate::metrics::DispatcherMetrics;
+use super::{Dispatcher, DispatcherRegistry};
+
+/// 一个 Actor 独占的 OS 线程，线程内的单线程运行时驱动该 Actor 的邮箱
+struct PinnedThread {
+    handle: Handle,
+    // 释放后线程内的运行时停止，线程随之退出
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl PinnedThread {
+    fn start(thread_name: String) -> Self {
+        let (handle_tx, handle_rx) = std_mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        std::thread::Builder::new()
+            .name(thread_name)
+            .spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Failed to build pinned runtime");
+                let _ = handle_tx.send(runtime.handle().clone());
+
+                runtime.block_on(async move {
+                    let _ = shutdown_rx.await;
+                });
+            })
+            .expect("Failed to spawn pinned dispatcher thread");
+
+        Self {
+            handle: handle_rx.recv().expect("Pinned dispatcher thread exited before starting"),
+            _shutdown: shutdown_tx,
+        }
+    }
+}
+
+/// 为每个 Actor 分配独立的 OS 线程，同一 Actor 的任务始终在该线程上运行
+pub struct PinnedDispatcher {
+    name: String,
+    next_id: AtomicUsize,
+    metrics: Arc<DispatcherMetrics>,
+}
+
+impl PinnedDispatcher {
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            metrics: Arc::new(DispatcherMetrics::new(name.clone(), 0)),
+            name,
+            next_id: AtomicUsize::new(0),
+        }
+    }
+
+    fn thread_name(&self) -> String {
+        format!("{}-{}", self.name, self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for PinnedDispatcher {
+    fn default() -> Self {
+        Self::new(DispatcherRegistry::PINNED)
+    }
+}
+
+impl Dispatcher for PinnedDispatcher {
+    /// 每个任务独占一个线程，任务结束后线程退出；Actor 的邮箱作为一个任务运行，因此独占线程直到停止
+    fn schedule(&self, f: BoxFuture<'static, ()>) -> JoinHandle<()> {
+        let thread = PinnedThread::start(self.thread_name());
+        let handle = thread.handle.clone();
+        handle.spawn(async move {
+            f.await;
+            drop(thread);
+        })
+    }
+
+    fn metrics(&self) -> Option<Arc<DispatcherMetrics>> {
+        Some(Arc::clone(&self.metrics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::Actor;
+    use crate::context::Context;
+    use crate::dispatcher::DispatcherExt;
+    use crate::dispatcher::actor_mailbox::test_mailbox;
+    use crate::errors::SendError;
+    use crate::message::Message;
+
+    fn thread_name_of(handle: JoinHandle<()>, rx: std_mpsc::Receiver<String>) -> String {
+        futures::executor::block_on(handle).unwrap();
+        rx.recv().unwrap()
+    }
+
+    fn current_thread_name(tx: std_mpsc::Sender<String>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let name = std::thread::current().name().unwrap_or_default().to_string();
+            tx.send(name).unwrap();
+        })
+    }
+
+    #[test]
+    fn test_pinned_dispatcher_uses_dedicated_threads() {
+        let dispatcher = PinnedDispatcher::new("pinned-test");
+
+        let names: Vec<String> = (0..2)
+            .map(|_| {
+                let (tx, rx) = std_mpsc::channel();
+                let handle = dispatcher.spawn(current_thread_name(tx));
+                thread_name_of(handle, rx)
+            })
+            .collect();
+
+        assert_eq!(names, vec!["pinned-test-0", "pinned-test-1"]);
+    }
+
+    struct ThreadNames(std_mpsc::Sender<String>);
+
+    #[async_trait::async_trait]
+    impl Actor for ThreadNames {
+        async fn receive(&mut self, _ctx: &Context, _msg: Message) -> Result<(), SendError> {
+            let _ = self.0.send(std::thread::current().name().unwrap_or_default().to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_actor_keeps_its_thread_until_stopped() {
+        let dispatcher = PinnedDispatcher::new("pinned-test");
+        let (tx, rx) = std_mpsc::channel();
+        let (mailbox, actor) = test_mailbox(ThreadNames(tx));
+        let running = dispatcher.attach(mailbox);
+
+        futures::executor::block_on(async {
+            for i in 0..3 {
+                actor.send(Message::new(i)).await.unwrap();
+            }
+            actor.stop().await;
+            running.await.unwrap();
+        });
+
+        let names: Vec<String> = rx.try_iter().collect();
+        assert_eq!(names, vec!["pinned-test-0"; 3]);
+    }
+}
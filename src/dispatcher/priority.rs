@@ -1,10 +1,21 @@
-use std::cmp::Ordering;
-use std::time::Instant;
-use tokio::sync::mpsc;
-use priority_queue::PriorityQueue;
-use crate::{Context, Message, SendError};
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use crate::actor::Props;
+use crate::errors::SendError;
+use crate::message::Message;
+use crate::mailbox::MessageInvoker;
+use crate::metrics::DispatcherMetrics;
+use super::{ActorMailbox, Dispatcher, DispatcherRegistry};
+use super::worker::ActorQueue;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Priority {
     Critical = 0,
     High = 1,
@@ -13,92 +24,286 @@ pub enum Priority {
     Background = 4,
 }
 
-#[derive(Debug)]
-pub struct PrioritizedMessage {
-    pub priority: Priority,
-    pub timestamp: Instant,
-    pub context: Context,
-    pub message: Message,
+impl Priority {
+    const CLASSES: usize = 5;
+
+    /// 加权公平调度中各优先级的权重，Critical 不参与加权
+    pub fn weight(self) -> u64 {
+        match self {
+            Priority::Critical => 0,
+            Priority::High => 8,
+            Priority::Normal => 4,
+            Priority::Low => 2,
+            Priority::Background => 1,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
 }
 
-impl PartialEq for PrioritizedMessage {
-    fn eq(&self, other: &Self) -> bool {
-        self.priority == other.priority && self.timestamp == other.timestamp
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
     }
 }
 
-impl Eq for PrioritizedMessage {}
+/// 步长调度的基准步长
+const STRIDE: u64 = 1 << 16;
 
-impl PartialOrd for PrioritizedMessage {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+/// 按优先级分类的运行队列
+///
+/// Critical 严格优先；其余类别按权重做步长调度，低优先级类别不会被饿死。
+pub(crate) struct ClassQueues<T> {
+    queues: [VecDeque<T>; Priority::CLASSES],
+    pass: [u64; Priority::CLASSES],
+    current_pass: u64,
 }
 
-impl Ord for PrioritizedMessage {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match self.priority.cmp(&other.priority) {
-            Ordering::Equal => other.timestamp.cmp(&self.timestamp),
-            ord => ord,
+impl<T> ClassQueues<T> {
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            pass: [0; Priority::CLASSES],
+            current_pass: 0,
+        }
+    }
+
+    pub fn push(&mut self, priority: Priority, item: T) {
+        let index = priority.index();
+        // 空闲后重新激活的类别不能累积调度额度
+        if self.queues[index].is_empty() {
+            self.pass[index] = self.pass[index].max(self.current_pass);
+        }
+        self.queues[index].push_back(item);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if let Some(item) = self.queues[Priority::Critical.index()].pop_front() {
+            return Some(item);
+        }
+
+        let index = (Priority::High.index()..Priority::CLASSES)
+            .filter(|&i| !self.queues[i].is_empty())
+            .min_by_key(|&i| (self.pass[i], i))?;
+
+        self.current_pass = self.pass[index];
+        self.pass[index] += STRIDE / Self::weight_of(index);
+        self.queues[index].pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
+    }
+
+    fn weight_of(index: usize) -> u64 {
+        match index {
+            1 => Priority::High.weight(),
+            2 => Priority::Normal.weight(),
+            3 => Priority::Low.weight(),
+            _ => Priority::Background.weight(),
         }
     }
 }
 
-pub struct PriorityDispatcher {
-    queue: PriorityQueue<PrioritizedMessage, Priority>,
-    sender: mpsc::UnboundedSender<PrioritizedMessage>,
+/// 带优先级的邮箱
+pub(crate) struct PrioritizedMailbox {
+    priority: Priority,
+    queue: ActorQueue,
+}
+
+struct Shared {
+    run_queue: Mutex<ClassQueues<Arc<PrioritizedMailbox>>>,
+    mailboxes: DashMap<String, Arc<PrioritizedMailbox>>,
+    notify: Notify,
+    shutdown: AtomicBool,
+    metrics: Arc<DispatcherMetrics>,
+    throughput: usize,
     max_queue_size: usize,
 }
 
-impl PriorityDispatcher {
-    pub fn new(max_queue_size: usize) -> Self {
-        let (sender, mut receiver) = mpsc::unbounded_channel();
-        let queue = PriorityQueue::new();
-
-        // 启动处理线程
-        tokio::spawn(async move {
-            while let Some(msg) = receiver.recv().await {
-                if let Err(e) = msg.context.receive_message(msg.message).await {
-                    log::error!("Failed to process prioritized message: {:?}", e);
+impl Shared {
+    fn enqueue(&self, mailbox: Arc<PrioritizedMailbox>) {
+        let priority = mailbox.priority;
+        self.run_queue.lock().unwrap().push(priority, mailbox);
+        self.notify.notify_one();
+    }
+
+    /// 投递消息，邮箱变为可运行时按其优先级进入运行队列
+    fn dispatch(&self, actor_id: &str, msg: Message) -> Result<(), SendError> {
+        if self.shutdown.load(Ordering::Acquire) {
+            return Err(SendError::MailboxClosed);
+        }
+
+        let mailbox = self.mailboxes
+            .get(actor_id)
+            .map(|m| Arc::clone(&m))
+            .ok_or(SendError::DeadLetter)?;
+
+        if mailbox.queue.len() >= self.max_queue_size {
+            return Err(SendError::MailboxFull);
+        }
+
+        mailbox.queue.push(msg);
+        self.metrics.record_task_queued();
+        if mailbox.queue.try_schedule() {
+            self.enqueue(mailbox);
+        }
+        Ok(())
+    }
+
+    async fn run_turn(&self, mailbox: &Arc<PrioritizedMailbox>) {
+        if mailbox.queue.run_turn(self.throughput, &self.metrics, || {}).await {
+            self.enqueue(Arc::clone(mailbox));
+        }
+    }
+
+    async fn run_worker(self: Arc<Self>) {
+        while !self.shutdown.load(Ordering::Acquire) {
+            let next = self.run_queue.lock().unwrap().pop();
+            match next {
+                Some(mailbox) => self.run_turn(&mailbox).await,
+                None => {
+                    let _ = tokio::time::timeout(Duration::from_millis(100), self.notify.notified()).await;
                 }
             }
+        }
+    }
+}
+
+/// 按优先级调度整个邮箱的调度器
+///
+/// 每个 Actor 的优先级来自 `Props::with_priority`，调度单元是邮箱而非单条消息。
+pub struct PriorityDispatcher {
+    runtime: Arc<Runtime>,
+    shared: Arc<Shared>,
+}
+
+impl PriorityDispatcher {
+    pub fn new(worker_count: usize, max_queue_size: usize) -> Self {
+        let worker_count = worker_count.max(1);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(worker_count)
+            .thread_name("priority-dispatcher")
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let shared = Arc::new(Shared {
+            run_queue: Mutex::new(ClassQueues::new()),
+            mailboxes: DashMap::new(),
+            notify: Notify::new(),
+            shutdown: AtomicBool::new(false),
+            metrics: Arc::new(DispatcherMetrics::new(DispatcherRegistry::PRIORITY, worker_count)),
+            throughput: 100,
+            max_queue_size,
         });
 
+        for _ in 0..worker_count {
+            runtime.spawn(Arc::clone(&shared).run_worker());
+        }
+
         Self {
-            queue,
-            sender,
-            max_queue_size,
+            runtime: Arc::new(runtime),
+            shared,
         }
     }
 
-    pub fn dispatch_with_priority(
-        &mut self,
-        priority: Priority,
-        ctx: Context,
-        msg: Message,
-    ) -> Result<(), SendError> {
-        if self.queue.len() >= self.max_queue_size {
-            return Err(SendError::MailboxFull);
-        }
+    /// 注册 Actor 及其优先级
+    pub fn register(&self, actor_id: &str, invoker: Arc<dyn MessageInvoker>, priority: Priority) {
+        self.register_queue(actor_id, ActorQueue::new(invoker), priority);
+    }
 
-        let prioritized_msg = PrioritizedMessage {
-            priority,
-            timestamp: Instant::now(),
-            context: ctx,
-            message: msg,
-        };
+    /// 使用 Props 中配置的优先级注册 Actor
+    pub fn register_props(&self, actor_id: &str, invoker: Arc<dyn MessageInvoker>, props: &Props) {
+        self.register(actor_id, invoker, props.priority());
+    }
 
-        self.queue.push(prioritized_msg, priority);
-        self.process_queue();
-        Ok(())
+    fn register_queue(&self, actor_id: &str, queue: ActorQueue, priority: Priority) {
+        self.shared.mailboxes.insert(
+            actor_id.to_string(),
+            Arc::new(PrioritizedMailbox { priority, queue }),
+        );
     }
 
-    fn process_queue(&mut self) {
-        while let Some((msg, _)) = self.queue.pop() {
-            if let Err(e) = self.sender.send(msg) {
-                log::error!("Failed to send prioritized message: {:?}", e);
-                break;
-            }
+    pub fn unregister(&self, actor_id: &str) {
+        self.shared.mailboxes.remove(actor_id);
+    }
+
+    /// 投递消息，邮箱变为可运行时按其优先级进入运行队列
+    pub fn dispatch(&self, actor_id: &str, msg: Message) -> Result<(), SendError> {
+        self.shared.dispatch(actor_id, msg)
+    }
+
+    /// 在运行队列中等待工作线程的邮箱数
+    pub fn runnable(&self) -> usize {
+        self.shared.run_queue.lock().unwrap().len()
+    }
+
+    pub fn shutdown(&self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        self.shared.notify.notify_waiters();
+    }
+}
+
+impl Default for PriorityDispatcher {
+    fn default() -> Self {
+        Self::new(num_cpus::get(), 10000)
+    }
+}
+
+impl Dispatcher for PriorityDispatcher {
+    fn schedule(&self, f: BoxFuture<'static, ()>) -> JoinHandle<()> {
+        self.runtime.spawn(f)
+    }
+
+    /// 按 Actor 的优先级登记邮箱，收到的消息经 `dispatch` 由工作线程按优先级处理，Actor 停止后注销
+    fn attach(&self, mailbox: ActorMailbox) -> JoinHandle<()> {
+        let actor_id = mailbox.id().to_string();
+        let queue = ActorQueue::new(mailbox.invoker()).with_backpressure(mailbox.backpressure());
+        self.register_queue(&actor_id, queue, mailbox.priority());
+
+        let shared = Arc::clone(&self.shared);
+        self.runtime.spawn(async move {
+            mailbox.forward(|msg| shared.dispatch(&actor_id, msg)).await;
+            shared.mailboxes.remove(&actor_id);
+        })
+    }
+
+    fn metrics(&self) -> Option<Arc<DispatcherMetrics>> {
+        Some(Arc::clone(&self.shared.metrics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_critical_is_always_first() {
+        let mut queues = ClassQueues::new();
+        queues.push(Priority::Background, "background");
+        queues.push(Priority::High, "high");
+        queues.push(Priority::Critical, "critical");
+
+        assert_eq!(queues.pop(), Some("critical"));
+        assert_eq!(queues.pop(), Some("high"));
+        assert_eq!(queues.pop(), Some("background"));
+        assert_eq!(queues.pop(), None);
+    }
+
+    #[test]
+    fn test_background_is_not_starved() {
+        let mut queues = ClassQueues::new();
+        for _ in 0..100 {
+            queues.push(Priority::High, Priority::High);
+            queues.push(Priority::Background, Priority::Background);
         }
+
+        // Served in proportion to their weights (8:1)
+        let served: Vec<_> = (0..90).filter_map(|_| queues.pop()).collect();
+        let background = served.iter().filter(|p| **p == Priority::Background).count();
+        assert_eq!(background, 10);
     }
-} 
\ No newline at end of file
+}
@@ -1,8 +1,9 @@
+use std::sync::Mutex;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use dashmap::DashMap;
-use tokio::sync::RwLock;
-use crate::{Context, Message, SendError};
+use super::config::{ScheduleStrategy, SchedulerConfig};
 
 // 轮询调度器状态
 pub(crate) struct RoundRobinState {
@@ -51,4 +52,59 @@ impl LeastBusyState {
             *load = (*load as i32 + delta).max(0) as usize;
         }
     }
-} 
\ No newline at end of file
+}
+
+// 随机调度状态
+pub(crate) struct RandomState {
+    rng: Mutex<StdRng>,
+    worker_count: usize,
+}
+
+impl RandomState {
+    pub fn new(worker_count: usize, seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self {
+            rng: Mutex::new(rng),
+            worker_count,
+        }
+    }
+
+    pub fn next_worker(&self) -> usize {
+        self.rng.lock().unwrap().gen_range(0..self.worker_count)
+    }
+}
+
+// 按配置的策略为可运行的邮箱选择工作线程
+pub(crate) enum WorkerSelector {
+    RoundRobin(RoundRobinState),
+    LeastBusy(LeastBusyState),
+    Random(RandomState),
+}
+
+impl WorkerSelector {
+    pub fn from_config(config: &SchedulerConfig) -> Self {
+        let worker_count = config.worker_count.max(1);
+        match config.strategy {
+            ScheduleStrategy::RoundRobin => Self::RoundRobin(RoundRobinState::new(worker_count)),
+            ScheduleStrategy::LeastBusy => Self::LeastBusy(LeastBusyState::new(worker_count)),
+            ScheduleStrategy::Random => Self::Random(RandomState::new(worker_count, config.random_seed)),
+        }
+    }
+
+    pub fn select(&self) -> usize {
+        match self {
+            Self::RoundRobin(state) => state.next_worker(),
+            Self::LeastBusy(state) => state.get_least_busy_worker(),
+            Self::Random(state) => state.next_worker(),
+        }
+    }
+
+    pub fn update_load(&self, worker_id: usize, delta: i32) {
+        if let Self::LeastBusy(state) = self {
+            state.update_load(worker_id, delta);
+        }
+    }
+}
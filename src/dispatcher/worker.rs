@@ -1,75 +1,149 @@
-use std::sync::Arc;
-use tokio::sync::mpsc;
-use tokio::task::JoinHandle;
-use crate::{Context, Message, SendError};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::mailbox::MessageInvoker;
+use crate::message::Message;
+use crate::metrics::DispatcherMetrics;
+use super::{measure, BackpressureController};
 
-pub(crate) struct Worker {
-    id: usize,
-    handle: JoinHandle<()>,
-    sender: mpsc::UnboundedSender<WorkerMessage>,
+/// 单个 Actor 的待处理消息，同一时刻只会被一个工作线程处理
+pub(crate) struct ActorQueue {
+    invoker: Arc<dyn MessageInvoker>,
+    messages: Mutex<VecDeque<Message>>,
+    scheduled: AtomicBool,
+    /// 邮箱的背压控制器，每处理一条消息归还一个位置
+    backpressure: Option<Arc<BackpressureController>>,
 }
 
-enum WorkerMessage {
-    Task(Context, Message),
-    Shutdown,
+impl ActorQueue {
+    pub fn new(invoker: Arc<dyn MessageInvoker>) -> Self {
+        Self {
+            invoker,
+            messages: Mutex::new(VecDeque::new()),
+            scheduled: AtomicBool::new(false),
+            backpressure: None,
+        }
+    }
+
+    pub fn with_backpressure(mut self, backpressure: Option<Arc<BackpressureController>>) -> Self {
+        self.backpressure = backpressure;
+        self
+    }
+
+    pub fn invoker(&self) -> &Arc<dyn MessageInvoker> {
+        &self.invoker
+    }
+
+    pub fn push(&self, msg: Message) {
+        self.messages.lock().unwrap().push_back(msg);
+    }
+
+    pub fn pop(&self) -> Option<Message> {
+        self.messages.lock().unwrap().pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 标记为已调度，返回 false 表示已在某个工作线程的运行队列中
+    pub fn try_schedule(&self) -> bool {
+        !self.scheduled.swap(true, Ordering::AcqRel)
+    }
+
+    pub fn unschedule(&self) {
+        self.scheduled.store(false, Ordering::Release);
+    }
+
+    /// 处理至多 throughput 条消息，每条消息出队后调用 `on_dequeue`
+    ///
+    /// 返回 true 表示仍有剩余消息且邮箱已重新标记为调度中，调用方需将其放回运行队列。
+    pub async fn run_turn(&self, throughput: usize, metrics: &DispatcherMetrics, mut on_dequeue: impl FnMut()) -> bool {
+        for _ in 0..throughput {
+            let msg = match self.pop() {
+                Some(msg) => msg,
+                None => break,
+            };
+            on_dequeue();
+
+            if let Err(e) = measure(Some(metrics), 1, self.invoker.invoke_user(msg)).await {
+                log::error!("Failed to handle message: {:?}", e);
+            }
+            if let Some(controller) = &self.backpressure {
+                controller.release();
+            }
+        }
+
+        // 先解除调度标记再检查剩余消息，避免与并发投递竞争时丢失唤醒
+        self.unschedule();
+        !self.is_empty() && self.try_schedule()
+    }
+}
+
+/// 工作线程的本地运行队列，队首由自身处理，队尾供空闲线程窃取
+pub(crate) struct Worker {
+    id: usize,
+    run_queue: Mutex<VecDeque<Arc<ActorQueue>>>,
 }
 
 impl Worker {
     pub fn new(id: usize) -> Self {
-        let (sender, mut receiver) = mpsc::unbounded_channel();
-        
-        let handle = tokio::spawn(async move {
-            while let Some(msg) = receiver.recv().await {
-                match msg {
-                    WorkerMessage::Task(ctx, msg) => {
-                        if let Err(e) = ctx.receive_message(msg).await {
-                            log::error!("Worker {} failed to process message: {:?}", id, e);
-                        }
-                    }
-                    WorkerMessage::Shutdown => break,
-                }
-            }
-        });
-
         Self {
             id,
-            handle,
-            sender,
+            run_queue: Mutex::new(VecDeque::new()),
         }
     }
 
-    pub fn send_task(&self, ctx: Context, msg: Message) -> Result<(), SendError> {
-        self.sender
-            .send(WorkerMessage::Task(ctx, msg))
-            .map_err(|_| SendError::MailboxFull)
+    pub fn id(&self) -> usize {
+        self.id
     }
 
-    pub async fn shutdown(self) {
-        let _ = self.sender.send(WorkerMessage::Shutdown);
-        let _ = self.handle.await;
+    pub fn push(&self, queue: Arc<ActorQueue>) {
+        self.run_queue.lock().unwrap().push_back(queue);
     }
-}
 
-pub(crate) struct WorkerPool {
-    workers: Vec<Worker>,
-}
+    pub fn pop(&self) -> Option<Arc<ActorQueue>> {
+        self.run_queue.lock().unwrap().pop_front()
+    }
 
-impl WorkerPool {
-    pub fn new(size: usize) -> Self {
-        let workers = (0..size)
-            .map(|id| Worker::new(id))
-            .collect();
-        
-        Self { workers }
+    pub fn steal(&self) -> Option<Arc<ActorQueue>> {
+        self.run_queue.lock().unwrap().pop_back()
     }
 
-    pub fn get_worker(&self, id: usize) -> Option<&Worker> {
-        self.workers.get(id)
+    pub fn len(&self) -> usize {
+        self.run_queue.lock().unwrap().len()
     }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::errors::SendError;
+    use crate::message::SystemMessage;
+
+    struct NoopInvoker(String);
+
+    #[async_trait::async_trait]
+    impl MessageInvoker for NoopInvoker {
+        fn id(&self) -> &str {
+            &self.0
+        }
 
-    pub async fn shutdown(self) {
-        for worker in self.workers {
-            worker.shutdown().await;
+        async fn invoke_system(&self, _msg: SystemMessage) -> Result<(), SendError> {
+            Ok(())
+        }
+
+        async fn invoke_user(&self, _msg: Message) -> Result<(), SendError> {
+            Ok(())
         }
     }
-} 
\ No newline at end of file
+
+    /// 不处理消息的 Actor 邮箱，供调度测试使用
+    pub(crate) fn actor_queue(id: &str) -> Arc<ActorQueue> {
+        Arc::new(ActorQueue::new(Arc::new(NoopInvoker(id.to_string()))))
+    }
+}
@@ -0,0 +1,354 @@
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use crate::mailbox::MessageInvoker;
+use crate::errors::SendError;
+use crate::message::Message;
+use crate::metrics::DispatcherMetrics;
+use super::{ActorMailbox, Dispatcher, DispatcherRegistry};
+use super::backpressure::{BackpressureController, BackpressureSignal};
+use super::config::SchedulerConfig;
+use super::strategy::WorkerSelector;
+use super::worker::{ActorQueue, Worker};
+
+struct Shared {
+    workers: Vec<Worker>,
+    actors: DashMap<String, Arc<ActorQueue>>,
+    selector: WorkerSelector,
+    notify: Notify,
+    shutdown: AtomicBool,
+    queued: AtomicUsize,
+    metrics: Arc<DispatcherMetrics>,
+    backpressure: Option<Arc<BackpressureController>>,
+    config: SchedulerConfig,
+}
+
+impl Shared {
+    fn new(config: SchedulerConfig, backpressure: Option<Arc<BackpressureController>>) -> Self {
+        let worker_count = config.worker_count.max(1);
+        Self {
+            workers: (0..worker_count).map(Worker::new).collect(),
+            actors: DashMap::new(),
+            selector: WorkerSelector::from_config(&config),
+            notify: Notify::new(),
+            shutdown: AtomicBool::new(false),
+            queued: AtomicUsize::new(0),
+            metrics: Arc::new(DispatcherMetrics::new(DispatcherRegistry::WORKER_POOL, worker_count)),
+            backpressure,
+            config,
+        }
+    }
+
+    /// 按策略把可运行的邮箱放入某个工作线程的运行队列
+    fn enqueue(&self, queue: Arc<ActorQueue>) {
+        let worker_id = self.selector.select();
+        self.workers[worker_id].push(queue);
+        self.selector.update_load(worker_id, 1);
+        self.notify.notify_one();
+    }
+
+    /// 投递一条消息到邮箱，必要时将邮箱加入运行队列
+    fn dispatch(&self, queue: &Arc<ActorQueue>, msg: Message) -> Result<BackpressureSignal, SendError> {
+        if self.shutdown.load(Ordering::Acquire) {
+            return Err(SendError::MailboxClosed);
+        }
+        if self.queued.load(Ordering::Relaxed) >= self.config.task_queue_size {
+            return Err(SendError::MailboxFull);
+        }
+
+        let signal = match &self.backpressure {
+            Some(controller) => controller.try_acquire(),
+            None => BackpressureSignal::Ok,
+        };
+        if signal == BackpressureSignal::Rejected {
+            return Err(SendError::BackPressure);
+        }
+
+        queue.push(msg);
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.metrics.record_task_queued();
+
+        if queue.try_schedule() {
+            self.enqueue(Arc::clone(queue));
+        }
+        Ok(signal)
+    }
+
+    /// 优先取本地队列，否则从最忙的工作线程窃取
+    fn next_job(&self, worker_id: usize) -> Option<Arc<ActorQueue>> {
+        if let Some(queue) = self.workers[worker_id].pop() {
+            self.selector.update_load(worker_id, -1);
+            return Some(queue);
+        }
+
+        let victim = self.workers
+            .iter()
+            .filter(|w| w.id() != worker_id)
+            .max_by_key(|w| w.len())
+            .filter(|w| w.len() > 0)?;
+        let queue = victim.steal()?;
+        self.selector.update_load(victim.id(), -1);
+        Some(queue)
+    }
+
+    /// 处理一个邮箱至多 throughput 条消息
+    async fn run_turn(&self, queue: &Arc<ActorQueue>) {
+        let requeue = queue.run_turn(self.config.throughput, &self.metrics, || {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            if let Some(controller) = &self.backpressure {
+                controller.release();
+                self.metrics.update_backpressure(controller.current_load());
+            }
+        }).await;
+        if requeue {
+            self.enqueue(Arc::clone(queue));
+        }
+    }
+
+    async fn run_worker(self: Arc<Self>, worker_id: usize) {
+        let sample_interval = self.config.load_update_interval;
+        let mut window_start = Instant::now();
+        let mut busy = Duration::ZERO;
+
+        while !self.shutdown.load(Ordering::Acquire) {
+            match self.next_job(worker_id) {
+                Some(queue) => {
+                    let started = Instant::now();
+                    self.run_turn(&queue).await;
+                    busy += started.elapsed();
+                }
+                None => {
+                    let _ = tokio::time::timeout(sample_interval, self.notify.notified()).await;
+                }
+            }
+
+            let elapsed = window_start.elapsed();
+            if elapsed >= sample_interval {
+                let utilization = busy.as_secs_f64() / elapsed.as_secs_f64();
+                self.metrics.update_worker_utilization(worker_id, utilization.min(1.0));
+                window_start = Instant::now();
+                busy = Duration::ZERO;
+            }
+        }
+    }
+}
+
+/// 基于工作线程池的调度器，按配置策略分配邮箱，空闲线程从繁忙线程窃取邮箱
+///
+/// 邮箱整体作为调度单元，同一 Actor 的消息始终按序由单个线程处理。
+pub struct WorkerPoolDispatcher {
+    runtime: Arc<Runtime>,
+    shared: Arc<Shared>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl WorkerPoolDispatcher {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self::with_backpressure(config, None)
+    }
+
+    /// 创建向背压控制器上报队列压力的调度器
+    pub fn with_backpressure(config: SchedulerConfig, backpressure: Option<Arc<BackpressureController>>) -> Self {
+        let worker_count = config.worker_count.max(1);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(worker_count)
+            .thread_name("worker-pool-dispatcher")
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let shared = Arc::new(Shared::new(config, backpressure));
+
+        let handles = (0..worker_count)
+            .map(|id| runtime.spawn(Arc::clone(&shared).run_worker(id)))
+            .collect();
+
+        Self {
+            runtime: Arc::new(runtime),
+            shared,
+            handles: Mutex::new(handles),
+        }
+    }
+
+    /// 投递一条消息到指定 Actor 的邮箱，必要时将邮箱加入运行队列
+    pub fn dispatch(&self, actor_id: &str, invoker: Arc<dyn MessageInvoker>, msg: Message) -> Result<BackpressureSignal, SendError> {
+        let queue = self.shared.actors
+            .entry(actor_id.to_string())
+            .or_insert_with(|| Arc::new(ActorQueue::new(invoker)))
+            .clone();
+        self.shared.dispatch(&queue, msg)
+    }
+
+    /// Actor 停止后移除其邮箱
+    pub fn remove_actor(&self, actor_id: &str) {
+        self.shared.actors.remove(actor_id);
+    }
+
+    pub fn queued_messages(&self) -> usize {
+        self.shared.queued.load(Ordering::Relaxed)
+    }
+
+    pub async fn shutdown(&self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        self.shared.notify.notify_waiters();
+        let handles = std::mem::take(&mut *self.handles.lock().unwrap());
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+}
+
+impl Dispatcher for WorkerPoolDispatcher {
+    fn schedule(&self, f: BoxFuture<'static, ()>) -> JoinHandle<()> {
+        self.runtime.spawn(f)
+    }
+
+    /// 登记邮箱，收到的消息经工作线程的运行队列处理，空闲线程可以窃取；Actor 停止后移除
+    fn attach(&self, mailbox: ActorMailbox) -> JoinHandle<()> {
+        let actor_id = mailbox.id().to_string();
+        let queue = Arc::new(ActorQueue::new(mailbox.invoker()).with_backpressure(mailbox.backpressure()));
+        self.shared.actors.insert(actor_id.clone(), Arc::clone(&queue));
+
+        let shared = Arc::clone(&self.shared);
+        self.runtime.spawn(async move {
+            mailbox.forward(|msg| shared.dispatch(&queue, msg).map(|_| ())).await;
+            shared.actors.remove(&actor_id);
+        })
+    }
+
+    fn metrics(&self) -> Option<Arc<DispatcherMetrics>> {
+        Some(Arc::clone(&self.shared.metrics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::Actor;
+    use crate::context::Context;
+    use crate::dispatcher::ScheduleStrategy;
+    use crate::dispatcher::actor_mailbox::test_mailbox;
+    use crate::dispatcher::worker::tests::actor_queue;
+
+    fn shared(strategy: ScheduleStrategy, worker_count: usize) -> Shared {
+        let config = SchedulerConfig {
+            strategy,
+            worker_count,
+            random_seed: Some(7),
+            ..SchedulerConfig::default()
+        };
+        Shared::new(config, None)
+    }
+
+    fn worker_lens(shared: &Shared) -> Vec<usize> {
+        shared.workers.iter().map(Worker::len).collect()
+    }
+
+    #[test]
+    fn test_idle_worker_steals_from_busiest() {
+        let shared = shared(ScheduleStrategy::RoundRobin, 3);
+        let queues: Vec<_> = (0..3).map(|i| actor_queue(&format!("a{}", i))).collect();
+        for queue in &queues {
+            shared.workers[0].push(Arc::clone(queue));
+        }
+        shared.workers[1].push(actor_queue("b0"));
+
+        // 空闲线程从最忙线程的队尾窃取
+        let stolen = shared.next_job(2).unwrap();
+        assert!(Arc::ptr_eq(&stolen, &queues[2]));
+        assert_eq!(worker_lens(&shared), vec![2, 1, 0]);
+
+        // 本地队列非空时优先处理本地邮箱
+        let local = shared.next_job(0).unwrap();
+        assert!(Arc::ptr_eq(&local, &queues[0]));
+    }
+
+    #[test]
+    fn test_stolen_mailbox_keeps_order() {
+        let shared = shared(ScheduleStrategy::RoundRobin, 2);
+        let queue = actor_queue("a");
+        for i in 0..3u32 {
+            queue.push(Message::new(i));
+        }
+        assert!(queue.try_schedule());
+        shared.workers[0].push(Arc::clone(&queue));
+
+        let stolen = shared.next_job(1).unwrap();
+        assert!(Arc::ptr_eq(&stolen, &queue));
+
+        // 邮箱被窃取后仍处于调度中，新消息不会让它被另一个线程同时处理
+        queue.push(Message::new(3u32));
+        assert!(!queue.try_schedule());
+        assert!(shared.next_job(0).is_none());
+
+        let order: Vec<u32> = std::iter::from_fn(|| stolen.pop())
+            .map(|msg| *msg.payload.downcast_ref::<u32>().unwrap())
+            .collect();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_schedule_strategy_selects_worker() {
+        let round_robin = shared(ScheduleStrategy::RoundRobin, 2);
+        for i in 0..4 {
+            round_robin.enqueue(actor_queue(&i.to_string()));
+        }
+        assert_eq!(worker_lens(&round_robin), vec![2, 2]);
+
+        let least_busy = shared(ScheduleStrategy::LeastBusy, 3);
+        least_busy.workers[0].push(actor_queue("busy"));
+        least_busy.selector.update_load(0, 1);
+        for i in 0..2 {
+            least_busy.enqueue(actor_queue(&i.to_string()));
+        }
+        assert_eq!(worker_lens(&least_busy), vec![1, 1, 1]);
+
+        // 相同种子的随机策略选择序列可复现
+        let random = shared(ScheduleStrategy::Random, 4);
+        let replay = shared(ScheduleStrategy::Random, 4);
+        let picks: Vec<_> = (0..16).map(|_| random.selector.select()).collect();
+        assert!(picks.iter().all(|&w| w < 4));
+        assert_eq!(picks, (0..16).map(|_| replay.selector.select()).collect::<Vec<_>>());
+    }
+
+    struct Recorder(std::sync::mpsc::Sender<u32>);
+
+    #[async_trait::async_trait]
+    impl Actor for Recorder {
+        async fn receive(&mut self, _ctx: &Context, msg: Message) -> Result<(), SendError> {
+            if let Some(value) = msg.payload.downcast_ref::<u32>() {
+                let _ = self.0.send(*value);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_attached_mailbox_runs_on_worker_queues() {
+        let dispatcher = WorkerPoolDispatcher::new(SchedulerConfig {
+            worker_count: 2,
+            ..SchedulerConfig::default()
+        });
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (mailbox, actor) = test_mailbox(Recorder(tx));
+        let running = dispatcher.attach(mailbox);
+
+        futures::executor::block_on(async {
+            for i in 0..5u32 {
+                actor.send(Message::new(i)).await.unwrap();
+            }
+            actor.stop().await;
+            running.await.unwrap();
+        });
+
+        // 消息经工作线程的运行队列按序处理，Actor 停止后邮箱被移除
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+        assert!(dispatcher.metrics().unwrap().snapshot().completed_tasks >= 5);
+        assert!(dispatcher.shared.actors.is_empty());
+    }
+}
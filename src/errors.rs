@@ -16,11 +16,18 @@ impl fmt::Display for ProtoError {
 
 impl std::error::Error for ProtoError {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum SendError {
     DeadLetter,
     MailboxClosed,
     MailboxFull,
+    BackPressure,
+    ConnectionFailed,
+    NoRoutee,
+    Timeout,
+    BroadcastFailed(Vec<SendError>),
+    SystemShuttingDown,
+    Other(String),
     // 其他错误类型...
 }
 
@@ -36,6 +43,8 @@ impl std::error::Error for SendError {}
 pub enum SpawnError {
     ActorPanicked,
     InvalidProps,
+    DispatcherNotFound(String),
+    DuplicatePid,
     // 其他错误类型...
 }
 
@@ -2,15 +2,20 @@
 
 // 基础模块
 pub mod actor;
+pub mod cluster;
 pub mod config;
 pub mod context;
 pub mod dispatcher;
 pub mod errors;
+pub mod eventstream;
 pub mod message;
+pub mod metrics;
 pub mod middleware;
+pub mod process;
 pub mod supervisor;
 pub mod system;
 pub mod props;
+pub mod routing;
 pub mod supervision;
 // 远程处理模块
 pub mod remote;
@@ -53,6 +58,9 @@ pub mod workflow {
 pub use actor::{Actor, /* Context, */ Props};
 pub use config::SystemConfig;
 pub use errors::{ProtoError, SendError, SpawnError, WorkflowError};
+pub use context::Context;
+pub use message::{Message, SystemMessage};
+pub use process::{Pid, Process};
 pub use system::ActorSystem;
 
 // 内部使用的模块
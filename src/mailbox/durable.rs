@@ -0,0 +1,718 @@
+use super::*;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
+use crate::remote::Serializer;
+
+/// 默认段文件大小上限
+const DEFAULT_SEGMENT_SIZE: u64 = 16 * 1024 * 1024;
+
+/// 记录类型：入队消息
+const RECORD_APPEND: u8 = 0;
+/// 记录类型：消息确认
+const RECORD_ACK: u8 = 1;
+
+/// 记录头：类型(1) + 序号(8) + 长度(4)
+const RECORD_HEADER_LEN: usize = 13;
+
+struct Segment {
+    path: PathBuf,
+    /// 段内追加的最大消息序号，只含确认记录的段为 None
+    max_seq: Option<u64>,
+}
+
+/// 本地分段日志，消息与确认按顺序追加
+struct SegmentLog {
+    dir: PathBuf,
+    segments: VecDeque<Segment>,
+    active: File,
+    active_size: u64,
+    next_segment_id: u64,
+    segment_size: u64,
+    next_seq: u64,
+    unacked: BTreeSet<u64>,
+}
+
+impl SegmentLog {
+    /// 打开日志目录，返回日志以及尚未确认的消息
+    fn open(dir: &Path, segment_size: u64) -> io::Result<(Self, Vec<(u64, Vec<u8>)>)> {
+        fs::create_dir_all(dir)?;
+
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("log") {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse::<u64>().ok()) {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+
+        let mut pending = BTreeMap::new();
+        let mut segments = VecDeque::new();
+        let mut last_seq = 0;
+        for id in &ids {
+            let path = Self::segment_path(dir, *id);
+            let mut max_seq = None;
+            for (kind, seq, data) in Self::read_segment(&path)? {
+                match kind {
+                    RECORD_APPEND => {
+                        pending.insert(seq, data);
+                        max_seq = Some(seq);
+                        last_seq = last_seq.max(seq);
+                    }
+                    RECORD_ACK => {
+                        pending.remove(&seq);
+                    }
+                    _ => break,
+                }
+            }
+            segments.push_back(Segment { path, max_seq });
+        }
+
+        // 恢复后总是写入新段，崩溃时残缺的尾部记录不会被续写
+        let next_segment_id = ids.last().map_or(0, |id| id + 1);
+        let path = Self::segment_path(dir, next_segment_id);
+        let active = OpenOptions::new().create(true).append(true).open(&path)?;
+        segments.push_back(Segment { path, max_seq: None });
+
+        let log = Self {
+            dir: dir.to_path_buf(),
+            segments,
+            active,
+            active_size: 0,
+            next_segment_id: next_segment_id + 1,
+            segment_size,
+            next_seq: last_seq + 1,
+            unacked: pending.keys().copied().collect(),
+        };
+        let recovered = pending.into_iter().collect();
+        Ok((log, recovered))
+    }
+
+    fn segment_path(dir: &Path, id: u64) -> PathBuf {
+        dir.join(format!("{:020}.log", id))
+    }
+
+    /// 读取段内完整记录，遇到残缺记录即停止
+    fn read_segment(path: &Path) -> io::Result<Vec<(u8, u64, Vec<u8>)>> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while offset + RECORD_HEADER_LEN <= bytes.len() {
+            let kind = bytes[offset];
+            let seq = u64::from_le_bytes(bytes[offset + 1..offset + 9].try_into().unwrap());
+            let len = u32::from_le_bytes(bytes[offset + 9..offset + 13].try_into().unwrap()) as usize;
+            offset += RECORD_HEADER_LEN;
+            if offset + len > bytes.len() {
+                break;
+            }
+            records.push((kind, seq, bytes[offset..offset + len].to_vec()));
+            offset += len;
+        }
+        Ok(records)
+    }
+
+    fn write_record(&mut self, kind: u8, seq: u64, data: &[u8]) -> io::Result<()> {
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + data.len());
+        record.push(kind);
+        record.extend_from_slice(&seq.to_le_bytes());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(data);
+
+        self.active.write_all(&record)?;
+        self.active_size += record.len() as u64;
+        Ok(())
+    }
+
+    /// 追加一条消息，返回分配的序号；落盘由调用方在释放日志锁后通过 `sync_handle` 完成
+    fn append(&mut self, data: &[u8]) -> io::Result<u64> {
+        if self.active_size >= self.segment_size {
+            self.roll()?;
+        }
+
+        let seq = self.next_seq;
+        self.write_record(RECORD_APPEND, seq, data)?;
+
+        self.next_seq += 1;
+        self.unacked.insert(seq);
+        if let Some(active) = self.segments.back_mut() {
+            active.max_seq = Some(seq);
+        }
+        Ok(seq)
+    }
+
+    /// 活动段的另一个句柄，切换段之后仍指向写入时的文件
+    fn sync_handle(&self) -> io::Result<File> {
+        self.active.try_clone()
+    }
+
+    /// 确认消息；确认记录不强制落盘，丢失只会导致重复投递
+    fn ack(&mut self, seq: u64) -> io::Result<()> {
+        if !self.unacked.remove(&seq) {
+            return Ok(());
+        }
+        self.write_record(RECORD_ACK, seq, &[])?;
+        self.compact()
+    }
+
+    /// 切换到新的活动段
+    fn roll(&mut self) -> io::Result<()> {
+        self.active.sync_data()?;
+        let path = Self::segment_path(&self.dir, self.next_segment_id);
+        self.active = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.active_size = 0;
+        self.next_segment_id += 1;
+        self.segments.push_back(Segment { path, max_seq: None });
+        self.compact()
+    }
+
+    /// 按顺序删除消息已全部确认的旧段
+    fn compact(&mut self) -> io::Result<()> {
+        while self.segments.len() > 1 {
+            let oldest = &self.segments[0];
+            let fully_acked = match (oldest.max_seq, self.unacked.iter().next()) {
+                (Some(max_seq), Some(&min_unacked)) => min_unacked > max_seq,
+                _ => true,
+            };
+            if !fully_acked {
+                break;
+            }
+            let segment = self.segments.pop_front().unwrap();
+            fs::remove_file(&segment.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// 已落盘、等待处理的消息
+struct Queued {
+    seq: u64,
+    msg: Message,
+    /// 经 `send` 进入的消息占用背压位置，重放的消息不占用
+    holds_slot: bool,
+}
+
+/// 磁盘持久化邮箱，消息在处理成功后才被确认，重启时重放未确认的消息
+pub struct DurableMailbox {
+    config: MailboxConfig,
+    /// 可在共享引用下替换的调度器
+    dispatcher: RwLock<Arc<dyn MailboxDispatcher>>,
+    status: Arc<RwLock<MailboxStatus>>,
+    serializer: Arc<dyn Serializer>,
+    log: Arc<Mutex<SegmentLog>>,
+    /// 串行化追加，入队顺序与序号一致
+    append_order: tokio::sync::Mutex<()>,
+    queue: Arc<Mutex<VecDeque<Queued>>>,
+    /// 已接收但尚未处理的消息数，包括正在落盘的消息
+    admitted: Arc<AtomicUsize>,
+    recovered: Arc<Mutex<Vec<(u64, Vec<u8>)>>>,
+    notify: Arc<Notify>,
+    system_sender: mpsc::UnboundedSender<SystemMessage>,
+    system_receiver: Arc<RwLock<Option<mpsc::UnboundedReceiver<SystemMessage>>>>,
+}
+
+impl DurableMailbox {
+    pub fn open(config: MailboxConfig, path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::open_with_segment_size(config, path, DEFAULT_SEGMENT_SIZE)
+    }
+
+    pub fn open_with_segment_size(
+        config: MailboxConfig,
+        path: impl AsRef<Path>,
+        segment_size: u64,
+    ) -> io::Result<Self> {
+        let serializer = config.serializer.clone().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "durable mailbox requires a serializer")
+        })?;
+        let (log, recovered) = SegmentLog::open(path.as_ref(), segment_size)?;
+        let (system_tx, system_rx) = mpsc::unbounded_channel();
+
+        Ok(Self {
+            dispatcher: RwLock::new(Arc::clone(&config.dispatcher)),
+            config,
+            status: Arc::new(RwLock::new(MailboxStatus::Open)),
+            serializer,
+            log: Arc::new(Mutex::new(log)),
+            append_order: tokio::sync::Mutex::new(()),
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+            admitted: Arc::new(AtomicUsize::new(0)),
+            recovered: Arc::new(Mutex::new(recovered)),
+            notify: Arc::new(Notify::new()),
+            system_sender: system_tx,
+            system_receiver: Arc::new(RwLock::new(Some(system_rx))),
+        })
+    }
+
+    /// 将恢复出的未确认消息反序列化后放回队首
+    async fn replay(&self) -> Result<(), SendError> {
+        let recovered = std::mem::take(&mut *self.recovered.lock().unwrap());
+        if recovered.is_empty() {
+            return Ok(());
+        }
+
+        let mut replayed = Vec::with_capacity(recovered.len());
+        let mut undecodable = Vec::new();
+        for (seq, data) in recovered {
+            match self.serializer.deserialize(&data).await {
+                Ok(msg) => replayed.push(Queued { seq, msg, holds_slot: false }),
+                Err(e) => {
+                    // 无法解码的消息不会再成功，确认后丢弃
+                    log::error!("Dropping undecodable durable message {}: {:?}", seq, e);
+                    undecodable.push(seq);
+                }
+            }
+        }
+        Self::ack(&self.log, undecodable).await;
+
+        self.admitted.fetch_add(replayed.len(), Ordering::AcqRel);
+        let mut queue = self.queue.lock().unwrap();
+        for entry in replayed.into_iter().rev() {
+            queue.push_front(entry);
+        }
+        drop(queue);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// 在阻塞线程池中追加并落盘，日志锁只在写入期间持有
+    async fn append(&self, data: Vec<u8>) -> Result<u64, SendError> {
+        let log = Arc::clone(&self.log);
+        let appended = tokio::task::spawn_blocking(move || -> io::Result<u64> {
+            let (seq, file) = {
+                let mut log = log.lock().unwrap();
+                let seq = log.append(&data)?;
+                (seq, log.sync_handle()?)
+            };
+            file.sync_data()?;
+            Ok(seq)
+        })
+        .await
+        .map_err(|_| SendError::MailboxClosed)?;
+
+        appended.map_err(|e| {
+            log::error!("Failed to append durable message: {:?}", e);
+            SendError::MailboxClosed
+        })
+    }
+
+    /// 在阻塞线程池中写入确认记录并压缩，文件 I/O 不占用运行时线程
+    async fn ack(log: &Arc<Mutex<SegmentLog>>, seqs: Vec<u64>) {
+        if seqs.is_empty() {
+            return;
+        }
+        let segments = Arc::clone(log);
+        let acked = tokio::task::spawn_blocking(move || {
+            let mut segments = segments.lock().unwrap();
+            for seq in seqs {
+                if let Err(e) = segments.ack(seq) {
+                    log::error!("Failed to ack durable message {}: {:?}", seq, e);
+                }
+            }
+        })
+        .await;
+        if let Err(e) = acked {
+            log::error!("Durable ack task failed: {:?}", e);
+        }
+    }
+
+    /// 取出一条消息，调用方处理后负责确认和归还背压位置
+    fn pop(queue: &Mutex<VecDeque<Queued>>, admitted: &AtomicUsize) -> Option<Queued> {
+        let entry = queue.lock().unwrap().pop_front();
+        if entry.is_some() {
+            admitted.fetch_sub(1, Ordering::AcqRel);
+        }
+        entry
+    }
+
+    /// 启动处理循环，`stop` 之后循环退出
+    pub(crate) fn spawn_loop<I: MessageInvoker + ?Sized>(&self, invoker: Arc<I>) -> tokio::task::JoinHandle<()> {
+        let status = Arc::clone(&self.status);
+        let queue = Arc::clone(&self.queue);
+        let admitted = Arc::clone(&self.admitted);
+        let log = Arc::clone(&self.log);
+        let notify = Arc::clone(&self.notify);
+        let system_receiver = Arc::clone(&self.system_receiver);
+        let backpressure = self.config.backpressure.clone();
+        let throughput = self.config.throughput;
+
+        tokio::spawn(async move {
+            let mut system_rx = system_receiver.write().unwrap().take()
+                .expect("Mailbox already started");
+
+            while *status.read().unwrap() == MailboxStatus::Open {
+                // 优先处理系统消息
+                let mut processed = 0;
+                while let Ok(sys_msg) = system_rx.try_recv() {
+                    if let Err(e) = invoker.invoke_system(sys_msg).await {
+                        log::error!("Failed to handle system message: {:?}", e);
+                    }
+                    processed += 1;
+                }
+
+                // 处理用户消息，本轮结束后统一确认
+                let mut acked = Vec::new();
+                while processed < throughput {
+                    let entry = match DurableMailbox::pop(&queue, &admitted) {
+                        Some(entry) => entry,
+                        None => break,
+                    };
+                    if let Err(e) = invoker.invoke_user(entry.msg).await {
+                        // 失败交给监督处理；不确认会阻塞压缩并在重启后反复重放，因此记为死信并确认
+                        log::error!("Dead-lettering durable message {} after handler failure: {:?}", entry.seq, e);
+                    }
+                    acked.push(entry.seq);
+                    if entry.holds_slot {
+                        release_slots(&backpressure, 1);
+                    }
+                    processed += 1;
+                }
+                DurableMailbox::ack(&log, acked).await;
+
+                if processed == 0 {
+                    notify.notified().await;
+                } else {
+                    tokio::task::yield_now().await;
+                }
+            }
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailbox for DurableMailbox {
+    async fn send(&self, msg: Message) -> Result<(), SendError> {
+        let status = *self.status.read().unwrap();
+        if status != MailboxStatus::Open {
+            return Err(SendError::MailboxClosed);
+        }
+
+        let data = self.serializer.serialize(&msg).map_err(|e| {
+            log::error!("Failed to serialize durable message: {:?}", e);
+            SendError::DeadLetter
+        })?;
+
+        if self.admitted.fetch_add(1, Ordering::AcqRel) >= self.config.capacity {
+            self.admitted.fetch_sub(1, Ordering::AcqRel);
+            return Err(SendError::MailboxFull);
+        }
+        if let Err(e) = self.config.acquire_slots(1) {
+            self.admitted.fetch_sub(1, Ordering::AcqRel);
+            return Err(e);
+        }
+
+        // 先落盘再入队，保证入队的消息一定可以重放；落盘期间队列仍可出队
+        let order = self.append_order.lock().await;
+        let seq = match self.append(data).await {
+            Ok(seq) => seq,
+            Err(e) => {
+                self.admitted.fetch_sub(1, Ordering::AcqRel);
+                release_slots(&self.config.backpressure, 1);
+                return Err(e);
+            }
+        };
+        self.queue.lock().unwrap().push_back(Queued { seq, msg, holds_slot: true });
+        drop(order);
+
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    async fn send_system(&self, msg: SystemMessage) -> Result<(), SendError> {
+        self.system_sender.send(msg)
+            .map_err(|_| SendError::MailboxClosed)?;
+        // 空闲的处理循环在等待通知，不唤醒则系统消息要等到下一条用户消息
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    async fn start(&self, invoker: Arc<dyn MessageInvoker>) -> Result<(), SendError> {
+        self.replay().await?;
+        self.spawn_loop(invoker);
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), SendError> {
+        *self.status.write().unwrap() = MailboxStatus::Closed;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    async fn suspend(&self) -> Result<(), SendError> {
+        *self.status.write().unwrap() = MailboxStatus::Suspended;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    async fn resume(&self) -> Result<(), SendError> {
+        *self.status.write().unwrap() = MailboxStatus::Open;
+        Ok(())
+    }
+
+    fn status(&self) -> MailboxStatus {
+        *self.status.read().unwrap()
+    }
+
+    fn len(&self) -> usize {
+        self.admitted.load(Ordering::Acquire) + self.recovered.lock().unwrap().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn dispatcher(&self) -> Arc<dyn MailboxDispatcher> {
+        Arc::clone(&self.dispatcher.read().unwrap())
+    }
+
+    fn metrics(&self) -> Arc<MailboxMetrics> {
+        Arc::new(MailboxMetrics::new())
+    }
+
+    fn set_dispatcher(&self, dispatcher: Arc<dyn MailboxDispatcher>) {
+        *self.dispatcher.write().unwrap() = dispatcher;
+    }
+
+    fn config(&self) -> &MailboxConfig {
+        &self.config
+    }
+
+    async fn clear(&self) -> Result<(), SendError> {
+        // 清空即视为全部确认，并归还占用的背压位置
+        let drained: Vec<_> = self.queue.lock().unwrap().drain(..).collect();
+        self.admitted.fetch_sub(drained.len(), Ordering::AcqRel);
+        let slots = drained.iter().filter(|entry| entry.holds_slot).count();
+        release_slots(&self.config.backpressure, slots);
+
+        let recovered = std::mem::take(&mut *self.recovered.lock().unwrap());
+        let seqs = drained.into_iter().map(|entry| entry.seq).chain(recovered.into_iter().map(|(seq, _)| seq)).collect();
+        Self::ack(&self.log, seqs).await;
+        Ok(())
+    }
+
+    fn stats(&self) -> MailboxStats {
+        MailboxStats {
+            messages_processed: 0,
+            messages_queued: self.len() as u64,
+            messages_dropped: 0,
+            avg_processing_time: Duration::new(0, 0),
+            avg_queuing_time: Duration::new(0, 0),
+            errors: 0,
+            status_changes: 0,
+        }
+    }
+
+    fn set_config(&mut self, config: MailboxConfig) {
+        *self.dispatcher.get_mut().unwrap() = Arc::clone(&config.dispatcher);
+        self.config = config;
+    }
+
+    /// 直接取出的消息视为已交付并立即确认，至少一次语义只适用于 `start` 驱动的处理循环
+    async fn receive(&self) -> Result<Option<Message>, SendError> {
+        self.replay().await?;
+        let entry = match Self::pop(&self.queue, &self.admitted) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        Self::ack(&self.log, vec![entry.seq]).await;
+        if entry.holds_slot {
+            release_slots(&self.config.backpressure, 1);
+        }
+        Ok(Some(entry.msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatcher::BackpressureConfig;
+    use crate::remote::{MessageSerializer, StoredMessageSerializer};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("protoactor-durable-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_segment_log_replays_unacked() {
+        let dir = temp_dir("replay");
+        {
+            let (mut log, recovered) = SegmentLog::open(&dir, DEFAULT_SEGMENT_SIZE).unwrap();
+            assert!(recovered.is_empty());
+            let first = log.append(b"first").unwrap();
+            log.append(b"second").unwrap();
+            log.ack(first).unwrap();
+        }
+
+        // Only the unacknowledged message survives a restart
+        let (log, recovered) = SegmentLog::open(&dir, DEFAULT_SEGMENT_SIZE).unwrap();
+        assert_eq!(recovered, vec![(2, b"second".to_vec())]);
+        assert_eq!(log.next_seq, 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_segment_log_compacts_acked_segments() {
+        let dir = temp_dir("compact");
+        let (mut log, _) = SegmentLog::open(&dir, 1).unwrap();
+        let seqs: Vec<u64> = (0..4).map(|i| log.append(&[i]).unwrap()).collect();
+        for seq in seqs {
+            log.ack(seq).unwrap();
+        }
+        log.roll().unwrap();
+
+        // Every acknowledged segment has been removed
+        assert_eq!(log.segments.len(), 1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    struct Recorder {
+        user: mpsc::UnboundedSender<String>,
+        system: mpsc::UnboundedSender<SystemMessage>,
+        fail_on: Option<&'static str>,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageInvoker for Recorder {
+        fn id(&self) -> &str {
+            "recorder"
+        }
+
+        async fn invoke_system(&self, msg: SystemMessage) -> Result<(), SendError> {
+            let _ = self.system.send(msg);
+            Ok(())
+        }
+
+        async fn invoke_user(&self, msg: Message) -> Result<(), SendError> {
+            let text = msg.payload.downcast_ref::<String>().cloned().unwrap_or_default();
+            let failed = self.fail_on == Some(text.as_str());
+            let _ = self.user.send(text);
+            if failed {
+                Err(SendError::DeadLetter)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn recorder(
+        fail_on: Option<&'static str>,
+    ) -> (Arc<Recorder>, mpsc::UnboundedReceiver<String>, mpsc::UnboundedReceiver<SystemMessage>) {
+        let (user, user_rx) = mpsc::unbounded_channel();
+        let (system, system_rx) = mpsc::unbounded_channel();
+        (Arc::new(Recorder { user, system, fail_on }), user_rx, system_rx)
+    }
+
+    fn config() -> MailboxConfig {
+        let serializer = MessageSerializer::new();
+        serializer.register_serde::<String>("test.String");
+        MailboxConfig {
+            serializer: Some(Arc::new(StoredMessageSerializer::new(serializer))),
+            ..MailboxConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unacked_message_is_replayed_after_restart() {
+        let dir = temp_dir("restart");
+        {
+            let mailbox = DurableMailbox::open(config(), &dir).unwrap();
+            for text in ["a", "b", "c"] {
+                mailbox.send(Message::new(text.to_string())).await.unwrap();
+            }
+
+            // Only "a" is delivered before the mailbox goes away
+            let first = mailbox.receive().await.unwrap().unwrap();
+            assert_eq!(first.payload.downcast_ref::<String>().map(String::as_str), Some("a"));
+        }
+
+        let mailbox = DurableMailbox::open(config(), &dir).unwrap();
+        assert_eq!(mailbox.len(), 2);
+        for expected in ["b", "c"] {
+            let replayed = mailbox.receive().await.unwrap().unwrap();
+            assert_eq!(replayed.payload.downcast_ref::<String>().map(String::as_str), Some(expected));
+        }
+        assert!(mailbox.receive().await.unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_message_does_not_block_compaction() {
+        let dir = temp_dir("failure");
+        {
+            // One message per segment, so an unacknowledged "b" would pin its segment
+            let mailbox = DurableMailbox::open_with_segment_size(config(), &dir, 1).unwrap();
+            for text in ["a", "b", "c"] {
+                mailbox.send(Message::new(text.to_string())).await.unwrap();
+            }
+
+            let (recorder, mut delivered, _) = recorder(Some("b"));
+            let running = mailbox.spawn_loop(recorder);
+            for expected in ["a", "b", "c"] {
+                assert_eq!(delivered.recv().await.unwrap(), expected);
+            }
+            mailbox.stop().await.unwrap();
+            running.await.unwrap();
+
+            assert_eq!(mailbox.log.lock().unwrap().segments.len(), 1);
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        }
+
+        // The failed message was dead-lettered, not replayed
+        let mailbox = DurableMailbox::open(config(), &dir).unwrap();
+        assert_eq!(mailbox.len(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_idle_loop_wakes_for_system_message_and_stop() {
+        let dir = temp_dir("idle");
+        let mailbox = DurableMailbox::open(config(), &dir).unwrap();
+        let (recorder, _, mut system) = recorder(None);
+        let running = mailbox.spawn_loop(recorder);
+
+        // Let the loop go idle before the system message arrives
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        mailbox.send_system(SystemMessage::Stop).await.unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(1), system.recv()).await.unwrap();
+        assert!(matches!(received, Some(SystemMessage::Stop)));
+
+        mailbox.stop().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), running).await.unwrap().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_backpressure_slot_is_released_after_processing() {
+        let dir = temp_dir("backpressure");
+        let controller = Arc::new(BackpressureController::new(BackpressureConfig {
+            max_queue_size: 1,
+            ..BackpressureConfig::default()
+        }));
+        let mailbox = DurableMailbox::open(
+            MailboxConfig {
+                backpressure: Some(Arc::clone(&controller)),
+                ..config()
+            },
+            &dir,
+        ).unwrap();
+
+        mailbox.send(Message::new("first".to_string())).await.unwrap();
+        let rejected = mailbox.send(Message::new("second".to_string())).await;
+        assert!(matches!(rejected, Err(SendError::BackPressure)));
+
+        mailbox.receive().await.unwrap().unwrap();
+        mailbox.send(Message::new("second".to_string())).await.unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
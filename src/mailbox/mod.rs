@@ -2,6 +2,7 @@ mod queue;
 mod priority;
 mod bounded;
 mod unbounded;
+mod durable;
 mod dispatcher;
 mod metrics;
 
@@ -9,16 +10,21 @@ pub use queue::*;
 pub use priority::*;
 pub use bounded::*;
 pub use unbounded::*;
+pub use durable::*;
 pub use dispatcher::*;
 pub use metrics::*;
 
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
 use crate::actor::{Actor, ActorRef};
 use crate::context::Context;
 use crate::message::{Message, SystemMessage};
 use crate::errors::SendError;
 use crate::mailbox::metrics::{MailboxMetrics, MailboxStats};
+use crate::remote::Serializer;
+use crate::dispatcher::{BackpressureController, BackpressureSignal};
 
 /// Mailbox 配置
 #[derive(Debug, Clone)]
@@ -27,6 +33,34 @@ pub struct MailboxConfig {
     pub throughput: usize,
     pub dispatcher: Arc<dyn MailboxDispatcher>,
     pub metrics_enabled: bool,
+    /// 单次交付给 Actor 的最大消息数，1 表示逐条处理
+    pub batch_size: usize,
+    /// 凑批时等待后续消息的最长时间
+    pub batch_max_wait: Duration,
+    /// 持久化邮箱使用的消息序列化器
+    pub serializer: Option<Arc<dyn Serializer>>,
+    /// 队列压力上报的背压控制器
+    pub backpressure: Option<Arc<BackpressureController>>,
+}
+
+impl MailboxConfig {
+    /// 是否启用批量交付
+    pub fn batching_enabled(&self) -> bool {
+        self.batch_size > 1
+    }
+
+    /// 入队前向背压控制器申请 n 个位置，被拒绝时归还已申请的位置
+    pub(crate) fn acquire_slots(&self, n: usize) -> Result<(), SendError> {
+        if let Some(controller) = &self.backpressure {
+            for acquired in 0..n {
+                if controller.try_acquire() == BackpressureSignal::Rejected {
+                    release_slots(&self.backpressure, acquired);
+                    return Err(SendError::BackPressure);
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for MailboxConfig {
@@ -36,6 +70,19 @@ impl Default for MailboxConfig {
             throughput: 100,
             dispatcher: Arc::new(DefaultDispatcher::new(100)),
             metrics_enabled: true,
+            batch_size: 1,
+            batch_max_wait: Duration::from_millis(0),
+            serializer: None,
+            backpressure: None,
+        }
+    }
+}
+
+/// 消息出队或入队失败后归还背压位置
+pub(crate) fn release_slots(backpressure: &Option<Arc<BackpressureController>>, n: usize) {
+    if let Some(controller) = backpressure {
+        for _ in 0..n {
+            controller.release();
         }
     }
 }
@@ -57,8 +104,8 @@ pub trait Mailbox: Send + Sync {
     /// 发送系统消息
     async fn send_system(&self, msg: SystemMessage) -> Result<(), SendError>;
     
-    /// 启动消息处理
-    async fn start(&self, ctx: Arc<Context>) -> Result<(), SendError>;
+    /// 启动消息处理，消息交付给 `invoker`
+    async fn start(&self, invoker: Arc<dyn MessageInvoker>) -> Result<(), SendError>;
     
     /// 停止消息处理
     async fn stop(&self) -> Result<(), SendError>;
@@ -128,6 +175,25 @@ pub trait Mailbox: Send + Sync {
     fn set_config(&mut self, config: MailboxConfig);
 }
 
+/// 邮箱处理循环交付消息的对象，运行中的 Actor 由 `ActorCell` 实现
+#[async_trait::async_trait]
+pub trait MessageInvoker: Send + Sync + 'static {
+    /// 所属 Actor 的标识
+    fn id(&self) -> &str;
+
+    async fn invoke_system(&self, msg: SystemMessage) -> Result<(), SendError>;
+
+    async fn invoke_user(&self, msg: Message) -> Result<(), SendError>;
+
+    /// 一次交付一批用户消息，默认逐条交付，遇到第一个错误即停止
+    async fn invoke_batch(&self, msgs: Vec<Message>) -> Result<(), SendError> {
+        for msg in msgs {
+            self.invoke_user(msg).await?;
+        }
+        Ok(())
+    }
+}
+
 /// 消息处理器
 #[async_trait::async_trait]
 pub trait MessageHandler: Send + Sync {
@@ -144,11 +210,17 @@ pub enum MailboxKind {
     Unbounded,
     Bounded(usize),
     Priority,
+    /// 落盘到 `path` 的持久化邮箱，消息以 `serializer` 编码，例如 `StoredMessageSerializer`
+    Durable {
+        path: PathBuf,
+        serializer: Arc<dyn Serializer>,
+    },
 }
 
 impl MailboxKind {
-    pub fn create(&self, config: MailboxConfig) -> Box<dyn Mailbox> {
-        match self {
+    /// 创建邮箱，只有持久化邮箱会因为打开日志失败而返回错误
+    pub fn create(&self, config: MailboxConfig) -> std::io::Result<Box<dyn Mailbox>> {
+        Ok(match self {
             MailboxKind::Unbounded => Box::new(UnboundedMailbox::new(config)),
             MailboxKind::Bounded(capacity) => {
                 let mut config = config;
@@ -156,6 +228,11 @@ impl MailboxKind {
                 Box::new(BoundedMailbox::new(config))
             }
             MailboxKind::Priority => Box::new(PriorityMailbox::new(config)),
-        }
+            MailboxKind::Durable { path, serializer } => {
+                let mut config = config;
+                config.serializer = Some(Arc::clone(serializer));
+                Box::new(DurableMailbox::open(config, path)?)
+            }
+        })
     }
 } 
\ No newline at end of file
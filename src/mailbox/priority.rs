@@ -1,6 +1,7 @@
 use super::*;
 use priority_queue::PriorityQueue;
 use std::{sync::RwLock, time::Duration};
+use tokio::sync::Notify;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum MessagePriority {
@@ -35,17 +36,33 @@ pub struct PriorityMailbox {
     status: Arc<RwLock<MailboxStatus>>,
     queue: Arc<RwLock<PriorityQueue<Message, i32>>>,
     system_queue: mpsc::UnboundedSender<SystemMessage>,
+    system_receiver: Arc<RwLock<Option<mpsc::UnboundedReceiver<SystemMessage>>>>,
+    // 有新消息入队时唤醒凑批等待
+    notify: Arc<Notify>,
 }
 
 impl PriorityMailbox {
     pub fn new(config: MailboxConfig) -> Self {
-        let (system_tx, _) = mpsc::unbounded_channel();
+        let (system_tx, system_rx) = mpsc::unbounded_channel();
         
         Self {
             config,
             status: Arc::new(RwLock::new(MailboxStatus::Open)),
             queue: Arc::new(RwLock::new(PriorityQueue::new())),
             system_queue: system_tx,
+            system_receiver: Arc::new(RwLock::new(Some(system_rx))),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// 按优先级取出消息直到批次达到 max_size
+    fn fill_batch(queue: &RwLock<PriorityQueue<Message, i32>>, batch: &mut Vec<Message>, max_size: usize) {
+        let mut queue = queue.write().unwrap();
+        while batch.len() < max_size {
+            match queue.pop() {
+                Some((msg, _)) => batch.push(msg),
+                None => break,
+            }
         }
     }
 }
@@ -62,24 +79,73 @@ impl Mailbox for PriorityMailbox {
         if queue.len() >= self.config.capacity {
             return Err(SendError::MailboxFull);
         }
+        self.config.acquire_slots(1)?;
 
         let priority = msg.priority();
         queue.push(msg, priority);
+        drop(queue);
+        self.notify.notify_one();
         Ok(())
     }
 
     async fn send_system(&self, msg: SystemMessage) -> Result<(), SendError> {
         self.system_queue.send(msg)
-            .map_err(|_| SendError::MailboxClosed)
+            .map_err(|_| SendError::MailboxClosed)?;
+        // 系统消息同样打断凑批等待
+        self.notify.notify_one();
+        Ok(())
     }
 
-    async fn start(&self, ctx: Arc<Context>) -> Result<(), SendError> {
+    async fn start(&self, invoker: Arc<dyn MessageInvoker>) -> Result<(), SendError> {
         let queue = Arc::clone(&self.queue);
         let status = Arc::clone(&self.status);
+        let system_receiver = Arc::clone(&self.system_receiver);
+        let notify = Arc::clone(&self.notify);
         let throughput = self.config.throughput;
+        let batch_size = self.config.batch_size;
+        let batch_max_wait = self.config.batch_max_wait;
+        let backpressure = self.config.backpressure.clone();
 
         tokio::spawn(async move {
+            let mut system_rx = system_receiver.write().unwrap().take()
+                .expect("Mailbox already started");
+
             while *status.read().unwrap() == MailboxStatus::Open {
+                // 优先处理系统消息
+                while let Ok(sys_msg) = system_rx.try_recv() {
+                    if let Err(e) = invoker.invoke_system(sys_msg).await {
+                        log::error!("Failed to handle system message: {:?}", e);
+                    }
+                }
+
+                // 批量交付：一次加锁按优先级取出整批
+                if batch_size > 1 {
+                    let max_size = batch_size.min(throughput);
+                    let mut batch = Vec::with_capacity(max_size);
+                    Self::fill_batch(&queue, &mut batch, max_size);
+
+                    // 批次未满时在 batch_max_wait 内等待后续消息，有系统消息待处理时立即交付
+                    if !batch.is_empty() {
+                        let deadline = tokio::time::Instant::now() + batch_max_wait;
+                        while batch.len() < max_size && system_rx.is_empty() {
+                            if tokio::time::timeout_at(deadline, notify.notified()).await.is_err() {
+                                break;
+                            }
+                            Self::fill_batch(&queue, &mut batch, max_size);
+                        }
+                    }
+
+                    if !batch.is_empty() {
+                        let batch_len = batch.len();
+                        if let Err(e) = invoker.invoke_batch(batch).await {
+                            log::error!("Failed to handle message batch: {:?}", e);
+                        }
+                        release_slots(&backpressure, batch_len);
+                    }
+                    tokio::task::yield_now().await;
+                    continue;
+                }
+
                 let mut processed = 0;
                 while processed < throughput {
                     let msg = {
@@ -89,9 +155,10 @@ impl Mailbox for PriorityMailbox {
 
                     match msg {
                         Some(msg) => {
-                            if let Err(e) = ctx.handle(msg).await {
+                            if let Err(e) = invoker.invoke_user(msg).await {
                                 log::error!("Failed to handle message: {:?}", e);
                             }
+                            release_slots(&backpressure, 1);
                             processed += 1;
                         }
                         None => break,
@@ -148,10 +215,13 @@ impl Mailbox for PriorityMailbox {
         &self.config
     }
 
-    fn clear(&mut self) {
-        // Clear the mailbox
+    async fn clear(&self) -> Result<(), SendError> {
+        // 丢弃排队的消息并归还其占用的背压位置
         let mut queue = self.queue.write().unwrap();
-        queue.clear(); // Clear the priority queue
+        let dropped = queue.len();
+        queue.clear();
+        release_slots(&self.config.backpressure, dropped);
+        Ok(())
     }
 
     fn stats(&self) -> MailboxStats {
@@ -178,6 +248,42 @@ impl Mailbox for PriorityMailbox {
             Ok(None) // No messages available
         }
     }
+
+    async fn receive_batch(&self, max_size: usize) -> Result<Vec<Message>, SendError> {
+        // 批次未满时在 batch_max_wait 内等待后续消息
+        let deadline = tokio::time::Instant::now() + self.config.batch_max_wait;
+        let mut batch = Vec::with_capacity(max_size);
+        Self::fill_batch(&self.queue, &mut batch, max_size);
+        while batch.len() < max_size {
+            if tokio::time::timeout_at(deadline, self.notify.notified()).await.is_err() {
+                break;
+            }
+            Self::fill_batch(&self.queue, &mut batch, max_size);
+        }
+        release_slots(&self.config.backpressure, batch.len());
+        Ok(batch)
+    }
+
+    async fn send_batch(&self, messages: Vec<Message>) -> Result<(), SendError> {
+        let status = *self.status.read().unwrap();
+        if status != MailboxStatus::Open {
+            return Err(SendError::MailboxClosed);
+        }
+
+        // 整批只加一次锁，容量不足时整批拒绝
+        let mut queue = self.queue.write().unwrap();
+        if queue.len() + messages.len() > self.config.capacity {
+            return Err(SendError::MailboxFull);
+        }
+        self.config.acquire_slots(messages.len())?;
+        for msg in messages {
+            let priority = msg.priority();
+            queue.push(msg, priority);
+        }
+        drop(queue);
+        self.notify.notify_one();
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -210,4 +316,40 @@ mod tests {
         let received_msg = mailbox.receive().await.unwrap();
         assert_eq!(received_msg, None);
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_priority_mailbox_batch_capacity() {
+        let config = MailboxConfig {
+            capacity: 2,
+            ..MailboxConfig::default()
+        };
+        let mailbox = PriorityMailbox::new(config);
+        let messages: Vec<Message> = (0..3).map(Message::new).collect();
+
+        // The whole batch is rejected when it does not fit
+        assert!(mailbox.send_batch(messages).await.is_err());
+        assert!(mailbox.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_receive_batch_waits_for_batch_max_wait() {
+        let config = MailboxConfig {
+            batch_max_wait: Duration::from_millis(10),
+            ..MailboxConfig::default()
+        };
+        let mailbox = Arc::new(PriorityMailbox::new(config));
+        mailbox.send(Message::new(1)).await.unwrap();
+
+        let sender = Arc::clone(&mailbox);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            sender.send(Message::new(2)).await.unwrap();
+        });
+
+        // The batch is not full, so it waits out batch_max_wait and picks up the late message
+        let started = tokio::time::Instant::now();
+        let batch = mailbox.receive_batch(3).await.unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(started.elapsed(), Duration::from_millis(10));
+    }
+}
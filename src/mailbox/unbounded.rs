@@ -7,7 +7,7 @@ pub struct UnboundedMailbox {
     config: MailboxConfig,
     status: Arc<RwLock<MailboxStatus>>,
     sender: mpsc::UnboundedSender<Message>,
-    receiver: Arc<RwLock<Option<mpsc::UnboundedReceiver<Message>>>>,
+    receiver: Arc<tokio::sync::Mutex<Option<mpsc::UnboundedReceiver<Message>>>>,
     system_sender: mpsc::UnboundedSender<SystemMessage>,
     system_receiver: Arc<RwLock<Option<mpsc::UnboundedReceiver<SystemMessage>>>>,
 }
@@ -21,7 +21,7 @@ impl UnboundedMailbox {
             config,
             status: Arc::new(RwLock::new(MailboxStatus::Open)),
             sender: tx,
-            receiver: Arc::new(RwLock::new(Some(rx))),
+            receiver: Arc::new(tokio::sync::Mutex::new(Some(rx))),
             system_sender: system_tx,
             system_receiver: Arc::new(RwLock::new(Some(system_rx))),
         }
@@ -36,8 +36,12 @@ impl Mailbox for UnboundedMailbox {
             return Err(SendError::MailboxClosed);
         }
 
+        self.config.acquire_slots(1)?;
         self.sender.send(msg)
-            .map_err(|_| SendError::MailboxClosed)
+            .map_err(|_| {
+                release_slots(&self.config.backpressure, 1);
+                SendError::MailboxClosed
+            })
     }
 
     async fn send_system(&self, msg: SystemMessage) -> Result<(), SendError> {
@@ -45,14 +49,17 @@ impl Mailbox for UnboundedMailbox {
             .map_err(|_| SendError::MailboxClosed)
     }
 
-    async fn start(&self, ctx: Arc<Context>) -> Result<(), SendError> {
+    async fn start(&self, invoker: Arc<dyn MessageInvoker>) -> Result<(), SendError> {
         let status = Arc::clone(&self.status);
         let receiver = Arc::clone(&self.receiver);
         let system_receiver = Arc::clone(&self.system_receiver);
         let throughput = self.config.throughput;
+        let batch_size = self.config.batch_size;
+        let batch_max_wait = self.config.batch_max_wait;
+        let backpressure = self.config.backpressure.clone();
 
         tokio::spawn(async move {
-            let mut rx = receiver.write().unwrap().take()
+            let mut rx = receiver.lock().await.take()
                 .expect("Mailbox already started");
             let mut system_rx = system_receiver.write().unwrap().take()
                 .expect("Mailbox already started");
@@ -60,19 +67,68 @@ impl Mailbox for UnboundedMailbox {
             while *status.read().unwrap() == MailboxStatus::Open {
                 // 优先处理系统消息
                 while let Ok(sys_msg) = system_rx.try_recv() {
-                    if let Err(e) = ctx.handle_system(sys_msg).await {
+                    if let Err(e) = invoker.invoke_system(sys_msg).await {
                         log::error!("Failed to handle system message: {:?}", e);
                     }
                 }
 
+                // 批量交付用户消息
+                if batch_size > 1 {
+                    let mut processed = 0;
+                    while processed < throughput {
+                        let mut batch = Vec::with_capacity(batch_size);
+                        while batch.len() < batch_size {
+                            match rx.try_recv() {
+                                Ok(msg) => batch.push(msg),
+                                Err(_) => break,
+                            }
+                        }
+                        if batch.is_empty() {
+                            break;
+                        }
+
+                        // 批次未满时在 batch_max_wait 内继续等待后续消息，
+                        // 系统消息到达时不再等待，先处理系统消息再交付当前批次
+                        let deadline = tokio::time::Instant::now() + batch_max_wait;
+                        while batch.len() < batch_size && system_rx.is_empty() {
+                            tokio::select! {
+                                biased;
+                                sys_msg = system_rx.recv() => {
+                                    if let Some(sys_msg) = sys_msg {
+                                        if let Err(e) = invoker.invoke_system(sys_msg).await {
+                                            log::error!("Failed to handle system message: {:?}", e);
+                                        }
+                                    }
+                                    break;
+                                }
+                                msg = tokio::time::timeout_at(deadline, rx.recv()) => match msg {
+                                    Ok(Some(msg)) => batch.push(msg),
+                                    _ => break,
+                                },
+                            }
+                        }
+
+                        processed += batch.len();
+                        let batch_len = batch.len();
+                        if let Err(e) = invoker.invoke_batch(batch).await {
+                            log::error!("Failed to handle message batch: {:?}", e);
+                        }
+                        release_slots(&backpressure, batch_len);
+                    }
+
+                    tokio::task::yield_now().await;
+                    continue;
+                }
+
                 // 处理用户消息
                 let mut processed = 0;
                 while processed < throughput {
                     match rx.try_recv() {
                         Ok(msg) => {
-                            if let Err(e) = ctx.handle(msg).await {
+                            if let Err(e) = invoker.invoke_user(msg).await {
                                 log::error!("Failed to handle message: {:?}", e);
                             }
+                            release_slots(&backpressure, 1);
                             processed += 1;
                         }
                         Err(_) => break,
@@ -131,9 +187,16 @@ impl Mailbox for UnboundedMailbox {
         &self.config
     }
 
-    fn clear(&mut self) {
-        // Clear the mailbox
-        self.sender = mpsc::unbounded_channel().0; // Reset the sender
+    async fn clear(&self) -> Result<(), SendError> {
+        // 丢弃排队的消息并归还其占用的背压位置
+        let mut receiver = self.receiver.lock().await;
+        let rx = receiver.as_mut().ok_or(SendError::MailboxClosed)?;
+        let mut dropped = 0;
+        while rx.try_recv().is_ok() {
+            dropped += 1;
+        }
+        release_slots(&self.config.backpressure, dropped);
+        Ok(())
     }
 
     fn stats(&self) -> MailboxStats {
@@ -153,16 +216,60 @@ impl Mailbox for UnboundedMailbox {
     }
 
     async fn receive(&self) -> Result<Option<Message>, SendError> {
-        let receiver = self.receiver.read().unwrap();
-        if let Some(ref rx) = *receiver {
+        let mut receiver = self.receiver.lock().await;
+        if let Some(rx) = receiver.as_mut() {
             match rx.recv().await {
-                Some(msg) => Ok(Some(msg)),
+                Some(msg) => {
+                    release_slots(&self.config.backpressure, 1);
+                    Ok(Some(msg))
+                }
                 None => Ok(None), // Channel closed
             }
         } else {
             Err(SendError::MailboxClosed)
         }
     }
+
+    async fn receive_batch(&self, max_size: usize) -> Result<Vec<Message>, SendError> {
+        let mut receiver = self.receiver.lock().await;
+        let rx = receiver.as_mut().ok_or(SendError::MailboxClosed)?;
+
+        // 批次未满时在 batch_max_wait 内等待后续消息
+        let deadline = tokio::time::Instant::now() + self.config.batch_max_wait;
+        let mut batch = Vec::with_capacity(max_size);
+        while batch.len() < max_size {
+            match rx.try_recv() {
+                Ok(msg) => batch.push(msg),
+                Err(mpsc::error::TryRecvError::Empty) => {
+                    match tokio::time::timeout_at(deadline, rx.recv()).await {
+                        Ok(Some(msg)) => batch.push(msg),
+                        _ => break,
+                    }
+                }
+                Err(mpsc::error::TryRecvError::Disconnected) => break,
+            }
+        }
+        release_slots(&self.config.backpressure, batch.len());
+        Ok(batch)
+    }
+
+    async fn send_batch(&self, messages: Vec<Message>) -> Result<(), SendError> {
+        // 整批只检查一次状态
+        let status = *self.status.read().unwrap();
+        if status != MailboxStatus::Open {
+            return Err(SendError::MailboxClosed);
+        }
+
+        let count = messages.len();
+        self.config.acquire_slots(count)?;
+        for (sent, msg) in messages.into_iter().enumerate() {
+            if self.sender.send(msg).is_err() {
+                release_slots(&self.config.backpressure, count - sent);
+                return Err(SendError::MailboxClosed);
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -195,4 +302,42 @@ mod tests {
         let received_msg = mailbox.receive().await.unwrap();
         assert_eq!(received_msg, None);
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_unbounded_mailbox_batch() {
+        let config = MailboxConfig::default();
+        let mailbox = UnboundedMailbox::new(config);
+        let messages: Vec<Message> = (0..5).map(Message::new).collect();
+
+        // Send all messages at once
+        assert!(mailbox.send_batch(messages).await.is_ok());
+
+        // Receive is capped by max_size
+        let batch = mailbox.receive_batch(3).await.unwrap();
+        assert_eq!(batch.len(), 3);
+        let rest = mailbox.receive_batch(10).await.unwrap();
+        assert_eq!(rest.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_receive_batch_waits_for_batch_max_wait() {
+        let config = MailboxConfig {
+            batch_max_wait: Duration::from_millis(10),
+            ..MailboxConfig::default()
+        };
+        let mailbox = Arc::new(UnboundedMailbox::new(config));
+        mailbox.send(Message::new(1)).await.unwrap();
+
+        let sender = Arc::clone(&mailbox);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            sender.send(Message::new(2)).await.unwrap();
+        });
+
+        // The batch is not full, so it waits out batch_max_wait and picks up the late message
+        let started = tokio::time::Instant::now();
+        let batch = mailbox.receive_batch(3).await.unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(started.elapsed(), Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// 消息头中一致性哈希路由键的名称
+pub const HASH_KEY_HEADER: &str = "hash-key";
+
+/// 路由标签在消息头中的键前缀，例如 `route-label.version`
+pub const ROUTE_LABEL_PREFIX: &str = "route-label.";
+
+/// Key/value metadata carried alongside a message
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageHeader {
+    values: HashMap<String, String>,
+}
+
+impl MessageHeader {
+    /// Creates an empty header
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value stored under `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Stores `value` under `key`, replacing any previous value
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(key.into(), value.into());
+    }
+
+    /// Removes the value stored under `key`
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.values.remove(key)
+    }
+
+    /// Iterates over the labels a routee must carry to receive this message
+    pub fn route_labels(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.iter()
+            .filter_map(|(k, v)| k.strip_prefix(ROUTE_LABEL_PREFIX).map(|label| (label, v)))
+    }
+
+    /// Iterates over all entries
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Messages that carry their own consistent-hash routing key
+pub trait Hashable {
+    /// The key used to pick a routee; equal keys always reach the same routee
+    fn hash_key(&self) -> String;
+}
@@ -1,13 +1,17 @@
 mod batch;
 mod envelope;
+mod header;
 mod queue;
 mod system_message;
 
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::sync::OnceLock;
+use dashmap::DashMap;
 use crate::actor::ActorRef;
 
 pub use batch::MessageBatch;
 pub use envelope::Envelope;
+pub use header::{Hashable, MessageHeader, HASH_KEY_HEADER, ROUTE_LABEL_PREFIX};
 pub use queue::MessageQueue;
 pub use system_message::SystemMessage;
 use crate::SendError;
@@ -25,9 +29,23 @@ pub struct Message {
     pub priority: u8,
 }
 
+/// Readable names of payload types seen by the `Message` constructors
+fn payload_type_names() -> &'static DashMap<TypeId, &'static str> {
+    static NAMES: OnceLock<DashMap<TypeId, &'static str>> = OnceLock::new();
+    NAMES.get_or_init(DashMap::new)
+}
+
+fn record_payload_type<T: Any>() {
+    let names = payload_type_names();
+    if !names.contains_key(&TypeId::of::<T>()) {
+        names.insert(TypeId::of::<T>(), std::any::type_name::<T>());
+    }
+}
+
 impl Message {
     /// Creates a new message
     pub fn new<T: Any + Send>(payload: T) -> Self {
+        record_payload_type::<T>();
         Self {
             payload: Box::new(payload),
             sender: None,
@@ -38,6 +56,7 @@ impl Message {
 
     /// Creates a new message with a sender
     pub fn with_sender<T: Any + Send>(payload: T, sender: ActorRef) -> Self {
+        record_payload_type::<T>();
         Self {
             payload: Box::new(payload),
             sender: Some(sender),
@@ -56,8 +75,55 @@ impl Message {
         target.send(self.clone()).await.map_err(|_| SendError::MailboxFull)
     }
 
+    /// Creates a new message routed by the payload's consistent-hash key
+    pub fn hashable<T: Hashable + Any + Send>(payload: T) -> Self {
+        let key = payload.hash_key();
+        Self::new(payload).with_hash_key(key)
+    }
+
+    /// Returns the structured header, if this message carries one
+    pub fn message_header(&self) -> Option<&MessageHeader> {
+        self.header.as_ref().and_then(|h| h.downcast_ref::<MessageHeader>())
+    }
+
+    /// Returns a header value by key
+    pub fn header_value(&self, key: &str) -> Option<&str> {
+        self.message_header().and_then(|h| h.get(key))
+    }
+
+    /// Sets a header value, replacing any non-structured header
+    pub fn with_header_value(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut header = self.message_header().cloned().unwrap_or_default();
+        header.set(key, value);
+        self.header = Some(Box::new(header));
+        self
+    }
+
+    /// Returns the payload's type name for diagnostics
+    ///
+    /// Known for payload types that have gone through one of the `Message` constructors.
+    pub fn payload_type_name(&self) -> Option<&'static str> {
+        payload_type_names().get(&(*self.payload).type_id()).map(|name| *name)
+    }
+
+    /// Sets the key used by consistent-hash routing
+    pub fn with_hash_key(self, key: impl Into<String>) -> Self {
+        self.with_header_value(HASH_KEY_HEADER, key)
+    }
+
+    /// Returns the consistent-hash routing key
+    pub fn hash_key(&self) -> Option<&str> {
+        self.header_value(HASH_KEY_HEADER)
+    }
+
+    /// Restricts routing to routees whose label `key` equals `value`
+    pub fn with_route_label(self, key: &str, value: impl Into<String>) -> Self {
+        self.with_header_value(format!("{}{}", ROUTE_LABEL_PREFIX, key), value)
+    }
+
     /// Creates a new message with a specified priority
     pub fn new_with_priority<T: Any + Send>(payload: T, priority: u8) -> Self {
+        record_payload_type::<T>();
         Self {
             payload: Box::new(payload),
             sender: None,
@@ -1,59 +1,165 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::time::Duration;
-use metrics::{Counter, Gauge, Histogram};
+use metrics::{register_counter, register_gauge, register_histogram, Counter, Gauge, Histogram};
 
-#[derive(Debug, Clone)]
+/// 调度器指标，通过 `metrics` 宏上报并保留本地快照供监控采样
 pub struct DispatcherMetrics {
-    active_tasks: Counter,
+    name: String,
+    active_tasks: Gauge,
     queued_tasks: Gauge,
     completed_tasks: Counter,
     failed_tasks: Counter,
     processing_time: Histogram,
     backpressure_ratio: Gauge,
     worker_utilization: Vec<Gauge>,
+
+    active: AtomicI64,
+    queued: AtomicI64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    processing_nanos: AtomicU64,
+    backpressure: AtomicU64,
+    utilization: Vec<AtomicU64>,
+}
+
+/// 某一时刻的调度器指标快照
+#[derive(Debug, Clone, Default)]
+pub struct DispatcherStats {
+    pub active_tasks: u64,
+    pub queued_tasks: u64,
+    pub completed_tasks: u64,
+    pub failed_tasks: u64,
+    pub avg_processing_time: Duration,
+    pub backpressure_ratio: f64,
+    pub worker_utilization: Vec<f64>,
 }
 
 impl DispatcherMetrics {
-    pub fn new(worker_count: usize) -> Self {
+    pub fn new(name: impl Into<String>, worker_count: usize) -> Self {
+        let name = name.into();
         Self {
-            active_tasks: Counter::new("dispatcher_active_tasks"),
-            queued_tasks: Gauge::new("dispatcher_queued_tasks"),
-            completed_tasks: Counter::new("dispatcher_completed_tasks"),
-            failed_tasks: Counter::new("dispatcher_failed_tasks"),
-            processing_time: Histogram::new("dispatcher_processing_time"),
-            backpressure_ratio: Gauge::new("dispatcher_backpressure_ratio"),
+            active_tasks: register_gauge!("dispatcher_active_tasks", "dispatcher" => name.clone()),
+            queued_tasks: register_gauge!("dispatcher_queued_tasks", "dispatcher" => name.clone()),
+            completed_tasks: register_counter!("dispatcher_completed_tasks", "dispatcher" => name.clone()),
+            failed_tasks: register_counter!("dispatcher_failed_tasks", "dispatcher" => name.clone()),
+            processing_time: register_histogram!("dispatcher_processing_time_seconds", "dispatcher" => name.clone()),
+            backpressure_ratio: register_gauge!("dispatcher_backpressure_ratio", "dispatcher" => name.clone()),
             worker_utilization: (0..worker_count)
-                .map(|id| Gauge::new(&format!("dispatcher_worker_{}_utilization", id)))
+                .map(|id| register_gauge!(
+                    "dispatcher_worker_utilization",
+                    "dispatcher" => name.clone(),
+                    "worker" => id.to_string()
+                ))
                 .collect(),
+
+            active: AtomicI64::new(0),
+            queued: AtomicI64::new(0),
+            completed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            processing_nanos: AtomicU64::new(0),
+            backpressure: AtomicU64::new(0f64.to_bits()),
+            utilization: (0..worker_count).map(|_| AtomicU64::new(0f64.to_bits())).collect(),
+            name,
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn record_task_queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
         self.queued_tasks.increment(1.0);
     }
 
     pub fn record_task_started(&self) {
-        self.active_tasks.increment(1);
+        self.active.fetch_add(1, Ordering::Relaxed);
+        self.active_tasks.increment(1.0);
+        self.queued.fetch_sub(1, Ordering::Relaxed);
         self.queued_tasks.decrement(1.0);
     }
 
     pub fn record_task_completed(&self, duration: Duration, success: bool) {
-        self.active_tasks.decrement(1);
+        self.active.fetch_sub(1, Ordering::Relaxed);
+        self.active_tasks.decrement(1.0);
         if success {
+            self.completed.fetch_add(1, Ordering::Relaxed);
             self.completed_tasks.increment(1);
         } else {
+            self.failed.fetch_add(1, Ordering::Relaxed);
             self.failed_tasks.increment(1);
         }
+        self.processing_nanos.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
         self.processing_time.record(duration.as_secs_f64());
     }
 
     pub fn update_backpressure(&self, ratio: f64) {
+        self.backpressure.store(ratio.to_bits(), Ordering::Relaxed);
         self.backpressure_ratio.set(ratio);
     }
 
     pub fn update_worker_utilization(&self, worker_id: usize, utilization: f64) {
-        if let Some(gauge) = self.worker_utilization.get(worker_id) {
+        if let (Some(gauge), Some(value)) = (self.worker_utilization.get(worker_id), self.utilization.get(worker_id)) {
+            value.store(utilization.to_bits(), Ordering::Relaxed);
             gauge.set(utilization);
         }
     }
-} 
\ No newline at end of file
+
+    pub fn snapshot(&self) -> DispatcherStats {
+        let completed = self.completed.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        let finished = completed + failed;
+        let avg_processing_time = if finished > 0 {
+            Duration::from_nanos(self.processing_nanos.load(Ordering::Relaxed) / finished)
+        } else {
+            Duration::ZERO
+        };
+
+        DispatcherStats {
+            active_tasks: self.active.load(Ordering::Relaxed).max(0) as u64,
+            queued_tasks: self.queued.load(Ordering::Relaxed).max(0) as u64,
+            completed_tasks: completed,
+            failed_tasks: failed,
+            avg_processing_time,
+            backpressure_ratio: f64::from_bits(self.backpressure.load(Ordering::Relaxed)),
+            worker_utilization: self.utilization
+                .iter()
+                .map(|v| f64::from_bits(v.load(Ordering::Relaxed)))
+                .collect(),
+        }
+    }
+}
+
+impl std::fmt::Debug for DispatcherMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DispatcherMetrics")
+            .field("name", &self.name)
+            .field("stats", &self.snapshot())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_tracks_task_lifecycle() {
+        let metrics = DispatcherMetrics::new("test", 2);
+        metrics.record_task_queued();
+        metrics.record_task_queued();
+        metrics.record_task_started();
+        metrics.record_task_completed(Duration::from_millis(10), true);
+        metrics.record_task_started();
+        metrics.record_task_completed(Duration::from_millis(30), false);
+        metrics.update_worker_utilization(1, 0.5);
+
+        let stats = metrics.snapshot();
+        assert_eq!(stats.queued_tasks, 0);
+        assert_eq!(stats.active_tasks, 0);
+        assert_eq!(stats.completed_tasks, 1);
+        assert_eq!(stats.failed_tasks, 1);
+        assert_eq!(stats.avg_processing_time, Duration::from_millis(20));
+        assert_eq!(stats.worker_utilization, vec![0.0, 0.5]);
+    }
+}
@@ -1,3 +1,7 @@
+mod dispatcher;
+
+pub use dispatcher::{DispatcherMetrics, DispatcherStats};
+
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
@@ -1,67 +1,31 @@
 use async_trait::async_trait;
-use tokio::sync::mpsc;
-use std::sync::Arc;
-use crate::{Actor, Context, Message, Pid, Props, Process, SendError};
+use crate::actor::ActorRef;
+use crate::{Message, Pid, Process, SendError};
 
-pub struct ActorProcess<A: Actor> {
-    context: Context,
-    sender: mpsc::UnboundedSender<Message>,
-    _phantom: std::marker::PhantomData<A>,
+/// 本地 Actor 的 Process，消息经由 `ActorRef` 投递到 Actor 的邮箱
+#[derive(Clone)]
+pub struct ActorProcess {
+    pid: Pid,
+    actor: ActorRef,
 }
 
-impl<A: Actor> ActorProcess<A> {
-    pub fn new(context: Context, props: Props<A>) -> Self {
-        let sender = context.sender.clone();
-        
-        // Spawn actor message processing loop
-        let mut ctx = context.clone();
-        tokio::spawn(async move {
-            // Start the actor
-            ctx.actor.started(&mut ctx).await;
-
-            // Process messages
-            while let Some(msg) = ctx.mailbox.recv().await {
-                let next = Next {
-                    middleware: &ctx.middleware,
-                    actor: &mut ctx.actor,
-                };
-                
-                if let Err(err) = next.run(&mut ctx, msg).await {
-                    // Handle error through supervision
-                    ctx.handle_failure(err).await;
-                }
-            }
+impl ActorProcess {
+    pub fn new(pid: Pid, actor: ActorRef) -> Self {
+        Self { pid, actor }
+    }
 
-            // Stop the actor
-            ctx.actor.stopped(&mut ctx).await;
-        });
-        
-        Self {
-            context,
-            sender,
-            _phantom: std::marker::PhantomData,
-        }
+    pub fn actor_ref(&self) -> &ActorRef {
+        &self.actor
     }
 }
 
 #[async_trait]
-impl<A: Actor> Process for ActorProcess<A> {
+impl Process for ActorProcess {
     async fn send_message(&self, message: Message) -> Result<(), SendError> {
-        self.sender.send(message)
-            .map_err(|_| SendError::MailboxFull)
+        self.actor.send(message).await
     }
 
     fn pid(&self) -> Pid {
-        self.context.self_pid.clone()
+        self.pid.clone()
     }
 }
-
-impl<A: Actor> Clone for ActorProcess<A> {
-    fn clone(&self) -> Self {
-        Self {
-            context: self.context.clone(),
-            sender: self.sender.clone(),
-            _phantom: std::marker::PhantomData,
-        }
-    }
-} 
\ No newline at end of file
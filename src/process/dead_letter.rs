@@ -15,8 +15,9 @@ impl DeadLetterProcess {
 #[async_trait]
 impl Process for DeadLetterProcess {
     async fn send_message(&self, message: Message) -> Result<(), SendError> {
+        // 投递目标已无法确定，只记录消息本身
         self.system.event_stream.publish(DeadLetterEvent {
-            pid: message.sender,
+            pid: None,
             message,
         });
         Ok(())
@@ -30,6 +31,7 @@ impl Process for DeadLetterProcess {
     }
 }
 
+/// 无法投递的消息，`pid` 为原投递目标
 pub struct DeadLetterEvent {
     pub pid: Option<Pid>,
     pub message: Message,
@@ -2,13 +2,15 @@ mod actor_process;
 mod dead_letter;
 
 pub use actor_process::ActorProcess;
-pub use dead_letter::DeadLetterProcess;
+pub use dead_letter::{DeadLetterEvent, DeadLetterProcess};
 
 use std::sync::Arc;
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
-use crate::{Message, SystemMessage, SendError, Context, Props, Actor};
+use crate::{Message, SendError, SpawnError};
 
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Pid {
     pub address: String,
     pub id: String,
@@ -23,41 +25,8 @@ impl Clone for Pid {
     }
 }
 
-pub struct Process {
-    context: Context,
-    props: Props,
-}
-
-impl Process {
-    pub fn new(context: Context, props: Props) -> Self {
-        Self { context, props }
-    }
-
-    pub async fn start(&self) {
-        self.context.start().await;
-    }
-
-    pub async fn stop(&self) {
-        self.context.stop().await;
-    }
-
-    pub async fn send_user_message(&self, message: Message) -> Result<(), SendError> {
-        if self.context.is_stopping() {
-            return Err(SendError::DeadLetter);
-        }
-        self.context.receive_message(message).await
-    }
-
-    pub async fn send_system_message(&self, message: SystemMessage) -> Result<(), SendError> {
-        if self.context.is_stopping() {
-            return Err(SendError::DeadLetter);
-        }
-        self.context.receive_system_message(message).await
-    }
-}
-
 pub struct ProcessRegistry {
-    processes: DashMap<String, Arc<Process>>,
+    processes: DashMap<String, Arc<dyn Process>>,
     sequence_id: RwLock<u64>,
 }
 
@@ -81,16 +50,16 @@ impl ProcessRegistry {
         }
     }
 
-    pub async fn add(&self, pid: Pid, process: Process) -> Result<(), SpawnError> {
+    pub async fn add(&self, pid: Pid, process: Arc<dyn Process>) -> Result<(), SpawnError> {
         if self.processes.contains_key(&pid.id) {
             return Err(SpawnError::DuplicatePid);
         }
-        self.processes.insert(pid.id, Arc::new(process));
+        self.processes.insert(pid.id, process);
         Ok(())
     }
 
-    pub fn get(&self, id: &str) -> Option<Arc<Process>> {
-        self.processes.get(id).map(|p| Arc::clone(p))
+    pub fn get(&self, id: &str) -> Option<Arc<dyn Process>> {
+        self.processes.get(id).map(|p| Arc::clone(p.value()))
     }
 
     pub fn remove(&self, id: &str) {
@@ -108,6 +77,7 @@ impl ProcessRegistry {
     }
 }
 
+/// 可按 Pid 寻址的消息接收方，如本地 Actor 与死信
 #[async_trait::async_trait]
 pub trait Process: Send + Sync {
     async fn send_message(&self, message: Message) -> Result<(), SendError>;
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 use crate::actor::{Actor, ActorRef, MockActor};
 use crate::context::Context;
-use crate::dispatcher::{Dispatcher, ThreadPoolDispatcher};
+use crate::dispatcher::{Dispatcher, DispatcherExt, ThreadPoolDispatcher};
 use crate::mailbox::Mailbox;
 use crate::errors::SendError;
 use crate::supervision::{SupervisorStrategy, DefaultStrategy};
@@ -57,7 +57,7 @@ impl Props {
         context.set_actor_ref(actor_ref.clone());
         
         // 启动 Actor
-        self.dispatcher.schedule(async move {
+        self.dispatcher.spawn(async move {
             if let Err(e) = context.start().await {
                 log::error!("Failed to start actor: {}", e);
             }
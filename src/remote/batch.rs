@@ -1,9 +1,13 @@
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
-use tokio::time::interval;
-use crate::{Message, Pid, SendError, RemoteError};
-
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+use tokio::time::{sleep_until, Instant};
+use crate::{Message, Pid};
+use super::{EndpointState, RemoteEndpoint};
+
+#[derive(Debug, Clone)]
 pub struct BatchConfig {
     pub max_batch_size: usize,
     pub max_batch_delay: Duration,
@@ -20,99 +24,209 @@ impl Default for BatchConfig {
     }
 }
 
-pub struct BatchManager {
-    config: BatchConfig,
-    batches: HashMap<String, MessageBatch>,
-    sender: mpsc::UnboundedSender<(String, Vec<Message>)>,
+/// 批次缓冲区的下一步动作
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Flush<T> {
+    /// 达到大小上限或发送期限，立即发送
+    Ready(Vec<T>),
+    /// 等待到期限再发送
+    Wait(Instant),
+    Idle,
 }
 
-impl BatchManager {
-    pub fn new(config: BatchConfig) -> Self {
-        let (sender, receiver) = mpsc::unbounded_channel();
-        
-        // 启动批处理任务
-        tokio::spawn(Self::process_batches(receiver));
-        
+/// 发往同一端点的消息缓冲区
+///
+/// 数量达到 `max_batch_size` 或最早的消息等待超过 `max_batch_delay` 时整批发送。
+pub(crate) struct BatchBuffer<T> {
+    items: Vec<T>,
+    max_size: usize,
+    max_delay: Duration,
+    initial_capacity: usize,
+    // 缓冲区中最早一条消息的入队时间
+    oldest: Option<Instant>,
+}
+
+impl<T> BatchBuffer<T> {
+    pub(crate) fn new(config: &BatchConfig) -> Self {
         Self {
-            config,
-            batches: HashMap::new(),
-            sender,
+            items: Vec::with_capacity(config.initial_buffer_size),
+            max_size: config.max_batch_size.max(1),
+            max_delay: config.max_batch_delay,
+            initial_capacity: config.initial_buffer_size,
+            oldest: None,
         }
     }
 
-    pub async fn add_message(&mut self, target: &Pid, message: Message) -> Result<(), SendError> {
-        let batch = self.batches
-            .entry(target.address.clone())
-            .or_insert_with(|| MessageBatch::new(
-                self.config.max_batch_size,
-                self.config.max_batch_delay,
-                self.config.initial_buffer_size,
-            ));
-
-        batch.add_message(message);
-
-        if batch.should_flush() {
-            self.flush_batch(&target.address).await?;
+    /// 加入一条消息，需要唤醒发送任务时返回 true
+    pub(crate) fn push(&mut self, item: T, now: Instant) -> bool {
+        let first = self.items.is_empty();
+        if first {
+            self.oldest = Some(now);
         }
-
-        Ok(())
+        self.items.push(item);
+        first || self.items.len() >= self.max_size
     }
 
-    async fn flush_batch(&mut self, address: &str) -> Result<(), SendError> {
-        if let Some(batch) = self.batches.get_mut(address) {
-            let messages = batch.take_messages();
-            if !messages.is_empty() {
-                self.sender.send((address.to_string(), messages))
-                    .map_err(|_| SendError::BatchProcessingError)?;
+    pub(crate) fn poll(&mut self, now: Instant) -> Flush<T> {
+        let deadline = match self.oldest {
+            Some(oldest) => oldest + self.max_delay,
+            None => return Flush::Idle,
+        };
+
+        if self.items.len() >= self.max_size {
+            // 剩余消息沿用原期限，不会因为拆批而多等一个周期
+            let rest = self.items.split_off(self.max_size);
+            let batch = std::mem::replace(&mut self.items, rest);
+            if self.items.is_empty() {
+                self.oldest = None;
             }
+            return Flush::Ready(batch);
         }
-        Ok(())
-    }
 
-    async fn process_batches(mut receiver: mpsc::UnboundedReceiver<(String, Vec<Message>)>) {
-        while let Some((address, messages)) = receiver.recv().await {
-            // 处理批量消息
-            if let Err(e) = Self::send_batch(&address, messages).await {
-                log::error!("Failed to send batch to {}: {:?}", address, e);
-            }
+        if now >= deadline {
+            return Flush::Ready(self.take());
         }
+        Flush::Wait(deadline)
     }
 
-    async fn send_batch(address: &str, messages: Vec<Message>) -> Result<(), RemoteError> {
-        // 这里实现批量发送��辑
-        Ok(())
+    /// 取出全部消息
+    pub(crate) fn take(&mut self) -> Vec<T> {
+        self.oldest = None;
+        std::mem::replace(&mut self.items, Vec::with_capacity(self.initial_capacity))
     }
 }
 
-struct MessageBatch {
-    messages: Vec<Message>,
-    max_size: usize,
-    max_delay: Duration,
-    created_at: Instant,
+/// 按端点合并出站消息
+///
+/// 所有批次由同一个发送任务按顺序发出，同一发送方的消息在批内和批间都保持顺序。
+pub struct BatchManager {
+    buffer: Mutex<BatchBuffer<(Pid, Message)>>,
+    notify: Notify,
 }
 
-impl MessageBatch {
-    fn new(max_size: usize, max_delay: Duration, initial_capacity: usize) -> Self {
+impl BatchManager {
+    pub fn new(config: BatchConfig) -> Self {
         Self {
-            messages: Vec::with_capacity(initial_capacity),
-            max_size,
-            max_delay,
-            created_at: Instant::now(),
+            buffer: Mutex::new(BatchBuffer::new(&config)),
+            notify: Notify::new(),
+        }
+    }
+
+    pub fn add_message(&self, target: &Pid, message: Message) {
+        let wake = self.buffer.lock().push((target.clone(), message), Instant::now());
+        if wake {
+            self.notify.notify_one();
         }
     }
 
-    fn add_message(&mut self, message: Message) {
-        self.messages.push(message);
+    /// 启动该端点的批量发送任务
+    pub fn start(self: &Arc<Self>, endpoint: Arc<RemoteEndpoint>) {
+        let manager = Arc::clone(self);
+
+        tokio::spawn(async move {
+            manager
+                .run(
+                    || endpoint.state() == EndpointState::Terminated,
+                    |batch| endpoint.send_batch(batch),
+                )
+                .await;
+        });
+    }
+
+    /// 按大小或期限发出批次，`terminated` 返回 true 时发出剩余消息后退出
+    pub(crate) async fn run<S, F>(&self, terminated: impl Fn() -> bool, mut send: S)
+    where
+        S: FnMut(Vec<(Pid, Message)>) -> F,
+        F: Future<Output = ()>,
+    {
+        loop {
+            if terminated() {
+                // 剩余消息交给端点转入死信
+                let rest = self.buffer.lock().take();
+                send(rest).await;
+                break;
+            }
+
+            let next = self.buffer.lock().poll(Instant::now());
+            match next {
+                Flush::Ready(batch) => send(batch).await,
+                Flush::Wait(deadline) => {
+                    tokio::select! {
+                        _ = sleep_until(deadline) => {}
+                        _ = self.notify.notified() => {}
+                    }
+                }
+                Flush::Idle => self.notify.notified().await,
+            }
+        }
     }
 
-    fn should_flush(&self) -> bool {
-        self.messages.len() >= self.max_size || 
-        self.created_at.elapsed() >= self.max_delay
+    /// 唤醒发送任务，用于端点终止时尽快清空缓冲区
+    pub fn wake(&self) {
+        self.notify.notify_one();
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn take_messages(&mut self) -> Vec<Message> {
-        let messages = std::mem::take(&mut self.messages);
-        self.created_at = Instant::now();
-        messages
+    fn buffer(max_batch_size: usize) -> BatchBuffer<u32> {
+        BatchBuffer::new(&BatchConfig {
+            max_batch_size,
+            max_batch_delay: Duration::from_millis(10),
+            initial_buffer_size: 4,
+        })
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_flush_on_size_keeps_order() {
+        let mut buffer = buffer(3);
+        let now = Instant::now();
+        assert!(buffer.push(1, now));
+        assert!(!buffer.push(2, now));
+        assert!(buffer.push(3, now));
+        buffer.push(4, now);
+
+        assert_eq!(buffer.poll(now), Flush::Ready(vec![1, 2, 3]));
+        assert_eq!(buffer.poll(now), Flush::Wait(now + Duration::from_millis(10)));
+        assert_eq!(buffer.poll(now + Duration::from_millis(10)), Flush::Ready(vec![4]));
+        assert_eq!(buffer.poll(now), Flush::Idle);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timer_flushes_partial_batch() {
+        let manager = Arc::new(BatchManager::new(BatchConfig {
+            max_batch_size: 10,
+            max_batch_delay: Duration::from_millis(50),
+            initial_buffer_size: 4,
+        }));
+        let (sent, mut batches) = tokio::sync::mpsc::unbounded_channel();
+        let running = Arc::clone(&manager);
+        tokio::spawn(async move {
+            running
+                .run(|| false, |batch| {
+                    let _ = sent.send(batch);
+                    async {}
+                })
+                .await;
+        });
+
+        let target = Pid {
+            address: "node-b:9000".to_string(),
+            id: "sink".to_string(),
+        };
+        for i in 0..3u32 {
+            manager.add_message(&target, Message::new(i));
+        }
+
+        // 未达到批次大小，期限之前不发送
+        tokio::time::sleep(Duration::from_millis(49)).await;
+        assert!(batches.try_recv().is_err());
+
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        let batch = batches.try_recv().expect("timer did not flush");
+        let sequence: Vec<u32> = batch.iter().map(|(_, m)| *m.payload.downcast_ref::<u32>().unwrap()).collect();
+        assert_eq!(sequence, vec![0, 1, 2]);
+    }
+}
@@ -1,11 +1,70 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
 use async_trait::async_trait;
-use flate2::{Compress, Decompress};
 use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use metrics::{register_counter, Counter};
+use super::CompressionError;
+
+/// 不压缩时在握手中使用的名称
+pub const NO_COMPRESSION: &str = "none";
+
+/// 默认的最小压缩长度，更小的帧压缩收益不足以抵消开销
+pub const DEFAULT_MIN_COMPRESS_SIZE: usize = 512;
+
+/// 超过该长度的数据在阻塞线程池中压缩，帧最大可达 16 MB，不能占用运行时线程
+pub const BLOCKING_COMPRESS_SIZE: usize = 64 * 1024;
 
 #[async_trait]
 pub trait MessageCompressor: Send + Sync {
+    /// 握手中使用的名称
+    fn name(&self) -> &'static str;
     async fn compress(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError>;
-    async fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError>;
+    /// 解压后超过 `max_size` 字节时返回 `DecompressionFailed`，不会分配更多内存
+    async fn decompress(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>, CompressionError>;
+}
+
+/// 最多读取 `max_size` 字节，多读一个字节用于判断是否超限
+fn read_bounded<R: Read>(reader: R, max_size: usize) -> Result<Vec<u8>, CompressionError> {
+    let mut decompressed = Vec::new();
+    reader
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| CompressionError::DecompressionFailed(e.to_string()))?;
+    if decompressed.len() > max_size {
+        return Err(too_large(max_size));
+    }
+    Ok(decompressed)
+}
+
+/// 数据达到 `threshold` 时在阻塞线程池中执行 `f`，否则直接执行
+async fn run_blocking<T, F>(
+    data: &[u8],
+    threshold: usize,
+    failed: fn(String) -> CompressionError,
+    f: F,
+) -> Result<T, CompressionError>
+where
+    T: Send + 'static,
+    F: FnOnce(&[u8]) -> Result<T, CompressionError> + Send + 'static,
+{
+    if data.len() < threshold {
+        return f(data);
+    }
+    let data = data.to_vec();
+    tokio::task::spawn_blocking(move || f(&data))
+        .await
+        .map_err(|e| failed(e.to_string()))?
+}
+
+/// 解压的开销取决于解压后的长度，按压缩数据长度估计时使用更低的阈值
+fn blocking_decompress_size() -> usize {
+    BLOCKING_COMPRESS_SIZE / 4
+}
+
+fn too_large(max_size: usize) -> CompressionError {
+    CompressionError::DecompressionFailed(format!("decompressed size exceeds {} bytes", max_size))
 }
 
 pub struct GzipCompressor {
@@ -20,25 +79,286 @@ impl GzipCompressor {
     }
 }
 
+impl Default for GzipCompressor {
+    fn default() -> Self {
+        Self {
+            level: Compression::default(),
+        }
+    }
+}
+
 #[async_trait]
 impl MessageCompressor for GzipCompressor {
+    fn name(&self) -> &'static str {
+        "gzip"
+    }
+
+    async fn compress(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let level = self.level;
+        run_blocking(data, BLOCKING_COMPRESS_SIZE, CompressionError::CompressionFailed, move |data| {
+            let mut encoder = GzEncoder::new(Vec::with_capacity(data.len() / 2), level);
+            encoder.write_all(data)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| CompressionError::CompressionFailed(e.to_string()))
+        })
+        .await
+    }
+
+    async fn decompress(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>, CompressionError> {
+        run_blocking(data, blocking_decompress_size(), CompressionError::DecompressionFailed, move |data| {
+            read_bounded(GzDecoder::new(data), max_size)
+        })
+        .await
+    }
+}
+
+pub struct ZstdCompressor {
+    level: i32,
+}
+
+impl ZstdCompressor {
+    pub fn new(level: i32) -> Self {
+        Self { level }
+    }
+}
+
+impl Default for ZstdCompressor {
+    fn default() -> Self {
+        Self::new(zstd::DEFAULT_COMPRESSION_LEVEL)
+    }
+}
+
+#[async_trait]
+impl MessageCompressor for ZstdCompressor {
+    fn name(&self) -> &'static str {
+        "zstd"
+    }
+
+    async fn compress(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let level = self.level;
+        run_blocking(data, BLOCKING_COMPRESS_SIZE, CompressionError::CompressionFailed, move |data| {
+            zstd::stream::encode_all(data, level)
+                .map_err(|e| CompressionError::CompressionFailed(e.to_string()))
+        })
+        .await
+    }
+
+    async fn decompress(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>, CompressionError> {
+        run_blocking(data, blocking_decompress_size(), CompressionError::DecompressionFailed, move |data| {
+            let decoder = zstd::stream::read::Decoder::new(data)
+                .map_err(|e| CompressionError::DecompressionFailed(e.to_string()))?;
+            read_bounded(decoder, max_size)
+        })
+        .await
+    }
+}
+
+/// LZ4 块压缩，原始长度写在压缩数据前
+#[derive(Default)]
+pub struct Lz4Compressor;
+
+#[async_trait]
+impl MessageCompressor for Lz4Compressor {
+    fn name(&self) -> &'static str {
+        "lz4"
+    }
+
     async fn compress(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
-        let mut compressor = Compress::new(self.level, false);
-        let mut compressed = Vec::with_capacity(data.len());
-        
-        compressor.compress_vec(data, &mut compressed, flate2::FlushCompress::Finish)
-            .map_err(|e| CompressionError::CompressionFailed(e.to_string()))?;
-            
-        Ok(compressed)
-    }
-
-    async fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
-        let mut decompressor = Decompress::new(false);
-        let mut decompressed = Vec::with_capacity(data.len() * 2);
-        
-        decompressor.decompress_vec(data, &mut decompressed, flate2::FlushDecompress::Finish)
-            .map_err(|e| CompressionError::DecompressionFailed(e.to_string()))?;
-            
-        Ok(decompressed)
-    }
-} 
\ No newline at end of file
+        Ok(lz4_flex::compress_prepend_size(data))
+    }
+
+    async fn decompress(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>, CompressionError> {
+        // 先检查对端声明的长度，避免按任意长度分配内存
+        let size = data
+            .get(..4)
+            .map(|prefix| u32::from_le_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize)
+            .ok_or_else(|| CompressionError::DecompressionFailed("missing size prefix".to_string()))?;
+        if size > max_size {
+            return Err(too_large(max_size));
+        }
+        lz4_flex::decompress_size_prepended(data)
+            .map_err(|e| CompressionError::DecompressionFailed(e.to_string()))
+    }
+}
+
+/// 可用的压缩算法，按偏好排序
+///
+/// 名称列表用于握手，协商结果通过 `get` 取得对应的实现。
+#[derive(Clone)]
+pub struct CompressorRegistry {
+    compressors: Vec<Arc<dyn MessageCompressor>>,
+}
+
+impl CompressorRegistry {
+    /// 不含任何压缩算法，只能协商出 `none`
+    pub fn new() -> Self {
+        Self {
+            compressors: Vec::new(),
+        }
+    }
+
+    /// 按偏好加入压缩算法，同名的实现会被替换
+    pub fn register(mut self, compressor: Arc<dyn MessageCompressor>) -> Self {
+        self.compressors.retain(|c| c.name() != compressor.name());
+        self.compressors.push(compressor);
+        self
+    }
+
+    /// 握手中声明的名称，最后总是 `none`
+    pub fn names(&self) -> Vec<String> {
+        self.compressors
+            .iter()
+            .map(|c| c.name().to_string())
+            .chain(std::iter::once(NO_COMPRESSION.to_string()))
+            .collect()
+    }
+
+    /// 协商结果对应的实现，`none` 返回 `Ok(None)`
+    pub fn get(&self, name: &str) -> Result<Option<Arc<dyn MessageCompressor>>, CompressionError> {
+        if name == NO_COMPRESSION {
+            return Ok(None);
+        }
+        self.compressors
+            .iter()
+            .find(|c| c.name() == name)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| CompressionError::UnknownCompressor(name.to_string()))
+    }
+}
+
+impl Default for CompressorRegistry {
+    /// zstd、lz4、gzip
+    fn default() -> Self {
+        Self::new()
+            .register(Arc::new(ZstdCompressor::default()))
+            .register(Arc::new(Lz4Compressor))
+            .register(Arc::new(GzipCompressor::default()))
+    }
+}
+
+/// 连接上协商出的压缩方式
+///
+/// 小于 `min_size` 的帧以及压缩后没有变小的帧按原样发送。
+pub struct FrameCompression {
+    compressor: Arc<dyn MessageCompressor>,
+    min_size: usize,
+    uncompressed_bytes: Counter,
+    compressed_bytes: Counter,
+    skipped: Counter,
+}
+
+impl FrameCompression {
+    pub fn new(compressor: Arc<dyn MessageCompressor>, min_size: usize) -> Self {
+        let name = compressor.name();
+        Self {
+            uncompressed_bytes: register_counter!("remote_compression_uncompressed_bytes", "compressor" => name),
+            compressed_bytes: register_counter!("remote_compression_compressed_bytes", "compressor" => name),
+            skipped: register_counter!("remote_compression_skipped_frames", "compressor" => name),
+            compressor,
+            min_size,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.compressor.name()
+    }
+
+    /// 压缩出站负载，返回 None 表示按原样发送
+    pub async fn compress(&self, data: &[u8]) -> Result<Option<Vec<u8>>, CompressionError> {
+        if data.len() < self.min_size {
+            self.skipped.increment(1);
+            return Ok(None);
+        }
+
+        let compressed = self.compressor.compress(data).await?;
+        if compressed.len() >= data.len() {
+            self.skipped.increment(1);
+            return Ok(None);
+        }
+
+        self.uncompressed_bytes.increment(data.len() as u64);
+        self.compressed_bytes.increment(compressed.len() as u64);
+        Ok(Some(compressed))
+    }
+
+    pub async fn decompress(&self, data: &[u8], max_size: usize) -> Result<Vec<u8>, CompressionError> {
+        self.compressor.decompress(data, max_size).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        b"remote actor payload ".iter().cycle().take(4096).copied().collect()
+    }
+
+    #[tokio::test]
+    async fn test_every_compressor_round_trips() {
+        let registry = CompressorRegistry::default();
+        assert_eq!(registry.names(), vec!["zstd", "lz4", "gzip", "none"]);
+
+        let data = sample();
+        for name in ["zstd", "lz4", "gzip"] {
+            let compressor = registry.get(name).unwrap().unwrap();
+            let compressed = compressor.compress(&data).await.unwrap();
+            assert!(compressed.len() < data.len(), "{} did not compress", name);
+            assert_eq!(compressor.decompress(&compressed, data.len()).await.unwrap(), data);
+        }
+        assert!(registry.get("none").unwrap().is_none());
+        assert!(registry.get("brotli").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_large_frames_round_trip_on_blocking_pool() {
+        // 伪随机数据压缩率低，压缩前后都超过阻塞阈值
+        let mut state = 0x2545_f491u32;
+        let data: Vec<u8> = (0..4 * BLOCKING_COMPRESS_SIZE)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state % 16) as u8
+            })
+            .collect();
+        for compressor in [Arc::new(GzipCompressor::default()) as Arc<dyn MessageCompressor>, Arc::new(ZstdCompressor::default())] {
+            let compressed = compressor.compress(&data).await.unwrap();
+            assert!(compressed.len() >= blocking_decompress_size(), "{} output below threshold", compressor.name());
+            assert_eq!(compressor.decompress(&compressed, data.len()).await.unwrap(), data);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_small_frames_are_not_compressed() {
+        let compression = FrameCompression::new(Arc::new(GzipCompressor::default()), 512);
+        assert!(compression.compress(b"ping").await.unwrap().is_none());
+        assert!(compression.compress(&sample()).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frames_are_rejected() {
+        // 高度可压缩的数据，压缩后远小于上限
+        let data = vec![0u8; 1024 * 1024];
+        let registry = CompressorRegistry::default();
+        for name in ["zstd", "lz4", "gzip"] {
+            let compressor = registry.get(name).unwrap().unwrap();
+            let compressed = compressor.compress(&data).await.unwrap();
+            assert!(compressed.len() < 64 * 1024);
+
+            let result = compressor.decompress(&compressed, 64 * 1024).await;
+            assert!(
+                matches!(result, Err(CompressionError::DecompressionFailed(_))),
+                "{} accepted an oversized frame",
+                name
+            );
+            assert_eq!(compressor.decompress(&compressed, data.len()).await.unwrap().len(), data.len());
+        }
+
+        // lz4 声明的长度超过上限时不解压
+        let mut forged = (u32::MAX).to_le_bytes().to_vec();
+        forged.extend_from_slice(&[0u8; 16]);
+        assert!(Lz4Compressor.decompress(&forged, 64 * 1024).await.is_err());
+    }
+}
@@ -1,45 +1,442 @@
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
 use dashmap::DashMap;
-use crate::{Connection, RemoteError, SendError};
+use crate::{Message, Pid, SendError};
+use super::{Connection, HeartbeatManager, RemoteError, Transport};
 
+/// 连接池配置
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// 每个地址的最大连接数
+    pub max_connections: usize,
+    /// 空闲超过该时长的连接会被回收
+    pub idle_ttl: Duration,
+    /// 所有连接的在途帧数都达到该值时新建连接
+    pub grow_threshold: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 5,
+            idle_ttl: Duration::from_secs(60),
+            grow_threshold: 1,
+        }
+    }
+}
+
+/// 某个地址的连接池统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub connections: usize,
+    pub healthy: usize,
+    pub in_flight: usize,
+    /// 累计新建的连接数
+    pub created: u64,
+    /// 累计因空闲或健康检查失败回收的连接数
+    pub evicted: u64,
+}
+
+/// 池中的连接，记录在途帧数和最近使用时间
+pub struct PooledConnection {
+    connection: Box<dyn Connection>,
+    in_flight: AtomicUsize,
+    /// 等待接收方回授信用的发送数
+    stalled: AtomicUsize,
+    healthy: AtomicBool,
+    last_used: Mutex<Instant>,
+}
+
+impl PooledConnection {
+    fn new(connection: Box<dyn Connection>) -> Self {
+        Self {
+            connection,
+            in_flight: AtomicUsize::new(0),
+            stalled: AtomicUsize::new(0),
+            healthy: AtomicBool::new(true),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// 正在发送或等待发送的帧数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// 是否有发送在等待信用
+    pub fn is_credit_stalled(&self) -> bool {
+        self.stalled.load(Ordering::Acquire) > 0
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Acquire)
+    }
+
+    /// 空闲时长，有在途帧时为 0
+    pub fn idle_for(&self) -> Duration {
+        if self.in_flight() > 0 {
+            return Duration::ZERO;
+        }
+        self.last_used.lock().elapsed()
+    }
+
+    /// 发送失败的连接标记为不健康，下次选择时回收
+    pub async fn send(&self, target: &Pid, message: Message) -> Result<(), SendError> {
+        self.acquire_credits(1).await;
+        let _in_flight = Pending::new(&self.in_flight);
+        let result = self.connection.send(target, message).await;
+        *self.last_used.lock() = Instant::now();
+
+        if result.is_err() {
+            self.healthy.store(false, Ordering::Release);
+        }
+        result
+    }
+
+    /// 以一个批量帧发送多条消息
+    pub async fn send_batch(&self, messages: Vec<(Pid, Message)>) -> Result<(), SendError> {
+        self.acquire_credits(messages.len() as u32).await;
+        let _in_flight = Pending::new(&self.in_flight);
+        let result = self.connection.send_batch(messages).await;
+        *self.last_used.lock() = Instant::now();
+
+        if result.is_err() {
+            self.healthy.store(false, Ordering::Release);
+        }
+        result
+    }
+
+    /// 信用不足时等待接收方回授，信用帧由该连接的读取任务处理
+    ///
+    /// 等待信用的发送不计入在途帧，否则连接池会因此新建连接，绕过接收方的信用窗口。
+    async fn acquire_credits(&self, messages: u32) {
+        if let Some(credits) = self.connection.credits() {
+            let _stalled = Pending::new(&self.stalled);
+            credits.acquire(messages).await;
+        }
+    }
+
+    /// 通过心跳帧检查连接是否可用
+    async fn check(&self) -> bool {
+        let healthy = self.connection.ping().await.is_ok();
+        if !healthy {
+            self.healthy.store(false, Ordering::Release);
+        }
+        healthy
+    }
+}
+
+// 在途或等待信用的计数，发送被取消时也会归还
+struct Pending<'a>(&'a AtomicUsize);
+
+impl<'a> Pending<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::AcqRel);
+        Self(counter)
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Default)]
+struct AddressPool {
+    connections: Vec<Arc<PooledConnection>>,
+    created: u64,
+    evicted: u64,
+}
+
+impl AddressPool {
+    fn evict(&mut self, keep: impl Fn(&PooledConnection) -> bool) -> usize {
+        let before = self.connections.len();
+        self.connections.retain(|conn| keep(conn));
+        let evicted = before - self.connections.len();
+        self.evicted += evicted as u64;
+        evicted
+    }
+}
+
+/// 选择结果
+#[derive(Debug, PartialEq, Eq)]
+enum Pick {
+    Existing(usize),
+    Grow,
+}
+
+/// 选择在途帧最少的连接，全部繁忙且未达上限时新建
+///
+/// 有连接在等待信用时说明接收方在限流，此时不新建连接，避免放大总的信用窗口。
+fn pick(loads: &[usize], credit_stalled: bool, max_connections: usize, grow_threshold: usize) -> Pick {
+    let least = loads.iter().enumerate().min_by_key(|(_, load)| **load);
+    match least {
+        Some((_, load)) if *load >= grow_threshold && !credit_stalled && loads.len() < max_connections => Pick::Grow,
+        Some((index, _)) => Pick::Existing(index),
+        None => Pick::Grow,
+    }
+}
+
+/// 按地址管理的连接池
+///
+/// 按需增长到 `max_connections`，发送时选择在途帧最少的连接，
+/// 空闲超过 `idle_ttl` 或健康检查失败的连接会被回收。
 pub struct ConnectionPool {
-    connections: DashMap<String, Vec<Arc<RwLock<Box<dyn Connection>>>>>,
-    max_connections: usize,
+    pools: DashMap<String, AddressPool>,
+    config: PoolConfig,
+    heartbeat: Option<Arc<HeartbeatManager>>,
 }
 
 impl ConnectionPool {
     pub fn new(max_connections: usize) -> Self {
-        Self {
-            connections: DashMap::new(),
+        Self::with_config(PoolConfig {
             max_connections,
+            ..PoolConfig::default()
+        })
+    }
+
+    pub fn with_config(config: PoolConfig) -> Self {
+        Self {
+            pools: DashMap::new(),
+            config,
+            heartbeat: None,
+        }
+    }
+
+    /// 池中的每个连接都把收到的 Pong 交给 `manager`
+    pub fn with_heartbeat(mut self, manager: Arc<HeartbeatManager>) -> Self {
+        self.heartbeat = Some(manager);
+        self
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// 获取发往 `address` 的连接，需要时通过 `transport` 新建
+    pub async fn acquire(
+        &self,
+        address: &str,
+        transport: &dyn Transport,
+    ) -> Result<Arc<PooledConnection>, RemoteError> {
+        if let Some(conn) = self.select(address) {
+            return Ok(conn);
+        }
+
+        match transport.connect(address).await {
+            Ok(connection) => Ok(self.add_connection(address.to_string(), connection)),
+            Err(e) => {
+                // 新建失败时退回到现有连接
+                log::warn!("Failed to open pooled connection to {}: {:?}", address, e);
+                self.get_connection(address)
+            }
+        }
+    }
+
+    /// 在途帧最少的健康连接，不新建连接
+    pub fn get_connection(&self, address: &str) -> Result<Arc<PooledConnection>, RemoteError> {
+        let pool = self.pools.get(address);
+        pool.and_then(|pool| {
+            pool.connections
+                .iter()
+                .filter(|conn| conn.is_healthy())
+                .min_by_key(|conn| conn.in_flight())
+                .cloned()
+        })
+        .ok_or_else(|| RemoteError::ConnectionError("No available connection".to_string()))
+    }
+
+    /// 需要新建连接时返回 None
+    fn select(&self, address: &str) -> Option<Arc<PooledConnection>> {
+        let mut pool = self.pools.get_mut(address)?;
+        pool.evict(PooledConnection::is_healthy);
+
+        let loads: Vec<usize> = pool.connections.iter().map(|conn| conn.in_flight()).collect();
+        let credit_stalled = pool.connections.iter().any(|conn| conn.is_credit_stalled());
+        match pick(&loads, credit_stalled, self.config.max_connections, self.config.grow_threshold) {
+            Pick::Existing(index) => Some(Arc::clone(&pool.connections[index])),
+            Pick::Grow => None,
         }
     }
 
-    pub async fn get_connection(&self, address: &str) -> Result<Arc<RwLock<Box<dyn Connection>>>, RemoteError> {
-        if let Some(mut conns) = self.connections.get_mut(address) {
-            // 简单的轮询策略
-            if let Some(conn) = conns.pop() {
-                conns.insert(0, Arc::clone(&conn));
-                return Ok(conn);
+    /// 加入新连接，池已满时丢弃新连接并返回现有连接
+    pub fn add_connection(&self, address: String, connection: Box<dyn Connection>) -> Arc<PooledConnection> {
+        let mut pool = self.pools.entry(address).or_default();
+        if pool.connections.len() >= self.config.max_connections {
+            if let Some(conn) = pool.connections.iter().min_by_key(|conn| conn.in_flight()) {
+                return Arc::clone(conn);
             }
         }
-        Err(RemoteError::ConnectionError("No available connection".to_string()))
+
+        if let Some(manager) = &self.heartbeat {
+            connection.attach_heartbeat(pool.key(), Arc::clone(manager));
+        }
+        let conn = Arc::new(PooledConnection::new(connection));
+        pool.connections.push(Arc::clone(&conn));
+        pool.created += 1;
+        conn
     }
 
-    pub async fn add_connection(&self, address: String, connection: Box<dyn Connection>) {
-        let conn = Arc::new(RwLock::new(connection));
-        self.connections
-            .entry(address)
-            .or_insert_with(Vec::new)
-            .push(conn);
+    /// 回收空闲超时的连接，返回回收数量
+    pub fn evict_idle(&self) -> usize {
+        let ttl = self.config.idle_ttl;
+        self.pools
+            .iter_mut()
+            .map(|mut pool| pool.evict(|conn| conn.is_healthy() && conn.idle_for() < ttl))
+            .sum()
     }
 
-    pub async fn remove_connection(&self, address: &str, index: usize) {
-        if let Some(mut conns) = self.connections.get_mut(address) {
-            if index < conns.len() {
-                conns.remove(index);
+    /// 随心跳执行的健康检查：向空闲连接发送 Ping，回收失败和空闲超时的连接
+    ///
+    /// 返回检查后仍可用的连接数。
+    pub async fn check_health(&self, address: &str) -> usize {
+        let idle: Vec<Arc<PooledConnection>> = match self.pools.get(address) {
+            Some(pool) => {
+                let idle: Vec<_> = pool.connections.iter().filter(|conn| conn.in_flight() == 0).cloned().collect();
+                if idle.is_empty() {
+                    // 全部繁忙时至少探测一个连接，保证心跳不中断
+                    pool.connections.iter().min_by_key(|conn| conn.in_flight()).cloned().into_iter().collect()
+                } else {
+                    idle
+                }
+            }
+            None => return 0,
+        };
+
+        for conn in idle {
+            if !conn.check().await {
+                log::warn!("Pooled connection to {} failed health check", address);
             }
         }
+
+        let ttl = self.config.idle_ttl;
+        match self.pools.get_mut(address) {
+            Some(mut pool) => {
+                pool.evict(|conn| conn.is_healthy() && conn.idle_for() < ttl);
+                pool.connections.len()
+            }
+            None => 0,
+        }
+    }
+
+    /// 移除某个地址的全部连接
+    pub fn remove_address(&self, address: &str) {
+        self.pools.remove(address);
     }
-} 
\ No newline at end of file
+
+    pub fn stats(&self, address: &str) -> PoolStats {
+        match self.pools.get(address) {
+            Some(pool) => PoolStats {
+                connections: pool.connections.len(),
+                healthy: pool.connections.iter().filter(|conn| conn.is_healthy()).count(),
+                in_flight: pool.connections.iter().map(|conn| conn.in_flight()).sum(),
+                created: pool.created,
+                evicted: pool.evicted,
+            },
+            None => PoolStats::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use super::*;
+    use super::super::{FlowControlConfig, RemoteConfig, SendCredits};
+
+    #[test]
+    fn test_pick_least_loaded_then_grow() {
+        // 空池需要新建
+        assert_eq!(pick(&[], false, 3, 1), Pick::Grow);
+        // 有空闲连接时复用
+        assert_eq!(pick(&[2, 0, 1], false, 3, 1), Pick::Existing(1));
+        // 全部繁忙且未满时增长
+        assert_eq!(pick(&[2, 1], false, 3, 1), Pick::Grow);
+        // 已满时选在途最少的
+        assert_eq!(pick(&[4, 2, 3], false, 3, 1), Pick::Existing(1));
+        // 接收方限流时不增长
+        assert_eq!(pick(&[2, 1], true, 3, 1), Pick::Existing(1));
+    }
+
+    /// 使用固定信用窗口、从不回授信用的连接
+    struct WindowedConnection {
+        credits: Arc<SendCredits>,
+    }
+
+    #[async_trait]
+    impl Connection for WindowedConnection {
+        async fn send(&self, _target: &Pid, _message: Message) -> Result<(), SendError> {
+            Ok(())
+        }
+
+        async fn send_batch(&self, _messages: Vec<(Pid, Message)>) -> Result<(), SendError> {
+            Ok(())
+        }
+
+        async fn ping(&self) -> Result<(), RemoteError> {
+            Ok(())
+        }
+
+        fn credits(&self) -> Option<Arc<SendCredits>> {
+            Some(Arc::clone(&self.credits))
+        }
+    }
+
+    struct WindowedTransport {
+        connects: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Transport for WindowedTransport {
+        async fn start(&self, _config: &RemoteConfig) -> Result<(), RemoteError> {
+            Ok(())
+        }
+
+        async fn connect(&self, address: &str) -> Result<Box<dyn Connection>, SendError> {
+            self.connects.fetch_add(1, Ordering::AcqRel);
+            let config = FlowControlConfig {
+                message_window: 2,
+                ..FlowControlConfig::default()
+            };
+            Ok(Box::new(WindowedConnection {
+                credits: Arc::new(SendCredits::new(&config, address)),
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_credit_stalled_sender_does_not_grow_pool() {
+        let pool = ConnectionPool::new(3);
+        let transport = WindowedTransport { connects: AtomicUsize::new(0) };
+        let target = Pid {
+            address: "node-b".to_string(),
+            id: "sink".to_string(),
+        };
+
+        // 用完唯一连接的窗口，下一条消息等待信用
+        let conn = pool.acquire("node-b", &transport).await.unwrap();
+        for _ in 0..2 {
+            conn.send(&target, Message::new(())).await.unwrap();
+        }
+        let stalled = {
+            let conn = Arc::clone(&conn);
+            let target = target.clone();
+            tokio::spawn(async move { conn.send(&target, Message::new(())).await })
+        };
+        while !conn.is_credit_stalled() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(conn.in_flight(), 0);
+
+        // 新的发送复用该连接，总窗口仍是一个连接的窗口
+        let next = pool.acquire("node-b", &transport).await.unwrap();
+        assert!(Arc::ptr_eq(&conn, &next));
+        assert_eq!(transport.connects.load(Ordering::Acquire), 1);
+        assert_eq!(pool.stats("node-b").connections, 1);
+
+        stalled.abort();
+    }
+}
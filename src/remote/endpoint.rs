@@ -1,10 +1,29 @@
 use std::sync::Arc;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+use parking_lot::Mutex;
 use tokio::sync::RwLock;
-use crate::{Message, Pid, SendError, ActorSystem, Transport};
+use crate::{Message, Pid, SendError, ActorSystem};
 use std::time::Duration;
-use connection_pool::ConnectionPool;
-use heartbeat::HeartbeatManager;
-use reconnection::ReconnectionStrategy;
+use super::connection_pool::{ConnectionPool, PoolConfig, PoolStats};
+use super::batch::{BatchConfig, BatchManager};
+use super::heartbeat::HeartbeatManager;
+use super::reconnection::ReconnectionStrategy;
+use super::Transport;
+use crate::process::DeadLetterEvent;
+use super::{EndpointState, PhiAccrualConfig, EndpointTerminated, RemoteWatchRegistry, SystemMessage};
+use super::endpoint_state::SendQueue;
+
+/// 发送队列的默认容量
+pub const DEFAULT_SEND_QUEUE_CAPACITY: usize = 1024;
+
+// 重连后每个批量帧携带的排队消息数
+const FLUSH_CHUNK: usize = 64;
+
+struct EndpointInner {
+    state: EndpointState,
+    queue: SendQueue<(Pid, Message)>,
+}
 
 pub struct RemoteEndpoint {
     address: String,
@@ -13,6 +32,9 @@ pub struct RemoteEndpoint {
     connection_pool: Arc<ConnectionPool>,
     heartbeat_manager: Arc<HeartbeatManager>,
     reconnection_strategy: ReconnectionStrategy,
+    batcher: Option<Arc<BatchManager>>,
+    watches: Arc<RemoteWatchRegistry>,
+    inner: Mutex<EndpointInner>,
 }
 
 impl RemoteEndpoint {
@@ -21,69 +43,587 @@ impl RemoteEndpoint {
         system: Arc<ActorSystem>,
         transport: Arc<RwLock<Box<dyn Transport>>>,
     ) -> Self {
+        let heartbeat_manager = Arc::new(HeartbeatManager::new(
+            Duration::from_secs(1),
+            PhiAccrualConfig::default(),
+        ));
         Self {
             address,
             system,
             transport,
-            connection_pool: Arc::new(ConnectionPool::new(5)),
-            heartbeat_manager: Arc::new(HeartbeatManager::new(
-                Duration::from_secs(5),
-                Duration::from_secs(15),
-            )),
+            connection_pool: Arc::new(ConnectionPool::new(5).with_heartbeat(Arc::clone(&heartbeat_manager))),
+            heartbeat_manager,
             reconnection_strategy: ReconnectionStrategy::new(
                 5,
                 Duration::from_secs(1),
                 Duration::from_secs(30),
             ),
+            batcher: None,
+            watches: Arc::new(RemoteWatchRegistry::new()),
+            inner: Mutex::new(EndpointInner {
+                state: EndpointState::Connecting,
+                queue: SendQueue::new(DEFAULT_SEND_QUEUE_CAPACITY),
+            }),
+        }
+    }
+
+    /// 与 `RemoteContext` 共享监视关系，端点终止时据此发送 Terminated
+    pub fn with_watches(mut self, watches: Arc<RemoteWatchRegistry>) -> Self {
+        self.watches = watches;
+        self
+    }
+
+    pub fn with_pool_config(mut self, config: PoolConfig) -> Self {
+        self.connection_pool = Arc::new(
+            ConnectionPool::with_config(config).with_heartbeat(Arc::clone(&self.heartbeat_manager)),
+        );
+        self
+    }
+
+    /// 合并发往该端点的消息，按批次大小或延迟发送
+    pub fn with_batching(mut self, config: BatchConfig) -> Self {
+        self.batcher = Some(Arc::new(BatchManager::new(config)));
+        self
+    }
+
+    pub fn with_reconnection_strategy(mut self, strategy: ReconnectionStrategy) -> Self {
+        self.reconnection_strategy = strategy;
+        self
+    }
+
+    /// 设置未连接期间发送队列的容量
+    pub fn with_send_queue_capacity(self, capacity: usize) -> Self {
+        self.inner.lock().queue = SendQueue::new(capacity);
+        self
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn state(&self) -> EndpointState {
+        self.inner.lock().state
+    }
+
+    /// 等待发送的排队消息数
+    pub fn queued(&self) -> usize {
+        self.inner.lock().queue.len()
+    }
+
+    /// 建立一条到远程节点的连接并放入连接池
+    pub async fn connect(&self) -> Result<(), SendError> {
+        let connection = self.transport.read().await.connect(&self.address).await?;
+        self.connection_pool.add_connection(self.address.clone(), connection);
+        Ok(())
+    }
+
+    pub fn heartbeat_manager(&self) -> Arc<HeartbeatManager> {
+        Arc::clone(&self.heartbeat_manager)
+    }
+
+    /// 最近一次心跳测得的 RTT
+    pub fn rtt(&self) -> Option<Duration> {
+        self.heartbeat_manager.rtt(&self.address)
+    }
+
+    pub fn phi(&self) -> f64 {
+        self.heartbeat_manager.phi(&self.address)
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        self.connection_pool.stats(&self.address)
+    }
+
+    /// 随心跳检查池中的连接，没有可用连接时按连接断开处理
+    pub async fn check_connections(&self) {
+        if self.state() != EndpointState::Connected {
+            return;
+        }
+        if self.connection_pool.check_health(&self.address).await == 0 {
+            self.handle_connection_lost().await;
         }
     }
 
+    /// 首次建立连接，由 `EndpointManager` 在创建端点后启动
+    pub async fn start(&self) {
+        self.establish().await;
+    }
+
+    /// 发送消息
+    ///
+    /// 连接建立前和重连期间消息进入有界队列，连接恢复后按顺序发送；
+    /// 队列已满或端点已终止时消息转入死信。启用批量发送时消息先进入批次缓冲区。
     pub async fn send(&self, target: &Pid, message: Message) -> Result<(), SendError> {
-        if let Ok(conn) = self.connection_pool.get_connection(&self.address).await {
-            let mut conn = conn.write().await;
-            conn.send(target, message).await
-        } else {
-            // 尝试重连
-            self.reconnection_strategy.attempt_reconnect(self).await?;
-            self.send(target, message).await
+        let message = match self.admit(target, message)? {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+
+        if let Some(batcher) = &self.batcher {
+            batcher.add_message(target, message);
+            return Ok(());
+        }
+
+        match self.send_direct(target, message.clone()).await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                log::warn!("Send to {} failed, reconnecting: {:?}", self.address, e);
+                self.requeue(vec![(target.clone(), message)]);
+                self.handle_connection_lost().await;
+                match self.state() {
+                    EndpointState::Terminated => Err(SendError::DeadLetter),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// 发送一批消息，由批量发送任务调用
+    pub(crate) async fn send_batch(&self, batch: Vec<(Pid, Message)>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let state = self.state();
+        if state == EndpointState::Terminated {
+            for (target, message) in batch {
+                self.dead_letter(target, message);
+            }
+            return;
+        }
+        if state != EndpointState::Connected {
+            self.requeue(batch);
+            return;
+        }
+
+        if let Err(e) = self.send_direct_batch(batch.clone()).await {
+            log::warn!("Batch send to {} failed, reconnecting: {:?}", self.address, e);
+            self.requeue(batch);
+            self.handle_connection_lost().await;
+        }
+    }
+
+    /// 可以直接发送时返回消息，否则排队或转入死信
+    fn admit(&self, target: &Pid, message: Message) -> Result<Option<Message>, SendError> {
+        let mut inner = self.inner.lock();
+        match inner.state {
+            EndpointState::Connected if inner.queue.is_empty() => Ok(Some(message)),
+            EndpointState::Terminated => {
+                drop(inner);
+                self.dead_letter(target.clone(), message);
+                Err(SendError::DeadLetter)
+            }
+            _ => {
+                // 队列中还有未发送的消息时也要排队，保证顺序
+                match inner.queue.push((target.clone(), message)) {
+                    Ok(()) => Ok(None),
+                    Err((target, message)) => {
+                        drop(inner);
+                        self.dead_letter(target, message);
+                        Err(SendError::MailboxFull)
+                    }
+                }
+            }
         }
     }
 
+    /// 未能发出的消息早于队列中的消息，放回队首；超出容量时队尾最新的消息转入死信
+    fn requeue(&self, messages: Vec<(Pid, Message)>) {
+        let overflow = self.inner.lock().queue.requeue_front(messages);
+        for (target, message) in overflow {
+            self.dead_letter(target, message);
+        }
+    }
+
+    async fn send_direct(&self, target: &Pid, message: Message) -> Result<(), SendError> {
+        let conn = {
+            let transport = self.transport.read().await;
+            self.connection_pool.acquire(&self.address, transport.as_ref()).await
+                .map_err(|_| SendError::ConnectionFailed)?
+        };
+        conn.send(target, message).await
+    }
+
+    async fn send_direct_batch(&self, batch: Vec<(Pid, Message)>) -> Result<(), SendError> {
+        let conn = {
+            let transport = self.transport.read().await;
+            self.connection_pool.acquire(&self.address, transport.as_ref()).await
+                .map_err(|_| SendError::ConnectionFailed)?
+        };
+        conn.send_batch(batch).await
+    }
+
+    /// 连接断开，进入重连状态
+    ///
+    /// 已在连接或重连中时直接返回，由正在进行的重连负责发送队列中的消息。
     pub async fn handle_connection_lost(&self) {
-        // 通知所有相关的 actor
-        // 清理连接池
-        // 尝试重连
+        if !self.transition(EndpointState::Connected, EndpointState::Reconnecting) {
+            return;
+        }
+        self.establish().await;
     }
 
-    pub async fn send_heartbeat(&self, heartbeat: HeartbeatMessage) -> Result<(), SendError> {
-        let message = Message::new(heartbeat);
-        self.send(&Pid::new(), message).await
+    /// 按重连策略建立连接，成功后发送队列中的消息，失败则终止端点
+    async fn establish(&self) {
+        match self.reconnection_strategy.attempt_reconnect(self).await {
+            Ok(()) => self.flush().await,
+            Err(e) => {
+                log::error!("Endpoint {} terminated: {:?}", self.address, e);
+                self.terminate().await;
+            }
+        }
+    }
+
+    /// 按入队顺序发送排队消息，队列清空后才切换为 Connected
+    async fn flush(&self) {
+        loop {
+            let batch = {
+                let mut inner = self.inner.lock();
+                if inner.queue.is_empty() {
+                    inner.state = EndpointState::Connected;
+                    return;
+                }
+                inner.queue.take(FLUSH_CHUNK)
+            };
+
+            if let Err(e) = self.send_direct_batch(batch.clone()).await {
+                log::warn!("Flush to {} failed, reconnecting: {:?}", self.address, e);
+                self.requeue(batch);
+                return Box::pin(self.establish()).await;
+            }
+        }
+    }
+
+    /// 重连次数耗尽：排队消息转入死信，通知监视者并发布 `EndpointTerminated`
+    async fn terminate(&self) {
+        let queued = {
+            let mut inner = self.inner.lock();
+            inner.state = EndpointState::Terminated;
+            inner.queue.drain()
+        };
+        if let Some(batcher) = &self.batcher {
+            // 让批量发送任务把缓冲区中的消息转入死信并退出
+            batcher.wake();
+        }
+        let dead_letters = queued.len();
+        for (target, message) in queued {
+            self.dead_letter(target, message);
+        }
+
+        // 该节点上被监视的 Actor 一律视为已终止
+        for (target, watchers) in self.watches.address_terminated(&self.address) {
+            for watcher in watchers {
+                let terminated = Message::new(SystemMessage::Terminated {
+                    who: target.clone(),
+                    address_terminated: true,
+                });
+                if let Err(e) = self.system.send(&watcher, terminated).await {
+                    log::warn!("Failed to deliver Terminated for {} to {}: {:?}", target.id, watcher.id, e);
+                }
+            }
+        }
+
+        self.system.event_stream.publish(EndpointTerminated {
+            address: self.address.clone(),
+            attempts: self.reconnection_strategy.max_attempts(),
+            dead_letters,
+        });
+    }
+
+    fn transition(&self, from: EndpointState, to: EndpointState) -> bool {
+        let mut inner = self.inner.lock();
+        if inner.state != from || !from.can_transition_to(to) {
+            return false;
+        }
+        inner.state = to;
+        true
+    }
+
+    fn dead_letter(&self, target: Pid, message: Message) {
+        self.system.event_stream.publish(DeadLetterEvent {
+            pid: Some(target),
+            message,
+        });
     }
 }
 
 pub struct EndpointManager {
     endpoints: DashMap<String, Arc<RemoteEndpoint>>,
+    watches: Arc<RemoteWatchRegistry>,
+    batch_config: Option<BatchConfig>,
 }
 
 impl EndpointManager {
     pub fn new() -> Self {
         Self {
             endpoints: DashMap::new(),
+            watches: Arc::new(RemoteWatchRegistry::new()),
+            batch_config: Some(BatchConfig::default()),
         }
     }
 
+    /// 新建端点使用的批量发送配置
+    pub fn with_batching(mut self, config: BatchConfig) -> Self {
+        self.batch_config = Some(config);
+        self
+    }
+
+    /// 逐条发送，不合并消息
+    pub fn without_batching(mut self) -> Self {
+        self.batch_config = None;
+        self
+    }
+
+    pub fn with_watches(mut self, watches: Arc<RemoteWatchRegistry>) -> Self {
+        self.watches = watches;
+        self
+    }
+
+    /// 返回 `address` 的端点，不存在时创建并启动
+    ///
+    /// 并发调用只有一个会插入新端点，连接、心跳和批量发送任务只在插入时启动一次。
     pub fn get_or_create(
         &self,
         address: String,
         system: Arc<ActorSystem>,
         transport: Arc<RwLock<Box<dyn Transport>>>,
     ) -> Arc<RemoteEndpoint> {
-        if let Some(endpoint) = self.endpoints.get(&address) {
-            endpoint.clone()
-        } else {
-            let endpoint = Arc::new(RemoteEndpoint::new(address.clone(), system, transport));
-            self.endpoints.insert(address, Arc::clone(&endpoint));
-            endpoint
+        let endpoint = match self.endpoints.entry(address.clone()) {
+            Entry::Occupied(entry) => return Arc::clone(entry.get()),
+            Entry::Vacant(entry) => {
+                let mut endpoint = RemoteEndpoint::new(address, system, transport)
+                    .with_watches(self.watches.clone());
+                if let Some(config) = &self.batch_config {
+                    endpoint = endpoint.with_batching(config.clone());
+                }
+                let inserted = entry.insert(Arc::new(endpoint));
+                Arc::clone(&inserted)
+            }
+        };
+
+        let starting = Arc::clone(&endpoint);
+        tokio::spawn(async move {
+            starting.start().await;
+        });
+        endpoint.heartbeat_manager().start(Arc::clone(&endpoint));
+        if let Some(batcher) = &endpoint.batcher {
+            batcher.start(Arc::clone(&endpoint));
+        }
+        endpoint
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Instant;
+    use async_trait::async_trait;
+    use super::*;
+    use super::super::{Connection, HeartbeatManager, RemoteConfig, RemoteError, SendCredits};
+    use crate::{Actor, Context, Props, SystemConfig};
+
+    /// 记录发出的消息，可按需拒绝连接或让若干次发送失败
+    #[derive(Clone, Default)]
+    struct FakeTransport {
+        sent: Arc<Mutex<Vec<String>>>,
+        refuse: Arc<AtomicBool>,
+        failures: Arc<AtomicUsize>,
+    }
+
+    impl FakeTransport {
+        fn sent(&self) -> Vec<String> {
+            self.sent.lock().clone()
+        }
+
+        fn record(&self, message: &Message) -> Result<(), SendError> {
+            let failed = self.failures.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1)).is_ok();
+            if failed {
+                return Err(SendError::ConnectionFailed);
+            }
+            let text = message.payload.downcast_ref::<String>().cloned().unwrap_or_default();
+            self.sent.lock().push(text);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Transport for FakeTransport {
+        async fn start(&self, _config: &RemoteConfig) -> Result<(), RemoteError> {
+            Ok(())
+        }
+
+        async fn connect(&self, _address: &str) -> Result<Box<dyn Connection>, SendError> {
+            if self.refuse.load(Ordering::Acquire) {
+                return Err(SendError::ConnectionFailed);
+            }
+            Ok(Box::new(FakeConnection(self.clone())))
+        }
+    }
+
+    struct FakeConnection(FakeTransport);
+
+    #[async_trait]
+    impl Connection for FakeConnection {
+        async fn send(&self, _target: &Pid, message: Message) -> Result<(), SendError> {
+            self.0.record(&message)
+        }
+
+        async fn send_batch(&self, messages: Vec<(Pid, Message)>) -> Result<(), SendError> {
+            for (_, message) in &messages {
+                self.0.record(message)?;
+            }
+            Ok(())
+        }
+
+        async fn ping(&self) -> Result<(), RemoteError> {
+            Ok(())
+        }
+
+        fn credits(&self) -> Option<Arc<SendCredits>> {
+            None
         }
+
+        fn attach_heartbeat(&self, _address: &str, _manager: Arc<HeartbeatManager>) {}
+    }
+
+    fn endpoint(system: &Arc<ActorSystem>, transport: &FakeTransport) -> RemoteEndpoint {
+        let transport: Box<dyn Transport> = Box::new(transport.clone());
+        RemoteEndpoint::new("node-b".to_string(), Arc::clone(system), Arc::new(RwLock::new(transport)))
+            .with_reconnection_strategy(ReconnectionStrategy::new(1, Duration::from_millis(1), Duration::from_millis(1)))
+    }
+
+    fn target(id: &str) -> Pid {
+        Pid {
+            address: "node-b".to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    fn text(value: &str) -> Message {
+        Message::new(value.to_string())
+    }
+
+    #[test]
+    fn test_connecting_queue_is_flushed_once_connected() {
+        let system = Arc::new(ActorSystem::new(SystemConfig::default()));
+        let transport = FakeTransport::default();
+        let endpoint = endpoint(&system, &transport);
+
+        system.runtime().block_on(async {
+            endpoint.send(&target("sink"), text("a")).await.unwrap();
+            endpoint.send(&target("sink"), text("b")).await.unwrap();
+            assert_eq!(endpoint.state(), EndpointState::Connecting);
+            assert_eq!(endpoint.queued(), 2);
+            assert!(transport.sent().is_empty());
+
+            endpoint.start().await;
+        });
+
+        assert_eq!(endpoint.state(), EndpointState::Connected);
+        assert_eq!(endpoint.queued(), 0);
+        assert_eq!(transport.sent(), vec!["a", "b"]);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_reconnecting_endpoint_queues_and_retries_failed_send() {
+        let system = Arc::new(ActorSystem::new(SystemConfig::default()));
+        let transport = FakeTransport::default();
+        let endpoint = endpoint(&system, &transport);
+
+        system.runtime().block_on(async {
+            endpoint.start().await;
+
+            // 重连期间的消息只进入队列
+            assert!(endpoint.transition(EndpointState::Connected, EndpointState::Reconnecting));
+            endpoint.send(&target("sink"), text("queued")).await.unwrap();
+            assert_eq!(endpoint.queued(), 1);
+            assert!(transport.sent().is_empty());
+            endpoint.establish().await;
+            assert_eq!(transport.sent(), vec!["queued"]);
+
+            // 发送失败的消息在重连后重新发送
+            transport.failures.store(1, Ordering::Release);
+            endpoint.send(&target("sink"), text("retried")).await.unwrap();
+        });
+
+        assert_eq!(endpoint.state(), EndpointState::Connected);
+        assert_eq!(transport.sent(), vec!["queued", "retried"]);
+    }
+
+    #[test]
+    fn test_queue_overflow_is_dead_lettered() {
+        let system = Arc::new(ActorSystem::new(SystemConfig::default()));
+        let transport = FakeTransport::default();
+        let endpoint = endpoint(&system, &transport).with_send_queue_capacity(1);
+        let mut events = system.event_stream().subscribe::<DeadLetterEvent>();
+
+        system.runtime().block_on(async {
+            endpoint.send(&target("sink"), text("kept")).await.unwrap();
+            let overflow = endpoint.send(&target("sink"), text("dropped")).await;
+            assert!(matches!(overflow, Err(SendError::MailboxFull)));
+        });
+
+        let event = events.try_recv().unwrap();
+        let dead_letter = event.downcast_ref::<DeadLetterEvent>().unwrap();
+        assert_eq!(dead_letter.pid, Some(target("sink")));
+        assert_eq!(dead_letter.message.payload.downcast_ref::<String>().map(String::as_str), Some("dropped"));
+        assert_eq!(endpoint.queued(), 1);
+    }
+
+    struct Watcher {
+        terminated: std::sync::mpsc::Sender<Pid>,
+    }
+
+    #[async_trait]
+    impl Actor for Watcher {
+        async fn receive(&mut self, _ctx: &Context, msg: Message) -> Result<(), SendError> {
+            if let Some(SystemMessage::Terminated { who, .. }) = msg.payload.downcast_ref::<SystemMessage>() {
+                let _ = self.terminated.send(who.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_termination_notifies_watchers_and_publishes_event() {
+        let system = Arc::new(ActorSystem::new(SystemConfig::default()));
+        let transport = FakeTransport::default();
+        transport.refuse.store(true, Ordering::Release);
+        let watches = Arc::new(RemoteWatchRegistry::new());
+        let endpoint = endpoint(&system, &transport).with_watches(Arc::clone(&watches));
+
+        let (terminated, received) = std::sync::mpsc::channel();
+        let watcher = system.spawn(&Props::new(move || Watcher { terminated: terminated.clone() })).unwrap();
+        let watcher_pid = Pid {
+            address: "node-a".to_string(),
+            id: watcher.id().to_string(),
+        };
+        watches.watch(watcher_pid, target("watched"));
+        let mut events = system.event_stream().subscribe::<EndpointTerminated>();
+
+        system.runtime().block_on(async {
+            endpoint.send(&target("sink"), text("pending")).await.unwrap();
+            endpoint.start().await;
+            assert!(matches!(endpoint.send(&target("sink"), text("late")).await, Err(SendError::DeadLetter)));
+        });
+        assert_eq!(endpoint.state(), EndpointState::Terminated);
+
+        // 事件流上依次是排队消息的死信和端点终止事件
+        let started = Instant::now();
+        let event = loop {
+            match events.try_recv() {
+                Ok(event) if event.is::<EndpointTerminated>() => break event,
+                Ok(_) => continue,
+                Err(_) => {
+                    assert!(started.elapsed() < Duration::from_secs(5), "EndpointTerminated was not published");
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        };
+        let terminated_event = event.downcast_ref::<EndpointTerminated>().unwrap();
+        assert_eq!(terminated_event.address, "node-b");
+        assert_eq!(terminated_event.dead_letters, 1);
+
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)).unwrap(), target("watched"));
+    }
+}
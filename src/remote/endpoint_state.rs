@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+
+/// 远程端点的连接状态
+///
+/// ```text
+/// Connecting ──> Connected <──> Reconnecting
+///      │                             │
+///      └──────────> Terminated <─────┘
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointState {
+    /// 首次建立连接
+    Connecting,
+    Connected,
+    /// 连接断开，按重连策略重试
+    Reconnecting,
+    /// 重连次数耗尽，端点不再可用
+    Terminated,
+}
+
+impl EndpointState {
+    /// 是否允许从当前状态迁移到 `next`
+    pub fn can_transition_to(self, next: EndpointState) -> bool {
+        use EndpointState::*;
+        matches!(
+            (self, next),
+            (Connecting, Connected)
+                | (Connecting, Terminated)
+                | (Connected, Reconnecting)
+                | (Reconnecting, Connected)
+                | (Reconnecting, Terminated)
+        )
+    }
+
+    /// 未连接期间消息需要进入发送队列
+    pub fn buffers(self) -> bool {
+        matches!(self, EndpointState::Connecting | EndpointState::Reconnecting)
+    }
+}
+
+/// 端点终止时发布到事件流的事件
+#[derive(Debug, Clone)]
+pub struct EndpointTerminated {
+    pub address: String,
+    /// 重连尝试次数
+    pub attempts: u32,
+    /// 转入死信的排队消息数
+    pub dead_letters: usize,
+}
+
+/// 未连接期间的有界发送队列，按入队顺序发送
+pub(crate) struct SendQueue<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> SendQueue<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            items: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// 入队，队列已满时原样返回
+    pub(crate) fn push(&mut self, item: T) -> Result<(), T> {
+        if self.items.len() >= self.capacity {
+            return Err(item);
+        }
+        self.items.push_back(item);
+        Ok(())
+    }
+
+    /// 取出最多 `max` 条最早入队的消息
+    pub(crate) fn take(&mut self, max: usize) -> Vec<T> {
+        let n = max.min(self.items.len());
+        self.items.drain(..n).collect()
+    }
+
+    /// 将发送失败的消息放回队首，保持原有顺序
+    ///
+    /// 超出容量时从队尾移出最新的消息并按入队顺序返回，较早的消息优先保留。
+    pub(crate) fn requeue_front(&mut self, items: Vec<T>) -> Vec<T> {
+        for item in items.into_iter().rev() {
+            self.items.push_front(item);
+        }
+        let excess = self.items.len().saturating_sub(self.capacity);
+        self.items.split_off(self.items.len() - excess).into()
+    }
+
+    pub(crate) fn drain(&mut self) -> Vec<T> {
+        self.items.drain(..).collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_transitions() {
+        use EndpointState::*;
+        assert!(Connecting.can_transition_to(Connected));
+        assert!(Connected.can_transition_to(Reconnecting));
+        assert!(Reconnecting.can_transition_to(Connected));
+        assert!(Reconnecting.can_transition_to(Terminated));
+        assert!(!Terminated.can_transition_to(Connected));
+        assert!(!Connected.can_transition_to(Connecting));
+    }
+
+    #[test]
+    fn test_send_queue_is_bounded_and_ordered() {
+        let mut queue = SendQueue::new(3);
+        for i in 0..3 {
+            assert!(queue.push(i).is_ok());
+        }
+        assert_eq!(queue.push(3), Err(3));
+
+        let first = queue.take(2);
+        assert_eq!(first, vec![0, 1]);
+        assert!(queue.requeue_front(first).is_empty());
+        assert_eq!(queue.drain(), vec![0, 1, 2]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_requeue_front_spills_newest_from_tail() {
+        let mut queue = SendQueue::new(3);
+        queue.push(3).unwrap();
+        queue.push(4).unwrap();
+
+        // The failed batch is older than what was queued meanwhile
+        let overflow = queue.requeue_front(vec![0, 1, 2]);
+        assert_eq!(overflow, vec![3, 4]);
+        assert_eq!(queue.drain(), vec![0, 1, 2]);
+    }
+}
@@ -9,6 +9,7 @@ pub enum RemoteError {
     ConnectionError(String),
     HandshakeError(String),
     CompressionError(CompressionError),
+    ProtocolError(ProtocolError),
     RateLimitExceeded,
 }
 
@@ -19,10 +20,27 @@ pub enum SerializationError {
     NoSerializerFound(String),
 }
 
+/// 帧格式或握手不符合协议
+#[derive(Debug)]
+pub enum ProtocolError {
+    FrameTooLarge(usize),
+    InvalidLength(usize),
+    UnknownFrameType(u8),
+    UnknownFlags(u8),
+    UnexpectedFrame(super::protocol::FrameType),
+    MalformedHandshake(String),
+    VersionMismatch { local: u16, remote: u16 },
+    NoCommonSerializer(Vec<String>),
+    /// 握手协商为不压缩，对端却发来了压缩帧
+    CompressionNotNegotiated,
+}
+
 #[derive(Debug)]
 pub enum CompressionError {
     CompressionFailed(String),
     DecompressionFailed(String),
+    /// 协商出的压缩算法在本地未注册
+    UnknownCompressor(String),
 }
 
 impl fmt::Display for RemoteError {
@@ -33,6 +51,7 @@ impl fmt::Display for RemoteError {
             RemoteError::ConnectionError(e) => write!(f, "Connection error: {}", e),
             RemoteError::HandshakeError(e) => write!(f, "Handshake error: {}", e),
             RemoteError::CompressionError(e) => write!(f, "Compression error: {:?}", e),
+            RemoteError::ProtocolError(e) => write!(f, "Protocol error: {}", e),
             RemoteError::RateLimitExceeded => write!(f, "Rate limit exceeded"),
         }
     }
@@ -40,6 +59,30 @@ impl fmt::Display for RemoteError {
 
 impl Error for RemoteError {}
 
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::FrameTooLarge(len) => write!(f, "frame of {} bytes exceeds the maximum size", len),
+            ProtocolError::InvalidLength(len) => write!(f, "invalid frame length {}", len),
+            ProtocolError::UnknownFrameType(t) => write!(f, "unknown frame type {}", t),
+            ProtocolError::UnknownFlags(flags) => write!(f, "unknown frame flags {:#04x}", flags),
+            ProtocolError::UnexpectedFrame(t) => write!(f, "unexpected {:?} frame", t),
+            ProtocolError::MalformedHandshake(e) => write!(f, "malformed handshake: {}", e),
+            ProtocolError::VersionMismatch { local, remote } => {
+                write!(f, "protocol version mismatch: local {}, remote {}", local, remote)
+            }
+            ProtocolError::NoCommonSerializer(remote) => {
+                write!(f, "no common serializer, remote supports {:?}", remote)
+            }
+            ProtocolError::CompressionNotNegotiated => {
+                write!(f, "received a compressed frame but compression was not negotiated")
+            }
+        }
+    }
+}
+
+impl Error for ProtocolError {}
+
 impl From<io::Error> for RemoteError {
     fn from(error: io::Error) -> Self {
         RemoteError::IoError(error)
@@ -56,4 +99,10 @@ impl From<CompressionError> for RemoteError {
     fn from(error: CompressionError) -> Self {
         RemoteError::CompressionError(error)
     }
-} 
\ No newline at end of file
+}
+
+impl From<ProtocolError> for RemoteError {
+    fn from(error: ProtocolError) -> Self {
+        RemoteError::ProtocolError(error)
+    }
+}
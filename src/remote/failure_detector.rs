@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// phi accrual 故障检测器配置
+#[derive(Debug, Clone)]
+pub struct PhiAccrualConfig {
+    /// phi 超过该值时判定不可达
+    pub threshold: f64,
+    /// 保留的心跳间隔样本数
+    pub max_sample_size: usize,
+    /// 标准差下限，避免间隔非常稳定时对抖动过于敏感
+    pub min_std_deviation: Duration,
+    /// 允许的额外停顿，例如 GC 或主机繁忙
+    pub acceptable_heartbeat_pause: Duration,
+    /// 尚无样本时对心跳间隔的估计
+    pub first_heartbeat_estimate: Duration,
+}
+
+impl Default for PhiAccrualConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 8.0,
+            max_sample_size: 200,
+            min_std_deviation: Duration::from_millis(100),
+            acceptable_heartbeat_pause: Duration::from_secs(3),
+            first_heartbeat_estimate: Duration::from_secs(1),
+        }
+    }
+}
+
+/// 心跳间隔的滑动窗口
+struct IntervalHistory {
+    intervals: VecDeque<f64>,
+    max_size: usize,
+    sum: f64,
+    squared_sum: f64,
+}
+
+impl IntervalHistory {
+    fn new(max_size: usize) -> Self {
+        Self {
+            intervals: VecDeque::with_capacity(max_size),
+            max_size: max_size.max(1),
+            sum: 0.0,
+            squared_sum: 0.0,
+        }
+    }
+
+    fn push(&mut self, interval: f64) {
+        if self.intervals.len() >= self.max_size {
+            if let Some(oldest) = self.intervals.pop_front() {
+                self.sum -= oldest;
+                self.squared_sum -= oldest * oldest;
+            }
+        }
+        self.intervals.push_back(interval);
+        self.sum += interval;
+        self.squared_sum += interval * interval;
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.intervals.len() as f64
+    }
+
+    fn std_deviation(&self) -> f64 {
+        let mean = self.mean();
+        let variance = self.squared_sum / self.intervals.len() as f64 - mean * mean;
+        variance.max(0.0).sqrt()
+    }
+}
+
+/// phi accrual 故障检测器
+///
+/// 根据历史心跳间隔的分布计算当前未收到心跳的可疑程度 phi，
+/// 而不是使用固定超时。间隔波动大的连接会自动得到更宽松的判定。
+pub struct PhiAccrualFailureDetector {
+    config: PhiAccrualConfig,
+    history: IntervalHistory,
+    last_heartbeat: Option<Instant>,
+}
+
+impl PhiAccrualFailureDetector {
+    pub fn new(config: PhiAccrualConfig) -> Self {
+        Self {
+            history: IntervalHistory::new(config.max_sample_size),
+            config,
+            last_heartbeat: None,
+        }
+    }
+
+    /// 记录一次心跳到达
+    pub fn heartbeat(&mut self, now: Instant) {
+        match self.last_heartbeat {
+            Some(last) => {
+                let interval = now.saturating_duration_since(last).as_secs_f64() * 1000.0;
+                self.history.push(interval);
+            }
+            None => {
+                // 用估计值填充两个样本，使第一个间隔也能计算 phi
+                let estimate = self.config.first_heartbeat_estimate.as_secs_f64() * 1000.0;
+                let std_deviation = estimate / 4.0;
+                self.history.push(estimate - std_deviation);
+                self.history.push(estimate + std_deviation);
+            }
+        }
+        self.last_heartbeat = Some(now);
+    }
+
+    /// 当前的 phi 值，尚未收到心跳时为 0
+    pub fn phi(&self, now: Instant) -> f64 {
+        let last = match self.last_heartbeat {
+            Some(last) => last,
+            None => return 0.0,
+        };
+
+        let elapsed = now.saturating_duration_since(last).as_secs_f64() * 1000.0;
+        let mean = self.history.mean() + self.config.acceptable_heartbeat_pause.as_secs_f64() * 1000.0;
+        let min_std_deviation = self.config.min_std_deviation.as_secs_f64() * 1000.0;
+        let std_deviation = self.history.std_deviation().max(min_std_deviation);
+        phi(elapsed, mean, std_deviation)
+    }
+
+    pub fn is_available(&self, now: Instant) -> bool {
+        self.phi(now) < self.config.threshold
+    }
+
+    /// 重连后清空历史
+    pub fn reset(&mut self) {
+        self.history = IntervalHistory::new(self.config.max_sample_size);
+        self.last_heartbeat = None;
+    }
+}
+
+/// 正态分布累积函数的 logistic 近似
+fn phi(elapsed: f64, mean: f64, std_deviation: f64) -> f64 {
+    let y = (elapsed - mean) / std_deviation;
+    let e = (-y * (1.5976 + 0.070566 * y * y)).exp();
+    if elapsed > mean {
+        -(e / (1.0 + e)).log10()
+    } else {
+        -(1.0 - 1.0 / (1.0 + e)).log10()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> PhiAccrualFailureDetector {
+        PhiAccrualFailureDetector::new(PhiAccrualConfig {
+            acceptable_heartbeat_pause: Duration::ZERO,
+            ..PhiAccrualConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_phi_grows_with_silence() {
+        let mut detector = detector();
+        let start = Instant::now();
+        for i in 0..10 {
+            detector.heartbeat(start + Duration::from_secs(i));
+        }
+        let last = start + Duration::from_secs(9);
+
+        let soon = detector.phi(last + Duration::from_millis(500));
+        let late = detector.phi(last + Duration::from_secs(3));
+        assert!(soon < late);
+        assert!(detector.is_available(last + Duration::from_millis(500)));
+        assert!(!detector.is_available(last + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_jittery_history_tolerates_longer_gaps() {
+        let start = Instant::now();
+        let mut steady = detector();
+        let mut jittery = detector();
+        let mut at = start;
+        for i in 0..20 {
+            steady.heartbeat(start + Duration::from_secs(i));
+            at += Duration::from_millis(if i % 2 == 0 { 200 } else { 1800 });
+            jittery.heartbeat(at);
+        }
+
+        let gap = Duration::from_millis(2500);
+        assert!(jittery.phi(at + gap) < steady.phi(start + Duration::from_secs(19) + gap));
+    }
+}
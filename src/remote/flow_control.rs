@@ -1,68 +1,258 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::{Duration, Instant};
-use tokio::time::sleep;
-
-pub struct RateLimiter {
-    rate: AtomicUsize,
-    interval: Duration,
-    last_reset: parking_lot::Mutex<Instant>,
-    current_count: AtomicUsize,
+use std::time::Instant;
+use metrics::{register_counter, register_gauge, register_histogram, Counter, Gauge, Histogram};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use super::{RemoteError, SerializationError};
+
+/// 基于信用的流量控制配置
+///
+/// 连接两端使用相同的初始窗口：发送方一开始就持有整个窗口的信用，
+/// 之后只能按接收方授予的信用继续发送。
+#[derive(Debug, Clone)]
+pub struct FlowControlConfig {
+    /// 每个连接的消息信用窗口
+    pub message_window: u32,
+    /// 每个连接的字节信用窗口
+    pub byte_window: u64,
+    /// 已消费的信用达到窗口的该比例时才回授，避免每帧都发送信用帧
+    pub grant_ratio: f64,
+    /// 邮箱负载低于该值时全额回授
+    pub low_pressure: f64,
+    /// 邮箱负载高于该值时暂停回授
+    pub high_pressure: f64,
 }
 
-impl RateLimiter {
-    pub fn new(rate: usize, interval: Duration) -> Self {
+impl Default for FlowControlConfig {
+    fn default() -> Self {
         Self {
-            rate: AtomicUsize::new(rate),
-            interval,
-            last_reset: parking_lot::Mutex::new(Instant::now()),
-            current_count: AtomicUsize::new(0),
+            message_window: 1024,
+            byte_window: 4 * 1024 * 1024,
+            grant_ratio: 0.25,
+            low_pressure: 0.5,
+            high_pressure: 0.9,
         }
     }
+}
+
+/// 接收方授予的信用，随 `FrameType::Credit` 帧发送
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreditGrant {
+    pub messages: u32,
+    pub bytes: u64,
+}
+
+impl CreditGrant {
+    pub fn encode(&self) -> Result<Vec<u8>, RemoteError> {
+        bincode::serialize(self)
+            .map_err(|e| SerializationError::EncodingError(e.to_string()).into())
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, RemoteError> {
+        bincode::deserialize(data)
+            .map_err(|e| SerializationError::DecodingError(e.to_string()).into())
+    }
+}
+
+/// 发送方持有的信用
+///
+/// 字节数在帧写出后才知道，允许透支一帧，余额为负时后续发送等待回授。
+struct CreditWindow {
+    messages: i64,
+    bytes: i64,
+    message_window: i64,
+}
 
-    pub async fn acquire(&self) -> bool {
-        let now = Instant::now();
-        let mut last_reset = self.last_reset.lock();
-        
-        if now.duration_since(*last_reset) >= self.interval {
-            *last_reset = now;
-            self.current_count.store(0, Ordering::SeqCst);
+impl CreditWindow {
+    fn new(config: &FlowControlConfig) -> Self {
+        Self {
+            messages: config.message_window as i64,
+            bytes: config.byte_window as i64,
+            message_window: config.message_window as i64,
         }
+    }
 
-        let current = self.current_count.fetch_add(1, Ordering::SeqCst);
-        if current >= self.rate.load(Ordering::SeqCst) {
-            sleep(self.interval).await;
-            false
-        } else {
-            true
+    /// 信用足够时扣除消息信用
+    ///
+    /// 超过整个窗口的批次在窗口全部空闲时放行。
+    fn try_acquire(&mut self, messages: u32) -> bool {
+        let needed = (messages as i64).min(self.message_window);
+        if self.messages < needed || self.bytes <= 0 {
+            return false;
         }
+        self.messages -= messages as i64;
+        true
     }
 
-    pub fn update_rate(&self, new_rate: usize) {
-        self.rate.store(new_rate, Ordering::SeqCst);
+    fn consume_bytes(&mut self, bytes: usize) {
+        self.bytes -= bytes as i64;
+    }
+
+    fn grant(&mut self, grant: CreditGrant) {
+        self.messages += grant.messages as i64;
+        self.bytes += grant.bytes as i64;
     }
 }
 
-pub struct FlowController {
-    message_rate_limiter: RateLimiter,
-    byte_rate_limiter: RateLimiter,
+/// 发送方的信用，信用耗尽时发送暂停直到接收方回授
+pub struct SendCredits {
+    window: Mutex<CreditWindow>,
+    notify: Notify,
+    stalled: Gauge,
+    stalls: Counter,
+    stall_time: Histogram,
 }
 
-impl FlowController {
-    pub fn new(messages_per_sec: usize, bytes_per_sec: usize) -> Self {
+impl SendCredits {
+    pub fn new(config: &FlowControlConfig, endpoint: &str) -> Self {
+        let endpoint = endpoint.to_string();
         Self {
-            message_rate_limiter: RateLimiter::new(
-                messages_per_sec,
-                Duration::from_secs(1),
-            ),
-            byte_rate_limiter: RateLimiter::new(
-                bytes_per_sec,
-                Duration::from_secs(1),
-            ),
+            window: Mutex::new(CreditWindow::new(config)),
+            notify: Notify::new(),
+            stalled: register_gauge!("remote_flow_stalled_senders", "endpoint" => endpoint.clone()),
+            stalls: register_counter!("remote_flow_stalls", "endpoint" => endpoint.clone()),
+            stall_time: register_histogram!("remote_flow_stall_seconds", "endpoint" => endpoint),
+        }
+    }
+
+    /// 等待并扣除 `messages` 条消息的信用
+    pub async fn acquire(&self, messages: u32) {
+        if self.window.lock().try_acquire(messages) {
+            return;
+        }
+
+        let started = Instant::now();
+        self.stalled.increment(1.0);
+        self.stalls.increment(1);
+        loop {
+            let granted = self.notify.notified();
+            if self.window.lock().try_acquire(messages) {
+                break;
+            }
+            granted.await;
         }
+        self.stalled.decrement(1.0);
+        self.stall_time.record(started.elapsed().as_secs_f64());
     }
 
-    pub async fn can_send(&self, message_size: usize) -> bool {
-        self.message_rate_limiter.acquire().await && 
-        self.byte_rate_limiter.acquire().await
+    /// 帧写出后扣除字节信用
+    pub fn consume_bytes(&self, bytes: usize) {
+        self.window.lock().consume_bytes(bytes);
     }
-} 
\ No newline at end of file
+
+    /// 收到接收方的信用帧
+    pub fn grant(&self, grant: CreditGrant) {
+        self.window.lock().grant(grant);
+        self.notify.notify_waiters();
+    }
+}
+
+/// 接收方按邮箱压力回授信用
+///
+/// 负载在 `low_pressure` 以下全额回授，在两个阈值之间按比例回授，
+/// 超过 `high_pressure` 时暂停回授。未回授的信用会在压力下降后补发，
+/// 因此压力越大，发送方可用的窗口越小。
+pub struct CreditGranter {
+    config: FlowControlConfig,
+    owed_messages: u64,
+    owed_bytes: u64,
+}
+
+impl CreditGranter {
+    pub fn new(config: FlowControlConfig) -> Self {
+        Self {
+            config,
+            owed_messages: 0,
+            owed_bytes: 0,
+        }
+    }
+
+    /// 记录收到的消息
+    pub fn record(&mut self, messages: u32, bytes: usize) {
+        self.owed_messages += messages as u64;
+        self.owed_bytes += bytes as u64;
+    }
+
+    /// 根据当前邮箱负载决定是否回授信用
+    ///
+    /// 心跳时也会调用，保证发送方停顿后压力下降能及时恢复。
+    pub fn poll(&mut self, load: f64) -> Option<CreditGrant> {
+        let threshold = (self.config.message_window as f64 * self.config.grant_ratio) as u64;
+        let byte_threshold = (self.config.byte_window as f64 * self.config.grant_ratio) as u64;
+        if self.owed_messages < threshold.max(1) && self.owed_bytes < byte_threshold.max(1) {
+            return None;
+        }
+
+        let factor = self.grant_factor(load);
+        let messages = (self.owed_messages as f64 * factor) as u64;
+        let bytes = (self.owed_bytes as f64 * factor) as u64;
+        if messages == 0 && bytes == 0 {
+            return None;
+        }
+
+        let messages = messages.min(u32::MAX as u64);
+        self.owed_messages -= messages;
+        self.owed_bytes -= bytes;
+        Some(CreditGrant {
+            messages: messages as u32,
+            bytes,
+        })
+    }
+
+    fn grant_factor(&self, load: f64) -> f64 {
+        let (low, high) = (self.config.low_pressure, self.config.high_pressure);
+        if load <= low {
+            1.0
+        } else if load >= high {
+            0.0
+        } else {
+            (high - load) / (high - low)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FlowControlConfig {
+        FlowControlConfig {
+            message_window: 4,
+            byte_window: 1000,
+            grant_ratio: 0.5,
+            low_pressure: 0.5,
+            high_pressure: 0.9,
+        }
+    }
+
+    #[test]
+    fn test_sender_pauses_until_granted() {
+        let mut window = CreditWindow::new(&config());
+        assert!(window.try_acquire(3));
+        window.consume_bytes(600);
+        assert!(!window.try_acquire(2));
+        assert!(window.try_acquire(1));
+
+        // 字节透支后即使有消息信用也要等待
+        window.grant(CreditGrant { messages: 4, bytes: 0 });
+        window.consume_bytes(500);
+        assert!(!window.try_acquire(1));
+        window.grant(CreditGrant { messages: 0, bytes: 200 });
+        assert!(window.try_acquire(1));
+    }
+
+    #[test]
+    fn test_grants_shrink_under_pressure() {
+        let mut granter = CreditGranter::new(config());
+        granter.record(1, 100);
+        assert_eq!(granter.poll(0.0), None);
+
+        granter.record(1, 100);
+        assert_eq!(granter.poll(0.0), Some(CreditGrant { messages: 2, bytes: 200 }));
+
+        granter.record(4, 400);
+        assert_eq!(granter.poll(0.95), None);
+        assert_eq!(granter.poll(0.7), Some(CreditGrant { messages: 2, bytes: 200 }));
+        assert_eq!(granter.poll(0.0), Some(CreditGrant { messages: 2, bytes: 200 }));
+    }
+}
@@ -1,71 +1,188 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+use metrics::{register_gauge, register_histogram, Gauge, Histogram};
+use serde::{Deserialize, Serialize};
 use tokio::time::interval;
-use crate::{Message, Pid, SendError};
+use super::RemoteEndpoint;
+use super::{EndpointState, PhiAccrualConfig, PhiAccrualFailureDetector, RemoteError, SerializationError};
 
-pub struct HeartbeatMessage {
-    pub timestamp: u64,
+/// 心跳帧的负载
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeartbeatMessage {
+    Ping { sequence: u64 },
+    Pong { sequence: u64 },
 }
 
+impl HeartbeatMessage {
+    pub fn encode(&self) -> Result<Vec<u8>, RemoteError> {
+        bincode::serialize(self)
+            .map_err(|e| SerializationError::EncodingError(e.to_string()).into())
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, RemoteError> {
+        bincode::deserialize(data)
+            .map_err(|e| SerializationError::DecodingError(e.to_string()).into())
+    }
+}
+
+/// 单个端点的心跳状态
+struct EndpointHeartbeat {
+    sequence: u64,
+    // 尚未收到 Pong 的 Ping 及其发送时间
+    pending: HashMap<u64, Instant>,
+    detector: PhiAccrualFailureDetector,
+    last_rtt: Option<Duration>,
+    rtt: Histogram,
+    phi: Gauge,
+}
+
+impl EndpointHeartbeat {
+    fn new(address: &str, config: PhiAccrualConfig) -> Self {
+        let endpoint = address.to_string();
+        Self {
+            sequence: 0,
+            pending: HashMap::new(),
+            detector: PhiAccrualFailureDetector::new(config),
+            last_rtt: None,
+            rtt: register_histogram!("remote_heartbeat_rtt_seconds", "endpoint" => endpoint.clone()),
+            phi: register_gauge!("remote_heartbeat_phi", "endpoint" => endpoint),
+        }
+    }
+}
+
+/// 基于 Ping/Pong 的心跳管理
+///
+/// 每个周期发起一轮 Ping，同一轮中最先到达的 Pong 计入 RTT 和故障检测器，
+/// 端点是否可达由 phi accrual 检测器判定。
 pub struct HeartbeatManager {
     interval: Duration,
-    timeout: Duration,
-    last_heartbeat: dashmap::DashMap<String, std::time::Instant>,
+    detector_config: PhiAccrualConfig,
+    endpoints: Arc<DashMap<String, EndpointHeartbeat>>,
 }
 
 impl HeartbeatManager {
-    pub fn new(interval: Duration, timeout: Duration) -> Self {
+    pub fn new(interval: Duration, detector_config: PhiAccrualConfig) -> Self {
         Self {
             interval,
-            timeout,
-            last_heartbeat: dashmap::DashMap::new(),
+            detector_config,
+            endpoints: Arc::new(DashMap::new()),
         }
     }
 
-    pub async fn start(&self, endpoint: Arc<RemoteEndpoint>) {
-        let mut ticker = interval(self.interval);
-        let address = endpoint.address.clone();
+    pub fn start(self: &Arc<Self>, endpoint: Arc<RemoteEndpoint>) {
+        let manager = Arc::clone(self);
+        let address = endpoint.address().to_string();
 
         tokio::spawn(async move {
+            let mut ticker = interval(manager.interval);
             loop {
                 ticker.tick().await;
-                
-                // 发送心跳
-                let heartbeat = HeartbeatMessage {
-                    timestamp: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs(),
-                };
-
-                if let Err(e) = endpoint.send_heartbeat(heartbeat).await {
-                    log::error!("Failed to send heartbeat to {}: {:?}", address, e);
-                    break;
+
+                match endpoint.state() {
+                    EndpointState::Terminated => break,
+                    EndpointState::Connected => {}
+                    _ => continue,
                 }
-            }
-        });
 
-        // 检查心跳超时
-        let timeout = self.timeout;
-        let last_heartbeat = self.last_heartbeat.clone();
-        
-        tokio::spawn(async move {
-            let mut check_interval = interval(timeout);
-            loop {
-                check_interval.tick().await;
-                
-                if let Some(last) = last_heartbeat.get(&address) {
-                    if last.elapsed() > timeout {
-                        log::warn!("Heartbeat timeout for {}", address);
-                        // 处理连接断开
-                        endpoint.handle_connection_lost().await;
-                        break;
-                    }
+                // 检查连接池中的连接，同时向每个连接发送本轮 Ping
+                manager.begin_round(&address);
+                endpoint.check_connections().await;
+
+                if !manager.is_available(&address) {
+                    log::warn!("Endpoint {} unreachable, phi = {:.2}", address, manager.phi(&address));
+                    endpoint.handle_connection_lost().await;
+                    manager.reset(&address);
                 }
             }
         });
     }
 
-    pub fn record_heartbeat(&self, address: &str) {
-        self.last_heartbeat.insert(address.to_string(), std::time::Instant::now());
+    /// 开始新一轮心跳，返回本轮序号
+    pub fn begin_round(&self, address: &str) -> u64 {
+        let mut state = self.state(address);
+        state.sequence += 1;
+        let sequence = state.sequence;
+        state.pending.insert(sequence, Instant::now());
+        // 超过一个检测窗口仍未回复的 Ping 不再等待
+        let horizon = self.interval * 10;
+        state.pending.retain(|_, sent| sent.elapsed() < horizon);
+        sequence
+    }
+
+    /// 连接发送 Ping 时使用的当前序号
+    pub fn ping(&self, address: &str) -> HeartbeatMessage {
+        let sequence = self.endpoints.get(address).map_or(0, |state| state.sequence);
+        HeartbeatMessage::Ping { sequence }
+    }
+
+    /// 收到 Pong，返回测得的 RTT；同一轮的重复 Pong 返回 None
+    pub fn record_pong(&self, address: &str, sequence: u64) -> Option<Duration> {
+        let mut state = self.endpoints.get_mut(address)?;
+        let sent = state.pending.remove(&sequence)?;
+        let now = Instant::now();
+        let rtt = now.saturating_duration_since(sent);
+
+        state.detector.heartbeat(now);
+        state.last_rtt = Some(rtt);
+        state.rtt.record(rtt.as_secs_f64());
+        let phi = state.detector.phi(now);
+        state.phi.set(phi);
+        Some(rtt)
+    }
+
+    /// 最近一次测得的 RTT
+    pub fn rtt(&self, address: &str) -> Option<Duration> {
+        self.endpoints.get(address).and_then(|state| state.last_rtt)
     }
-} 
\ No newline at end of file
+
+    pub fn phi(&self, address: &str) -> f64 {
+        match self.endpoints.get(address) {
+            Some(state) => {
+                let phi = state.detector.phi(Instant::now());
+                state.phi.set(phi);
+                phi
+            }
+            None => 0.0,
+        }
+    }
+
+    pub fn is_available(&self, address: &str) -> bool {
+        self.endpoints
+            .get(address)
+            .map_or(true, |state| state.detector.is_available(Instant::now()))
+    }
+
+    /// 重连后重新积累心跳历史
+    pub fn reset(&self, address: &str) {
+        if let Some(mut state) = self.endpoints.get_mut(address) {
+            state.pending.clear();
+            state.detector.reset();
+            state.last_rtt = None;
+        }
+    }
+
+    fn state(&self, address: &str) -> dashmap::mapref::one::RefMut<'_, String, EndpointHeartbeat> {
+        self.endpoints
+            .entry(address.to_string())
+            .or_insert_with(|| EndpointHeartbeat::new(address, self.detector_config.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_first_pong_per_round_counts() {
+        let manager = HeartbeatManager::new(Duration::from_secs(1), PhiAccrualConfig::default());
+        let sequence = manager.begin_round("node-b");
+        assert_eq!(manager.ping("node-b"), HeartbeatMessage::Ping { sequence });
+
+        assert!(manager.record_pong("node-b", sequence).is_some());
+        assert!(manager.record_pong("node-b", sequence).is_none());
+        assert!(manager.rtt("node-b").is_some());
+        assert!(manager.is_available("node-b"));
+    }
+}
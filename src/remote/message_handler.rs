@@ -1,46 +1,56 @@
 use std::sync::Arc;
-use crate::{ActorSystem, Message, MessageEnvelope, RemoteError};
+use crate::{ActorSystem, Message, Pid};
+use super::{RemoteError, RemoteWatchRegistry, SystemMessage};
 
+/// 处理从远程节点收到的消息
 pub struct RemoteMessageHandler {
     system: Arc<ActorSystem>,
+    watches: Arc<RemoteWatchRegistry>,
 }
 
 impl RemoteMessageHandler {
-    pub fn new(system: Arc<ActorSystem>) -> Self {
-        Self { system }
+    pub fn new(system: Arc<ActorSystem>, watches: Arc<RemoteWatchRegistry>) -> Self {
+        Self { system, watches }
     }
 
-    pub async fn handle_envelope(&self, envelope: MessageEnvelope) -> Result<(), RemoteError> {
-        let message = Message {
-            payload: envelope.message_data,
-            sender: envelope.sender,
-            header: envelope.header,
+    /// 系统消息更新监视关系，其余消息投递给本地目标
+    pub async fn handle_inbound(&self, target: Pid, message: Message) -> Result<(), RemoteError> {
+        let message = match message.payload.downcast::<SystemMessage>() {
+            Ok(system_message) => return self.handle_system_message(*system_message).await,
+            Err(payload) => Message { payload, ..message },
         };
 
-        self.system.send(&envelope.target, message).await
+        self.system.send(&target, message).await
             .map_err(|e| RemoteError::ConnectionError(format!("Failed to deliver message: {:?}", e)))
     }
 
     pub async fn handle_system_message(&self, message: SystemMessage) -> Result<(), RemoteError> {
         match message {
             SystemMessage::Watch(watcher, target) => {
-                // 处理监视请求
-                if let Some(process) = self.system.process_registry.get(&target.id) {
-                    process.watch(watcher).await;
-                }
+                // 远程 Actor 监视本地 Actor，本地 Actor 终止时回复 Terminated
+                self.watches.add_remote_watcher(target, watcher);
             }
             SystemMessage::Unwatch(watcher, target) => {
-                // 处理取消监视请求
-                if let Some(process) = self.system.process_registry.get(&target.id) {
-                    process.unwatch(&watcher).await;
-                }
+                self.watches.remove_remote_watcher(&target, &watcher);
             }
-            SystemMessage::Terminated(pid) => {
-                // 处理终止通知
-                self.system.process_registry.remove(&pid.id);
+            SystemMessage::Terminated { who, address_terminated } => {
+                // 被监视的远程 Actor 已终止
+                for watcher in self.watches.remote_terminated(&who) {
+                    self.deliver_terminated(&watcher, &who, address_terminated).await;
+                }
             }
             _ => {}
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    async fn deliver_terminated(&self, watcher: &Pid, who: &Pid, address_terminated: bool) {
+        let terminated = Message::new(SystemMessage::Terminated {
+            who: who.clone(),
+            address_terminated,
+        });
+        if let Err(e) = self.system.send(watcher, terminated).await {
+            log::warn!("Failed to deliver Terminated for {} to {}: {:?}", who.id, watcher.id, e);
+        }
+    }
+}
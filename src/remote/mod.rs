@@ -1,58 +1,285 @@
+mod batch;
+mod compression;
+mod connection_pool;
 mod endpoint;
+mod endpoint_state;
+mod errors;
+mod failure_detector;
+mod flow_control;
+mod heartbeat;
+mod message_handler;
+mod protocol;
+mod reconnection;
+mod remote_ref;
 mod serialization;
+mod system_message;
+mod tcp_connection;
 mod transport;
+mod watch;
 
-pub use endpoint::{RemoteEndpoint, EndpointManager};
-pub use serialization::{Serializer, MessageSerializer};
-pub use transport::{Transport, TcpTransport};
+pub use batch::{BatchConfig, BatchManager};
+pub use compression::{
+    CompressorRegistry, FrameCompression, GzipCompressor, Lz4Compressor, MessageCompressor, ZstdCompressor,
+    DEFAULT_MIN_COMPRESS_SIZE, NO_COMPRESSION,
+};
+pub use connection_pool::{ConnectionPool, PoolConfig, PoolStats, PooledConnection};
+pub use endpoint::{RemoteEndpoint, EndpointManager, DEFAULT_SEND_QUEUE_CAPACITY};
+pub use endpoint_state::{EndpointState, EndpointTerminated};
+pub use failure_detector::{PhiAccrualConfig, PhiAccrualFailureDetector};
+pub use flow_control::{CreditGrant, CreditGranter, FlowControlConfig, SendCredits};
+pub use heartbeat::{HeartbeatManager, HeartbeatMessage};
+pub use message_handler::RemoteMessageHandler;
+pub use errors::{CompressionError, ProtocolError, RemoteError, SerializationError};
+pub use protocol::{
+    client_handshake, read_frame, server_handshake, write_frame, Frame, FrameFlags, FrameType, Handshake,
+    Negotiated, DEFAULT_MAX_FRAME_SIZE, PROTOCOL_VERSION,
+};
+pub use reconnection::ReconnectionStrategy;
+pub use remote_ref::RemoteRefs;
+pub use serialization::{
+    BincodeCodec, Codec, JsonCodec, MessageEnvelope, MessagePackCodec, MessageSerializer, Serializer,
+    StoredMessageSerializer, WireCodec,
+};
+pub use system_message::SystemMessage;
+pub use tcp_connection::{ConnectionSettings, FrameReader, TcpConnection};
+pub use transport::{Connection, InboundSender, Transport, TcpTransport};
+pub use watch::RemoteWatchRegistry;
 
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use dashmap::DashMap;
+use tokio::sync::{mpsc, RwLock};
 use crate::{ActorSystem, Message, Pid, SendError};
-use crate::remote::message_handler::RemoteMessageHandler;
-use crate::remote::errors::RemoteError;
-use crate::remote::system_message::SystemMessage;
-use std::net::SocketAddr;
-use tokio::net::{TcpListener, TcpStream};
+use crate::dispatcher::BackpressureController;
 
 pub struct RemoteConfig {
     pub host: String,
     pub port: u16,
     pub serializer: Box<dyn Serializer>,
+    /// 握手中声明的压缩算法，按偏好排序
+    pub compressors: Arc<CompressorRegistry>,
+    /// 小于该长度的帧不压缩
+    pub min_compress_size: usize,
+    pub flow_control: FlowControlConfig,
+    /// 本系统邮箱的背压控制器，接受的连接按其负载回授信用
+    pub backpressure: Option<Arc<BackpressureController>>,
 }
 
+impl RemoteConfig {
+    pub fn new(host: impl Into<String>, port: u16, serializer: Box<dyn Serializer>) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            serializer,
+            compressors: Arc::new(CompressorRegistry::default()),
+            min_compress_size: DEFAULT_MIN_COMPRESS_SIZE,
+            flow_control: FlowControlConfig::default(),
+            backpressure: None,
+        }
+    }
+
+    pub fn with_compressors(mut self, compressors: CompressorRegistry) -> Self {
+        self.compressors = Arc::new(compressors);
+        self
+    }
+
+    pub fn with_min_compress_size(mut self, min_compress_size: usize) -> Self {
+        self.min_compress_size = min_compress_size;
+        self
+    }
+
+    pub fn with_flow_control(mut self, flow_control: FlowControlConfig) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    pub fn with_backpressure(mut self, backpressure: Arc<BackpressureController>) -> Self {
+        self.backpressure = Some(backpressure);
+        self
+    }
+}
+
+/// 远程通信的入口
+///
+/// 接受的连接按配置的压缩、流量控制和背压设置建立，收到的消息由
+/// `RemoteMessageHandler` 投递；创建后注册到 `ActorSystem`，本地 Actor
+/// 终止时据此通知监视它的远程 Actor。
 pub struct RemoteContext {
-    config: RemoteConfig,
-    connections: Arc<DashMap<String, RemoteEndpoint>>,
+    // 本节点实际监听的地址，本地 Actor 的 Pid 使用该地址
+    address: String,
+    system: Arc<ActorSystem>,
+    transport: Arc<RwLock<Box<dyn Transport>>>,
+    endpoints: EndpointManager,
+    watches: Arc<RemoteWatchRegistry>,
 }
 
 impl RemoteContext {
-    pub async fn new(config: RemoteConfig) -> Result<Self, std::io::Error> {
-        let ctx = Self {
-            config,
-            connections: Arc::new(DashMap::new()),
-        };
+    pub async fn new(config: RemoteConfig, system: Arc<ActorSystem>) -> Result<Arc<Self>, RemoteError> {
+        let watches = Arc::new(RemoteWatchRegistry::new());
+        let (inbound, mut received) = mpsc::unbounded_channel();
+        let (outbound, mut replies) = mpsc::unbounded_channel();
+        // 收到的消息的发送方还原为代理引用，回复经由本上下文发回发送方节点
+        let serializer = MessageSerializer::new().with_remote_refs(RemoteRefs::new(outbound));
+        let transport = TcpTransport::from_config(&config)
+            .with_serializer(serializer)
+            .with_inbound(inbound);
+        let address = transport.bind(&format!("{}:{}", config.host, config.port)).await?.to_string();
+
+        let handler = RemoteMessageHandler::new(Arc::clone(&system), Arc::clone(&watches));
+        tokio::spawn(async move {
+            while let Some((target, message)) = received.recv().await {
+                if let Err(e) = handler.handle_inbound(target, message).await {
+                    log::warn!("Failed to handle remote message: {:?}", e);
+                }
+            }
+        });
+
+        let ctx = Arc::new(Self {
+            address,
+            system: Arc::clone(&system),
+            transport: Arc::new(RwLock::new(Box::new(transport) as Box<dyn Transport>)),
+            endpoints: EndpointManager::new().with_watches(Arc::clone(&watches)),
+            watches,
+        });
+        system.set_remote(Arc::clone(&ctx));
 
-        ctx.start_server().await?;
+        let weak = Arc::downgrade(&ctx);
+        tokio::spawn(async move {
+            while let Some((target, message)) = replies.recv().await {
+                let ctx = match weak.upgrade() {
+                    Some(ctx) => ctx,
+                    None => break,
+                };
+                if let Err(e) = ctx.send(&target, message).await {
+                    log::warn!("Failed to send reply to {}: {:?}", target.address, e);
+                }
+            }
+        });
         Ok(ctx)
     }
 
     pub async fn send(&self, target: &Pid, msg: Message) -> Result<(), SendError> {
-        let endpoint = self.get_or_connect_endpoint(&target.address).await?;
+        let endpoint = self.endpoints.get_or_create(
+            target.address.clone(),
+            Arc::clone(&self.system),
+            Arc::clone(&self.transport),
+        );
         endpoint.send(target, msg).await
     }
 
-    async fn start_server(&self) -> Result<(), std::io::Error> {
-        let addr = format!("{}:{}", self.config.host, self.config.port);
-        let listener = TcpListener::bind(addr).await?;
+    pub fn address(&self) -> &str {
+        &self.address
+    }
 
-        tokio::spawn(async move {
-            while let Ok((stream, _)) = listener.accept().await {
-                self.handle_connection(stream).await;
+    /// 本地 Actor 在其他节点上的标识
+    pub fn pid(&self, id: impl Into<String>) -> Pid {
+        Pid {
+            address: self.address.clone(),
+            id: id.into(),
+        }
+    }
+
+    pub fn watches(&self) -> Arc<RemoteWatchRegistry> {
+        self.watches.clone()
+    }
+
+    /// 本地 `watcher` 监视远程 `target`，同一目标只向远程节点发送一次 Watch
+    ///
+    /// Watch 发送失败时撤销登记，否则之后的 watch 会误以为远程节点已收到。
+    pub async fn watch(&self, watcher: Pid, target: Pid) -> Result<(), SendError> {
+        if !self.watches.watch(watcher.clone(), target.clone()) {
+            return Ok(());
+        }
+        let result = self.send_system(&target, SystemMessage::Watch(watcher.clone(), target.clone())).await;
+        if result.is_err() {
+            self.watches.unwatch(&watcher, &target);
+        }
+        result
+    }
+
+    /// 本地 `watcher` 取消监视远程 `target`，最后一个监视者取消时通知远程节点
+    pub async fn unwatch(&self, watcher: Pid, target: Pid) -> Result<(), SendError> {
+        if !self.watches.unwatch(&watcher, &target) {
+            return Ok(());
+        }
+        self.send_system(&target, SystemMessage::Unwatch(watcher, target.clone())).await
+    }
+
+    /// 本地 Actor 终止，通知监视它的远程 Actor
+    pub async fn notify_terminated(&self, target: &Pid) {
+        for watcher in self.watches.local_terminated(target) {
+            let terminated = SystemMessage::Terminated {
+                who: target.clone(),
+                address_terminated: false,
+            };
+            if let Err(e) = self.send_system(&watcher, terminated).await {
+                log::warn!("Failed to notify {} of {} termination: {:?}", watcher.id, target.id, e);
             }
-        });
+        }
+    }
+
+    async fn send_system(&self, target: &Pid, message: SystemMessage) -> Result<(), SendError> {
+        self.send(target, Message::new(message)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use async_trait::async_trait;
+    use super::*;
+    use crate::{Actor, Context, Props, SystemConfig};
+
+    fn config() -> RemoteConfig {
+        let serializer = StoredMessageSerializer::new(MessageSerializer::new());
+        RemoteConfig::new("127.0.0.1", 0, Box::new(serializer))
+    }
+
+    struct Idle;
 
-        Ok(())
+    #[async_trait]
+    impl Actor for Idle {
+        async fn receive(&mut self, _ctx: &Context, _msg: Message) -> Result<(), SendError> {
+            Ok(())
+        }
     }
-} 
\ No newline at end of file
+
+    struct Watcher {
+        terminated: std::sync::mpsc::Sender<Pid>,
+    }
+
+    #[async_trait]
+    impl Actor for Watcher {
+        async fn receive(&mut self, _ctx: &Context, msg: Message) -> Result<(), SendError> {
+            if let Some(SystemMessage::Terminated { who, .. }) = msg.payload.downcast_ref::<SystemMessage>() {
+                let _ = self.terminated.send(who.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stopping_watched_actor_notifies_remote_watcher() {
+        let node_a = Arc::new(ActorSystem::new(SystemConfig::default()));
+        let node_b = Arc::new(ActorSystem::new(SystemConfig::default()));
+        let remote_a = node_a.runtime().block_on(RemoteContext::new(config(), Arc::clone(&node_a))).unwrap();
+        let remote_b = node_b.runtime().block_on(RemoteContext::new(config(), Arc::clone(&node_b))).unwrap();
+
+        let target = node_a.spawn(&Props::new(|| Idle)).unwrap();
+        let (terminated, received) = std::sync::mpsc::channel();
+        let watcher = node_b.spawn(&Props::new(move || Watcher { terminated: terminated.clone() })).unwrap();
+
+        let target_pid = remote_a.pid(target.id());
+        node_b.runtime()
+            .block_on(remote_b.watch(remote_b.pid(watcher.id()), target_pid.clone()))
+            .unwrap();
+
+        // Watch 经由连接异步到达节点 A
+        let started = Instant::now();
+        while !remote_a.watches().is_watched(&target_pid) {
+            assert!(started.elapsed() < Duration::from_secs(5), "watch never reached node A");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        node_a.runtime().block_on(target.stop());
+        assert_eq!(received.recv_timeout(Duration::from_secs(5)).unwrap(), target_pid);
+    }
+}
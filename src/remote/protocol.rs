@@ -0,0 +1,493 @@
+//! 远程通信的帧格式与握手
+//!
+//! 每个帧的格式（整数均为大端序）：
+//!
+//! ```text
+//! +----------------+-------------+--------------+-------------------------+
+//! | length: u32    | type: u8    | flags: u8    | payload: length - 2 字节 |
+//! +----------------+-------------+--------------+-------------------------+
+//! ```
+//!
+//! `length` 包含 type 与 flags 两个字节，不包含自身。
+//! 连接建立后双方必须先交换 `Handshake` 帧，之后才能发送其他类型的帧。
+
+use std::future::Future;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use super::errors::{ProtocolError, RemoteError};
+
+/// 当前协议版本，版本不同的节点无法建立连接
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// 长度前缀的字节数
+pub const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// 帧头（type 与 flags）的字节数
+const HEADER_SIZE: usize = 2;
+
+/// 默认允许的最大帧长度
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// 握手帧的最大长度，握手只包含地址和格式名称
+pub const MAX_HANDSHAKE_FRAME_SIZE: usize = 64 * 1024;
+
+/// 等待对方完成握手的最长时间
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Handshake = 0,
+    User = 1,
+    System = 2,
+    Heartbeat = 3,
+    Batch = 4,
+    /// 接收方回授的流量控制信用
+    Credit = 5,
+}
+
+impl FrameType {
+    fn from_u8(value: u8) -> Result<Self, ProtocolError> {
+        match value {
+            0 => Ok(FrameType::Handshake),
+            1 => Ok(FrameType::User),
+            2 => Ok(FrameType::System),
+            3 => Ok(FrameType::Heartbeat),
+            4 => Ok(FrameType::Batch),
+            5 => Ok(FrameType::Credit),
+            other => Err(ProtocolError::UnknownFrameType(other)),
+        }
+    }
+}
+
+/// 帧标志位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameFlags(u8);
+
+impl FrameFlags {
+    /// 载荷已压缩
+    pub const COMPRESSED: FrameFlags = FrameFlags(0x01);
+    /// 载荷包含多条消息
+    pub const BATCHED: FrameFlags = FrameFlags(0x02);
+
+    const ALL: u8 = 0x03;
+
+    pub fn empty() -> Self {
+        FrameFlags(0)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u8) -> Result<Self, ProtocolError> {
+        if bits & !Self::ALL != 0 {
+            return Err(ProtocolError::UnknownFlags(bits));
+        }
+        Ok(FrameFlags(bits))
+    }
+
+    pub fn contains(self, other: FrameFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn with(self, other: FrameFlags) -> Self {
+        FrameFlags(self.0 | other.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub frame_type: FrameType,
+    pub flags: FrameFlags,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(frame_type: FrameType, payload: Vec<u8>) -> Self {
+        Self {
+            frame_type,
+            flags: FrameFlags::empty(),
+            payload,
+        }
+    }
+
+    pub fn with_flags(mut self, flags: FrameFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// 编码后的总字节数
+    pub fn encoded_len(&self) -> usize {
+        LENGTH_PREFIX_SIZE + HEADER_SIZE + self.payload.len()
+    }
+
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let length = (HEADER_SIZE + self.payload.len()) as u32;
+        buf.reserve(self.encoded_len());
+        buf.extend_from_slice(&length.to_be_bytes());
+        buf.push(self.frame_type as u8);
+        buf.push(self.flags.bits());
+        buf.extend_from_slice(&self.payload);
+    }
+
+    /// 从缓冲区头部解码一个帧
+    ///
+    /// 数据不足时返回 `Ok(None)`，成功时同时返回消耗的字节数。
+    pub fn decode(buf: &[u8], max_frame_size: usize) -> Result<Option<(Frame, usize)>, ProtocolError> {
+        if buf.len() < LENGTH_PREFIX_SIZE {
+            return Ok(None);
+        }
+
+        let length = Self::check_length(&buf[..LENGTH_PREFIX_SIZE], max_frame_size)?;
+        let total = LENGTH_PREFIX_SIZE + length;
+        if buf.len() < total {
+            return Ok(None);
+        }
+
+        let frame = Self::decode_body(&buf[LENGTH_PREFIX_SIZE..total])?;
+        Ok(Some((frame, total)))
+    }
+
+    fn check_length(prefix: &[u8], max_frame_size: usize) -> Result<usize, ProtocolError> {
+        let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+        if length < HEADER_SIZE {
+            return Err(ProtocolError::InvalidLength(length));
+        }
+        if length > max_frame_size {
+            return Err(ProtocolError::FrameTooLarge(length));
+        }
+        Ok(length)
+    }
+
+    fn decode_body(body: &[u8]) -> Result<Frame, ProtocolError> {
+        Ok(Frame {
+            frame_type: FrameType::from_u8(body[0])?,
+            flags: FrameFlags::from_bits(body[1])?,
+            payload: body[HEADER_SIZE..].to_vec(),
+        })
+    }
+}
+
+/// 从流中读取一个帧
+pub async fn read_frame<R>(reader: &mut R, max_frame_size: usize) -> Result<Frame, RemoteError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut prefix = [0u8; LENGTH_PREFIX_SIZE];
+    reader.read_exact(&mut prefix).await?;
+    let length = Frame::check_length(&prefix, max_frame_size)?;
+
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Frame::decode_body(&body)?)
+}
+
+/// 向流中写入一个帧
+pub async fn write_frame<W>(writer: &mut W, frame: &Frame) -> Result<(), RemoteError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = Vec::with_capacity(frame.encoded_len());
+    frame.encode(&mut buf);
+    writer.write_all(&buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// 握手时交换的节点信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    pub address: String,
+    pub protocol_version: u16,
+    /// 按偏好排序的序列化格式，见 `WireCodec`
+    pub serializers: Vec<String>,
+    /// 按偏好排序的压缩算法
+    pub compressors: Vec<String>,
+}
+
+impl Handshake {
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            protocol_version: PROTOCOL_VERSION,
+            serializers: vec!["bincode".to_string()],
+            compressors: vec!["none".to_string()],
+        }
+    }
+
+    pub fn with_serializers(mut self, serializers: Vec<String>) -> Self {
+        self.serializers = serializers;
+        self
+    }
+
+    pub fn with_compressors(mut self, compressors: Vec<String>) -> Self {
+        self.compressors = compressors;
+        self
+    }
+
+    /// 按本地偏好选出双方都支持的序列化格式与压缩算法
+    pub fn negotiate(&self, remote: &Handshake) -> Result<Negotiated, ProtocolError> {
+        self.negotiate_by(remote, false)
+    }
+
+    /// 接受连接的一方按发起方的偏好协商，保证双方得到相同的结果
+    fn accept(&self, remote: &Handshake) -> Result<Negotiated, ProtocolError> {
+        self.negotiate_by(remote, true)
+    }
+
+    fn negotiate_by(&self, remote: &Handshake, prefer_remote: bool) -> Result<Negotiated, ProtocolError> {
+        let (preferred, other) = if prefer_remote { (remote, self) } else { (self, remote) };
+        if remote.protocol_version != self.protocol_version {
+            return Err(ProtocolError::VersionMismatch {
+                local: self.protocol_version,
+                remote: remote.protocol_version,
+            });
+        }
+
+        let serializer = first_common(&preferred.serializers, &other.serializers)
+            .ok_or_else(|| ProtocolError::NoCommonSerializer(remote.serializers.clone()))?;
+        // 未压缩总是可用
+        let compressor = first_common(&preferred.compressors, &other.compressors)
+            .unwrap_or_else(|| "none".to_string());
+
+        Ok(Negotiated {
+            remote_address: remote.address.clone(),
+            protocol_version: self.protocol_version,
+            serializer,
+            compressor,
+        })
+    }
+}
+
+fn first_common(local: &[String], remote: &[String]) -> Option<String> {
+    local.iter().find(|name| remote.contains(name)).cloned()
+}
+
+/// 握手协商的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Negotiated {
+    pub remote_address: String,
+    pub protocol_version: u16,
+    pub serializer: String,
+    pub compressor: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum HandshakeMessage {
+    Hello(Handshake),
+    Accept(Handshake),
+    Reject { protocol_version: u16, reason: String },
+}
+
+async fn send_handshake<W>(writer: &mut W, message: &HandshakeMessage) -> Result<(), RemoteError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let payload = bincode::serialize(message)
+        .map_err(|e| ProtocolError::MalformedHandshake(e.to_string()))?;
+    write_frame(writer, &Frame::new(FrameType::Handshake, payload)).await
+}
+
+async fn receive_handshake<R>(reader: &mut R) -> Result<HandshakeMessage, RemoteError>
+where
+    R: AsyncRead + Unpin,
+{
+    let frame = read_frame(reader, MAX_HANDSHAKE_FRAME_SIZE).await?;
+    if frame.frame_type != FrameType::Handshake {
+        return Err(ProtocolError::UnexpectedFrame(frame.frame_type).into());
+    }
+    bincode::deserialize(&frame.payload)
+        .map_err(|e| ProtocolError::MalformedHandshake(e.to_string()).into())
+}
+
+/// 对方在 `HANDSHAKE_TIMEOUT` 内没有完成握手时返回错误，避免连接一直占用
+async fn within_timeout<F>(handshake: F) -> Result<Negotiated, RemoteError>
+where
+    F: Future<Output = Result<Negotiated, RemoteError>>,
+{
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake).await {
+        Ok(result) => result,
+        Err(_) => Err(RemoteError::HandshakeError(format!(
+            "handshake not completed within {:?}",
+            HANDSHAKE_TIMEOUT
+        ))),
+    }
+}
+
+/// 发起连接的一方：发送本地信息并等待对方接受
+pub async fn client_handshake<S>(stream: &mut S, local: &Handshake) -> Result<Negotiated, RemoteError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    within_timeout(async {
+        send_handshake(stream, &HandshakeMessage::Hello(local.clone())).await?;
+
+        match receive_handshake(stream).await? {
+            HandshakeMessage::Accept(remote) => Ok(local.negotiate(&remote)?),
+            HandshakeMessage::Reject { protocol_version, reason } => Err(RemoteError::HandshakeError(format!(
+                "rejected by remote (protocol version {}): {}",
+                protocol_version, reason
+            ))),
+            HandshakeMessage::Hello(_) => Err(RemoteError::HandshakeError("unexpected hello".to_string())),
+        }
+    })
+    .await
+}
+
+/// 接受连接的一方：校验对方信息，协商失败时回复拒绝原因
+pub async fn server_handshake<S>(stream: &mut S, local: &Handshake) -> Result<Negotiated, RemoteError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    within_timeout(async {
+        let remote = match receive_handshake(stream).await? {
+            HandshakeMessage::Hello(remote) => remote,
+            _ => return Err(RemoteError::HandshakeError("expected hello".to_string())),
+        };
+
+        match local.accept(&remote) {
+            Ok(negotiated) => {
+                send_handshake(stream, &HandshakeMessage::Accept(local.clone())).await?;
+                Ok(negotiated)
+            }
+            Err(e) => {
+                log::warn!("Rejecting handshake from {}: {}", remote.address, e);
+                send_handshake(stream, &HandshakeMessage::Reject {
+                    protocol_version: local.protocol_version,
+                    reason: e.to_string(),
+                }).await?;
+                Err(e.into())
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn encoded(frame: &Frame) -> Vec<u8> {
+        let mut buf = Vec::new();
+        frame.encode(&mut buf);
+        buf
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let frame = Frame::new(FrameType::Batch, b"payload".to_vec())
+            .with_flags(FrameFlags::BATCHED.with(FrameFlags::COMPRESSED));
+        let buf = encoded(&frame);
+
+        // Partial input waits for more data
+        for end in 0..buf.len() {
+            assert_eq!(Frame::decode(&buf[..end], DEFAULT_MAX_FRAME_SIZE).unwrap(), None);
+        }
+        let (decoded, consumed) = Frame::decode(&buf, DEFAULT_MAX_FRAME_SIZE).unwrap().unwrap();
+        assert_eq!(decoded, frame);
+        assert_eq!(consumed, buf.len());
+    }
+
+    #[test]
+    fn test_malformed_frames_are_rejected() {
+        let mut buf = encoded(&Frame::new(FrameType::User, vec![1, 2, 3]));
+        buf[4] = 42;
+        assert!(matches!(Frame::decode(&buf, 1024), Err(ProtocolError::UnknownFrameType(42))));
+
+        let mut buf = encoded(&Frame::new(FrameType::User, vec![1, 2, 3]));
+        buf[5] = 0x80;
+        assert!(matches!(Frame::decode(&buf, 1024), Err(ProtocolError::UnknownFlags(0x80))));
+
+        assert!(matches!(Frame::decode(&[0, 0, 0, 1, 1], 1024), Err(ProtocolError::InvalidLength(1))));
+        assert!(matches!(Frame::decode(&[0xff; 8], 1024), Err(ProtocolError::FrameTooLarge(_))));
+    }
+
+    #[tokio::test]
+    async fn test_random_input_never_panics() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let valid = encoded(&Frame::new(FrameType::System, vec![7; 32]));
+
+        for _ in 0..2000 {
+            let mut input = if rng.gen_bool(0.5) {
+                valid.clone()
+            } else {
+                (0..rng.gen_range(0..64)).map(|_| rng.gen()).collect()
+            };
+            // Flip a few random bytes
+            for _ in 0..rng.gen_range(0..4) {
+                if !input.is_empty() {
+                    let i = rng.gen_range(0..input.len());
+                    input[i] = rng.gen();
+                }
+            }
+
+            let _ = Frame::decode(&input, 1024);
+            let _ = read_frame(&mut input.as_slice(), 1024).await;
+            let _ = receive_handshake(&mut input.as_slice()).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_negotiates_common_formats() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let client_info = Handshake::new("client:8000")
+            .with_serializers(vec!["msgpack".to_string(), "json".to_string(), "bincode".to_string()]);
+        let server_info = Handshake::new("server:9000")
+            .with_serializers(vec!["bincode".to_string(), "json".to_string()]);
+
+        let server_task = tokio::spawn(async move { server_handshake(&mut server, &server_info).await });
+        let negotiated = client_handshake(&mut client, &client_info).await.unwrap();
+        assert_eq!(negotiated.remote_address, "server:9000");
+        assert_eq!(negotiated.serializer, "json");
+        assert_eq!(negotiated.compressor, "none");
+
+        // Both sides follow the initiator's preference
+        let negotiated = server_task.await.unwrap().unwrap();
+        assert_eq!(negotiated.remote_address, "client:8000");
+        assert_eq!(negotiated.serializer, "json");
+    }
+
+    #[tokio::test]
+    async fn test_version_mismatch_is_rejected() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let mut client_info = Handshake::new("client:8000");
+        client_info.protocol_version = PROTOCOL_VERSION + 1;
+        let server_info = Handshake::new("server:9000");
+
+        let server_task = tokio::spawn(async move { server_handshake(&mut server, &server_info).await });
+        let result = client_handshake(&mut client, &client_info).await;
+        assert!(matches!(result, Err(RemoteError::HandshakeError(_))));
+
+        let result = server_task.await.unwrap();
+        assert!(matches!(
+            result,
+            Err(RemoteError::ProtocolError(ProtocolError::VersionMismatch { .. }))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_silent_peer_times_out() {
+        // The client never sends its hello
+        let (_client, mut server) = tokio::io::duplex(1024);
+        let server_info = Handshake::new("server:9000");
+
+        let result = server_handshake(&mut server, &server_info).await;
+        assert!(matches!(result, Err(RemoteError::HandshakeError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_handshake_frame_is_rejected() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let length = (MAX_HANDSHAKE_FRAME_SIZE + 1) as u32;
+        client.write_all(&length.to_be_bytes()).await.unwrap();
+
+        let result = server_handshake(&mut server, &Handshake::new("server:9000")).await;
+        assert!(matches!(
+            result,
+            Err(RemoteError::ProtocolError(ProtocolError::FrameTooLarge(_)))
+        ));
+    }
+}
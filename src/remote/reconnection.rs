@@ -1,6 +1,6 @@
 use std::time::Duration;
 use tokio::time::sleep;
-use crate::{RemoteEndpoint, RemoteError};
+use super::{RemoteEndpoint, RemoteError};
 
 pub struct ReconnectionStrategy {
     max_attempts: u32,
@@ -17,6 +17,10 @@ impl ReconnectionStrategy {
         }
     }
 
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
     pub async fn attempt_reconnect(&self, endpoint: &RemoteEndpoint) -> Result<(), RemoteError> {
         let mut attempts = 0;
         let mut delay = self.base_delay;
@@ -28,7 +32,7 @@ impl ReconnectionStrategy {
                     log::warn!(
                         "Reconnection attempt {} failed for {}: {:?}",
                         attempts + 1,
-                        endpoint.address,
+                        endpoint.address(),
                         e
                     );
                     attempts += 1;
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use dashmap::DashMap;
+use tokio::sync::mpsc;
+use crate::{Message, Pid};
+use crate::actor::ActorRef;
+
+/// 代理邮箱的容量
+const PROXY_CAPACITY: usize = 128;
+
+/// 远程 Actor 在本地的代理引用
+///
+/// 收到的消息带有远程发送方时，反序列化以代理引用填入 `sender`；
+/// 发往代理的消息转交给 `outbound`，由远程上下文发往发送方所在节点。
+pub struct RemoteRefs {
+    refs: DashMap<Pid, ActorRef>,
+    outbound: mpsc::UnboundedSender<(Pid, Message)>,
+}
+
+impl RemoteRefs {
+    pub fn new(outbound: mpsc::UnboundedSender<(Pid, Message)>) -> Arc<Self> {
+        Arc::new(Self {
+            refs: DashMap::new(),
+            outbound,
+        })
+    }
+
+    /// 返回 `pid` 的代理引用，同一个远程 Actor 共用一个代理
+    pub fn get(&self, pid: Pid) -> ActorRef {
+        self.refs
+            .entry(pid.clone())
+            .or_insert_with(|| self.spawn_proxy(pid))
+            .clone()
+    }
+
+    fn spawn_proxy(&self, pid: Pid) -> ActorRef {
+        let (sender, mut receiver) = mpsc::channel(PROXY_CAPACITY);
+        let outbound = self.outbound.clone();
+        let target = pid.clone();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if outbound.send((target.clone(), message)).is_err() {
+                    break;
+                }
+            }
+        });
+        ActorRef::remote(pid, sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_proxy_forwards_to_remote_pid() {
+        let (outbound, mut sent) = mpsc::unbounded_channel();
+        let refs = RemoteRefs::new(outbound);
+        let pid = Pid {
+            address: "node-b:9000".to_string(),
+            id: "worker".to_string(),
+        };
+
+        let proxy = refs.get(pid.clone());
+        assert_eq!(proxy.address(), Some("node-b:9000"));
+        assert_eq!(refs.get(pid.clone()), proxy);
+
+        proxy.send(Message::new("reply".to_string())).await.unwrap();
+        let (to, message) = sent.recv().await.unwrap();
+        assert_eq!(to, pid);
+        assert_eq!(message.payload.downcast_ref::<String>().map(String::as_str), Some("reply"));
+    }
+}
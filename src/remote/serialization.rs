@@ -1,64 +1,388 @@
+use std::any::{Any, TypeId};
+use std::marker::PhantomData;
+use std::sync::Arc;
 use async_trait::async_trait;
+use dashmap::DashMap;
 use serde::{Serialize, Deserialize};
-use crate::{Message, MessageHeader, Pid};
+use serde::de::DeserializeOwned;
+use crate::{Message, Pid};
+use crate::actor::ActorRef;
+use crate::message::MessageHeader;
+use super::errors::SerializationError;
+use super::{RemoteRefs, SystemMessage};
 
 #[async_trait]
 pub trait Serializer: Send + Sync {
-    async fn serialize(&self, message: &Message) -> Result<Vec<u8>, SerializationError>;
+    /// 同步编码：`Message` 不是 `Sync`，其引用不能跨越 await
+    fn serialize(&self, message: &Message) -> Result<Vec<u8>, SerializationError>;
     async fn deserialize(&self, bytes: &[u8]) -> Result<Message, SerializationError>;
 }
 
+/// 载荷的编码格式
+pub trait Codec: Send + Sync + 'static {
+    /// 在握手中使用的格式名称
+    fn name(&self) -> &'static str;
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, SerializationError>;
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, SerializationError>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    fn name(&self) -> &'static str {
+        "bincode"
+    }
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, SerializationError> {
+        bincode::serialize(value).map_err(|e| SerializationError::EncodingError(e.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, SerializationError> {
+        bincode::deserialize(bytes).map_err(|e| SerializationError::DecodingError(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, SerializationError> {
+        serde_json::to_vec(value).map_err(|e| SerializationError::EncodingError(e.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, SerializationError> {
+        serde_json::from_slice(bytes).map_err(|e| SerializationError::DecodingError(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackCodec;
+
+impl Codec for MessagePackCodec {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, SerializationError> {
+        // 以字段名编码结构体，便于非 Rust 的对端读取
+        rmp_serde::to_vec_named(value).map_err(|e| SerializationError::EncodingError(e.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, SerializationError> {
+        rmp_serde::from_slice(bytes).map_err(|e| SerializationError::DecodingError(e.to_string()))
+    }
+}
+
+/// 握手中可协商的编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireCodec {
+    #[default]
+    Bincode,
+    Json,
+    MessagePack,
+}
+
+impl WireCodec {
+    /// 按偏好排序的全部格式
+    pub const ALL: [WireCodec; 3] = [WireCodec::Bincode, WireCodec::MessagePack, WireCodec::Json];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|codec| codec.name() == name)
+    }
+
+    /// 握手中通告的格式名称
+    pub fn names() -> Vec<String> {
+        Self::ALL.iter().map(|codec| codec.name().to_string()).collect()
+    }
+}
+
+impl Codec for WireCodec {
+    fn name(&self) -> &'static str {
+        match self {
+            WireCodec::Bincode => BincodeCodec.name(),
+            WireCodec::Json => JsonCodec.name(),
+            WireCodec::MessagePack => MessagePackCodec.name(),
+        }
+    }
+
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, SerializationError> {
+        match self {
+            WireCodec::Bincode => BincodeCodec.encode(value),
+            WireCodec::Json => JsonCodec.encode(value),
+            WireCodec::MessagePack => MessagePackCodec.encode(value),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, SerializationError> {
+        match self {
+            WireCodec::Bincode => BincodeCodec.decode(bytes),
+            WireCodec::Json => JsonCodec.decode(bytes),
+            WireCodec::MessagePack => MessagePackCodec.decode(bytes),
+        }
+    }
+}
+
+/// 已注册类型的编解码入口，`wire` 为连接协商的格式
+trait PayloadCodec: Send + Sync {
+    fn manifest(&self) -> &str;
+    fn encode(&self, payload: &(dyn Any + Send), wire: WireCodec) -> Result<Vec<u8>, SerializationError>;
+    fn decode(&self, bytes: &[u8], wire: WireCodec) -> Result<Box<dyn Any + Send>, SerializationError>;
+}
+
+struct Registration<T, C> {
+    manifest: String,
+    codec: C,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, C> PayloadCodec for Registration<T, C>
+where
+    T: Serialize + DeserializeOwned + Any + Send,
+    C: Codec,
+{
+    fn manifest(&self) -> &str {
+        &self.manifest
+    }
+
+    fn encode(&self, payload: &(dyn Any + Send), _wire: WireCodec) -> Result<Vec<u8>, SerializationError> {
+        self.codec.encode(downcast::<T>(payload, &self.manifest)?)
+    }
+
+    fn decode(&self, bytes: &[u8], _wire: WireCodec) -> Result<Box<dyn Any + Send>, SerializationError> {
+        Ok(Box::new(self.codec.decode::<T>(bytes)?))
+    }
+}
+
+/// 使用连接协商格式编码的注册
+struct WireRegistration<T> {
+    manifest: String,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> PayloadCodec for WireRegistration<T>
+where
+    T: Serialize + DeserializeOwned + Any + Send,
+{
+    fn manifest(&self) -> &str {
+        &self.manifest
+    }
+
+    fn encode(&self, payload: &(dyn Any + Send), wire: WireCodec) -> Result<Vec<u8>, SerializationError> {
+        wire.encode(downcast::<T>(payload, &self.manifest)?)
+    }
+
+    fn decode(&self, bytes: &[u8], wire: WireCodec) -> Result<Box<dyn Any + Send>, SerializationError> {
+        Ok(Box::new(wire.decode::<T>(bytes)?))
+    }
+}
+
+fn downcast<'a, T: Any>(payload: &'a (dyn Any + Send), manifest: &str) -> Result<&'a T, SerializationError> {
+    payload
+        .downcast_ref::<T>()
+        .ok_or_else(|| SerializationError::EncodingError(format!("payload is not {}", manifest)))
+}
+
+/// 线上传输的消息，载荷以 manifest 标识类型
 #[derive(Serialize, Deserialize)]
 pub struct MessageEnvelope {
     pub target: Pid,
     pub sender: Option<Pid>,
-    pub message_type: String,
+    pub manifest: String,
     pub message_data: Vec<u8>,
     pub header: Option<MessageHeader>,
 }
 
+/// 按稳定的 manifest 注册载荷类型的序列化器
+///
+/// manifest 随消息一起传输，接收方据此选择反序列化方式，
+/// 因此同一类型在所有节点上必须使用相同的 manifest 注册。
+/// 克隆的序列化器共享注册表，各连接只有协商出的编码不同。
+#[derive(Clone)]
 pub struct MessageSerializer {
-    serializers: DashMap<String, Box<dyn Serializer>>,
+    by_manifest: Arc<DashMap<String, Arc<dyn PayloadCodec>>>,
+    by_type: Arc<DashMap<TypeId, Arc<dyn PayloadCodec>>>,
+    // 信封及未指定格式的载荷使用的编码，握手后按协商结果设置
+    codec: WireCodec,
+    // 本地发送方在信封中使用的节点地址
+    local_address: Option<String>,
+    // 收到的远程发送方还原为代理引用，未设置时丢弃发送方
+    remote_refs: Option<Arc<RemoteRefs>>,
 }
 
 impl MessageSerializer {
     pub fn new() -> Self {
-        Self {
-            serializers: DashMap::new(),
-        }
+        let serializer = Self {
+            by_manifest: Arc::new(DashMap::new()),
+            by_type: Arc::new(DashMap::new()),
+            codec: WireCodec::default(),
+            local_address: None,
+            remote_refs: None,
+        };
+        // 监视和终止通知在所有节点之间传输，预先注册
+        serializer.register_serde::<SystemMessage>("protoactor.SystemMessage");
+        serializer
+    }
+
+    pub fn with_codec(mut self, codec: WireCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// 本节点的地址，本地发送方以此地址编码，对端据此回复
+    pub fn with_local_address(mut self, address: impl Into<String>) -> Self {
+        self.local_address = Some(address.into());
+        self
+    }
+
+    /// 解码时把远程发送方还原为 `refs` 中的代理引用
+    pub fn with_remote_refs(mut self, refs: Arc<RemoteRefs>) -> Self {
+        self.remote_refs = Some(refs);
+        self
+    }
+
+    pub fn set_codec(&mut self, codec: WireCodec) {
+        self.codec = codec;
+    }
+
+    pub fn codec(&self) -> WireCodec {
+        self.codec
+    }
+
+    /// 以 `manifest` 注册类型 `T`，例如 `register::<PlaceOrder, _>("orders.PlaceOrder", JsonCodec)`
+    pub fn register<T, C>(&self, manifest: &str, codec: C)
+    where
+        T: Serialize + DeserializeOwned + Any + Send,
+        C: Codec,
+    {
+        self.insert::<T>(Arc::new(Registration::<T, C> {
+            manifest: manifest.to_string(),
+            codec,
+            _marker: PhantomData,
+        }));
+    }
+
+    /// 以 `manifest` 注册类型 `T`，载荷使用连接协商的格式编码
+    pub fn register_serde<T>(&self, manifest: &str)
+    where
+        T: Serialize + DeserializeOwned + Any + Send,
+    {
+        self.insert::<T>(Arc::new(WireRegistration::<T> {
+            manifest: manifest.to_string(),
+            _marker: PhantomData,
+        }));
+    }
+
+    fn insert<T: Any>(&self, registration: Arc<dyn PayloadCodec>) {
+        self.by_manifest.insert(registration.manifest().to_string(), Arc::clone(&registration));
+        self.by_type.insert(TypeId::of::<T>(), registration);
+    }
+
+    /// 载荷类型注册的 manifest
+    pub fn manifest_of(&self, message: &Message) -> Option<String> {
+        // 取 Box 内部值的类型，而不是 Box 本身
+        let type_id = (*message.payload).type_id();
+        self.by_type.get(&type_id).map(|r| r.manifest().to_string())
+    }
+
+    /// 编码载荷，返回 manifest 与字节
+    pub fn encode_payload(&self, message: &Message) -> Result<(String, Vec<u8>), SerializationError> {
+        self.encode_payload_with(message, self.codec)
     }
 
-    pub fn register<S: Serializer + 'static>(&self, message_type: &str, serializer: S) {
-        self.serializers.insert(message_type.to_string(), Box::new(serializer));
+    /// 以 `wire` 编码未指定格式的载荷
+    fn encode_payload_with(&self, message: &Message, wire: WireCodec) -> Result<(String, Vec<u8>), SerializationError> {
+        let type_id = (*message.payload).type_id();
+        let registration = self.by_type
+            .get(&type_id)
+            .map(|r| Arc::clone(&r))
+            .ok_or_else(|| {
+                let name = message.payload_type_name().map_or_else(|| format!("{:?}", type_id), str::to_string);
+                SerializationError::NoSerializerFound(name)
+            })?;
+        let data = registration.encode(message.payload.as_ref(), wire)?;
+        Ok((registration.manifest().to_string(), data))
+    }
+
+    /// 按 manifest 解码载荷
+    pub fn decode_payload(&self, manifest: &str, bytes: &[u8]) -> Result<Box<dyn Any + Send>, SerializationError> {
+        self.decode_payload_with(manifest, bytes, self.codec)
+    }
+
+    fn decode_payload_with(&self, manifest: &str, bytes: &[u8], wire: WireCodec) -> Result<Box<dyn Any + Send>, SerializationError> {
+        let registration = self.by_manifest
+            .get(manifest)
+            .map(|r| Arc::clone(&r))
+            .ok_or_else(|| SerializationError::NoSerializerFound(manifest.to_string()))?;
+        registration.decode(bytes, wire)
     }
 
     pub async fn serialize(&self, message: &Message, target: &Pid) -> Result<Vec<u8>, SerializationError> {
-        let message_type = message.payload.type_id().to_string();
-        if let Some(serializer) = self.serializers.get(&message_type) {
-            let message_data = serializer.serialize(message).await?;
-            let envelope = MessageEnvelope {
-                target: target.clone(),
-                sender: message.sender.clone(),
-                message_type,
-                message_data,
-                header: message.header.clone(),
-            };
-            bincode::serialize(&envelope).map_err(|e| SerializationError::EncodingError(e.to_string()))
-        } else {
-            Err(SerializationError::NoSerializerFound(message_type))
-        }
+        self.encode_message(message, target)
+    }
+
+    /// 编码消息及其目标，不跨越 await 持有消息的引用
+    pub fn encode_message(&self, message: &Message, target: &Pid) -> Result<Vec<u8>, SerializationError> {
+        let (manifest, message_data) = self.encode_payload(message)?;
+        let envelope = MessageEnvelope {
+            target: target.clone(),
+            sender: message.sender.as_ref().and_then(|sender| self.sender_pid(sender)),
+            manifest,
+            message_data,
+            header: message.message_header().cloned(),
+        };
+        self.codec.encode(&envelope)
+    }
+
+    /// 解码消息及其目标
+    pub async fn deserialize_envelope(&self, bytes: &[u8]) -> Result<(Pid, Message), SerializationError> {
+        let envelope: MessageEnvelope = self.codec.decode(bytes)?;
+        let payload = self.decode_payload(&envelope.manifest, &envelope.message_data)?;
+
+        let sender = match (envelope.sender, &self.remote_refs) {
+            (Some(sender), Some(refs)) => Some(refs.get(sender)),
+            _ => None,
+        };
+        let message = Message {
+            payload,
+            sender,
+            header: envelope.header.map(|h| Box::new(h) as Box<dyn Any + Send>),
+            priority: 0,
+        };
+        Ok((envelope.target, message))
     }
 
-    pub async fn serialize_batch(&self, messages: &[Message]) -> Result<Vec<u8>, SerializationError> {
+    // 代理引用保留原节点地址，本地 Actor 使用本节点地址
+    fn sender_pid(&self, sender: &ActorRef) -> Option<Pid> {
+        let address = sender.address().map(str::to_string).or_else(|| self.local_address.clone())?;
+        Some(Pid {
+            address,
+            id: sender.id().to_string(),
+        })
+    }
+
+    pub async fn deserialize(&self, bytes: &[u8]) -> Result<Message, SerializationError> {
+        self.deserialize_envelope(bytes).await.map(|(_, message)| message)
+    }
+
+    pub async fn serialize_batch(&self, messages: &[(Pid, Message)]) -> Result<Vec<u8>, SerializationError> {
+        self.encode_batch(messages)
+    }
+
+    pub fn encode_batch(&self, messages: &[(Pid, Message)]) -> Result<Vec<u8>, SerializationError> {
         let mut batch_data = Vec::with_capacity(messages.len() * 100); // 估计大小
-        
+
         // 写入消息数量
         let count = messages.len() as u32;
         batch_data.extend_from_slice(&count.to_be_bytes());
 
         // 序列化每条消息
-        for message in messages {
-            let message_data = self.serialize(message, &message.target).await?;
+        for (target, message) in messages {
+            let message_data = self.encode_message(message, target)?;
             let len = message_data.len() as u32;
             batch_data.extend_from_slice(&len.to_be_bytes());
             batch_data.extend_from_slice(&message_data);
@@ -67,26 +391,326 @@ impl MessageSerializer {
         Ok(batch_data)
     }
 
-    pub async fn deserialize_batch(&self, data: &[u8]) -> Result<Vec<Message>, SerializationError> {
+    /// 按批内顺序解码消息及其目标
+    pub async fn deserialize_batch(&self, data: &[u8]) -> Result<Vec<(Pid, Message)>, SerializationError> {
         let mut messages = Vec::new();
         let mut offset = 0;
 
         // 读取消息数量
-        let count = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap());
-        offset += 4;
+        let count = read_u32(data, &mut offset)?;
 
         // 反序列化每条消息
         for _ in 0..count {
-            let len = u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
-            offset += 4;
-            
-            let message_data = &data[offset..offset + len];
+            let len = read_u32(data, &mut offset)? as usize;
+            let message_data = data
+                .get(offset..offset + len)
+                .ok_or_else(|| SerializationError::DecodingError("truncated batch".to_string()))?;
             offset += len;
-            
-            let message = self.deserialize(message_data).await?;
-            messages.push(message);
+
+            messages.push(self.deserialize_envelope(message_data).await?);
         }
 
         Ok(messages)
     }
-} 
\ No newline at end of file
+}
+
+/// 不带目标的消息，持久化邮箱以此格式落盘
+#[derive(Serialize, Deserialize)]
+struct StoredMessage {
+    manifest: String,
+    message_data: Vec<u8>,
+    header: Option<MessageHeader>,
+    priority: u8,
+}
+
+/// 落盘格式固定使用的编码，不随连接协商的格式变化
+const STORED_CODEC: WireCodec = WireCodec::Bincode;
+
+/// 持久化邮箱使用的序列化器，与 `MessageSerializer` 共享载荷类型注册表
+///
+/// 消息不带目标落盘；发送方引用无法在重启后还原，不会写入。
+/// 信封和未指定格式的载荷总是以 `STORED_CODEC` 编码，日志可被任意配置的节点重放。
+#[derive(Clone)]
+pub struct StoredMessageSerializer {
+    inner: MessageSerializer,
+}
+
+impl StoredMessageSerializer {
+    pub fn new(inner: MessageSerializer) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl Serializer for StoredMessageSerializer {
+    fn serialize(&self, message: &Message) -> Result<Vec<u8>, SerializationError> {
+        let (manifest, message_data) = self.inner.encode_payload_with(message, STORED_CODEC)?;
+        STORED_CODEC.encode(&StoredMessage {
+            manifest,
+            message_data,
+            header: message.message_header().cloned(),
+            priority: message.priority,
+        })
+    }
+
+    async fn deserialize(&self, bytes: &[u8]) -> Result<Message, SerializationError> {
+        let stored: StoredMessage = STORED_CODEC.decode(bytes)?;
+        Ok(Message {
+            payload: self.inner.decode_payload_with(&stored.manifest, &stored.message_data, STORED_CODEC)?,
+            sender: None,
+            header: stored.header.map(|h| Box::new(h) as Box<dyn Any + Send>),
+            priority: stored.priority,
+        })
+    }
+}
+
+fn read_u32(data: &[u8], offset: &mut usize) -> Result<u32, SerializationError> {
+    let bytes = data
+        .get(*offset..*offset + 4)
+        .ok_or_else(|| SerializationError::DecodingError("truncated batch".to_string()))?;
+    *offset += 4;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::cluster::membership::{Member, MemberStatus};
+    use crate::cluster::messages::{ClusterMessage, GossipState};
+    use crate::cluster::partition::Partition;
+    use crate::remote::SystemMessage;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct PlaceOrder {
+        id: u64,
+        item: String,
+    }
+
+    fn target() -> Pid {
+        Pid {
+            address: "remote:9000".to_string(),
+            id: "orders".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_by_manifest() {
+        let serializer = MessageSerializer::new();
+        serializer.register::<PlaceOrder, _>("orders.PlaceOrder", JsonCodec);
+        serializer.register::<u64, _>("builtin.u64", BincodeCodec);
+
+        let message = Message::new(PlaceOrder { id: 7, item: "book".to_string() }).with_hash_key("7");
+        assert_eq!(serializer.manifest_of(&message).as_deref(), Some("orders.PlaceOrder"));
+
+        let bytes = serializer.serialize(&message, &target()).await.unwrap();
+        let (to, decoded) = serializer.deserialize_envelope(&bytes).await.unwrap();
+        assert_eq!(to.id, "orders");
+        assert_eq!(decoded.hash_key(), Some("7"));
+        assert_eq!(
+            decoded.payload.downcast_ref::<PlaceOrder>(),
+            Some(&PlaceOrder { id: 7, item: "book".to_string() })
+        );
+
+        let batch = serializer
+            .serialize_batch(&[(target(), Message::new(1u64)), (target(), Message::new(2u64))])
+            .await
+            .unwrap();
+        let decoded = serializer.deserialize_batch(&batch).await.unwrap();
+        let values: Vec<_> = decoded.iter().filter_map(|(_, m)| m.payload.downcast_ref::<u64>()).collect();
+        assert_eq!(values, vec![&1, &2]);
+    }
+
+    #[tokio::test]
+    async fn test_envelope_uses_negotiated_codec() {
+        for codec in WireCodec::ALL {
+            let serializer = MessageSerializer::new().with_codec(codec);
+            serializer.register_serde::<PlaceOrder>("orders.PlaceOrder");
+
+            let message = Message::new(PlaceOrder { id: 1, item: "pen".to_string() });
+            let bytes = serializer.serialize(&message, &target()).await.unwrap();
+            let decoded = serializer.deserialize(&bytes).await.unwrap();
+            assert_eq!(decoded.payload.downcast_ref::<PlaceOrder>().map(|o| o.id), Some(1));
+        }
+
+        // JSON peers can read the envelope directly
+        let serializer = MessageSerializer::new().with_codec(WireCodec::Json);
+        serializer.register_serde::<PlaceOrder>("orders.PlaceOrder");
+        let bytes = serializer.serialize(&Message::new(PlaceOrder { id: 2, item: "ink".to_string() }), &target()).await.unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(value["manifest"], "orders.PlaceOrder");
+    }
+
+    fn random_pid(rng: &mut StdRng) -> Pid {
+        Pid {
+            address: format!("node-{}:{}", rng.gen_range(0..10), rng.gen_range(1000..9999)),
+            id: format!("actor-{}", rng.gen::<u32>()),
+        }
+    }
+
+    fn random_member(rng: &mut StdRng) -> Member {
+        let status = match rng.gen_range(0..3) {
+            0 => MemberStatus::Alive,
+            1 => MemberStatus::Suspect,
+            _ => MemberStatus::Dead,
+        };
+        // A single label keeps the Debug output independent of HashMap ordering
+        let labels = (0..rng.gen_range(0..2))
+            .map(|_| ("version".to_string(), format!("v{}", rng.gen_range(0..5))))
+            .collect();
+        Member {
+            id: format!("member-{}", rng.gen::<u16>()),
+            host: "127.0.0.1".to_string(),
+            port: rng.gen(),
+            status,
+            labels,
+        }
+    }
+
+    fn random_partition(rng: &mut StdRng) -> Partition {
+        Partition {
+            id: format!("partition-{}", rng.gen_range(0..64)),
+            owner: format!("member-{}", rng.gen::<u16>()),
+            replicas: (0..rng.gen_range(0..3)).map(|i| format!("replica-{}", i)).collect(),
+            actors: (0..rng.gen_range(0..2)).map(|_| ("actor".to_string(), random_pid(rng))).collect(),
+        }
+    }
+
+    fn random_cluster_message(rng: &mut StdRng) -> ClusterMessage {
+        match rng.gen_range(0..13) {
+            0 => ClusterMessage::Join { member: random_member(rng) },
+            1 => ClusterMessage::Leave { member_id: format!("member-{}", rng.gen::<u16>()) },
+            2 => ClusterMessage::Heartbeat { from: random_member(rng), incarnation: rng.gen() },
+            3 => ClusterMessage::PartitionOwnershipRequest {
+                partition_id: format!("partition-{}", rng.gen::<u8>()),
+                member_id: format!("member-{}", rng.gen::<u16>()),
+            },
+            4 => ClusterMessage::PartitionOwnershipResponse {
+                partition_id: format!("partition-{}", rng.gen::<u8>()),
+                owner: format!("member-{}", rng.gen::<u16>()),
+                replicas: vec!["a".to_string(), "b".to_string()],
+            },
+            5 => ClusterMessage::PartitionRebalance {
+                partitions: (0..rng.gen_range(0..3)).map(|_| random_partition(rng)).collect(),
+            },
+            6 => ClusterMessage::GossipState {
+                members: (0..rng.gen_range(0..3)).map(|_| random_member(rng)).collect(),
+                partitions: (0..rng.gen_range(0..3)).map(|_| random_partition(rng)).collect(),
+                incarnation: rng.gen(),
+            },
+            7 => ClusterMessage::GossipSync {
+                from: random_member(rng),
+                to: random_member(rng),
+                state: Box::new(GossipState {
+                    members: vec![random_member(rng)],
+                    partitions: vec![random_partition(rng)],
+                    incarnation: rng.gen(),
+                }),
+            },
+            8 => ClusterMessage::DiscoveryPing { from: random_member(rng) },
+            9 => ClusterMessage::PrepareMigration {
+                partition_id: format!("partition-{}", rng.gen::<u8>()),
+                to: format!("member-{}", rng.gen::<u16>()),
+            },
+            10 => ClusterMessage::CopyPartitionData {
+                partition_id: format!("partition-{}", rng.gen::<u8>()),
+                from: format!("member-{}", rng.gen::<u16>()),
+            },
+            11 => ClusterMessage::SwitchPartitionOwnership {
+                partition_id: format!("partition-{}", rng.gen::<u8>()),
+                from: format!("member-{}", rng.gen::<u16>()),
+                to: format!("member-{}", rng.gen::<u16>()),
+            },
+            _ => ClusterMessage::DiscoveryPong {
+                from: random_member(rng),
+                known_members: (0..rng.gen_range(0..3)).map(|_| random_member(rng)).collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_system_messages_round_trip_through_every_codec() {
+        let mut rng = StdRng::seed_from_u64(43);
+        for _ in 0..200 {
+            let message = match rng.gen_range(0..4) {
+                0 => SystemMessage::Watch(random_pid(&mut rng), random_pid(&mut rng)),
+                1 => SystemMessage::Unwatch(random_pid(&mut rng), random_pid(&mut rng)),
+                2 => SystemMessage::Terminated {
+                    who: random_pid(&mut rng),
+                    address_terminated: rng.gen(),
+                },
+                _ => SystemMessage::Stop(random_pid(&mut rng)),
+            };
+            for codec in WireCodec::ALL {
+                let bytes = codec.encode(&message).unwrap();
+                assert_eq!(codec.decode::<SystemMessage>(&bytes).unwrap(), message, "{:?}", codec);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cluster_messages_round_trip_through_every_codec() {
+        let mut rng = StdRng::seed_from_u64(43);
+        for _ in 0..200 {
+            let message = random_cluster_message(&mut rng);
+            for codec in WireCodec::ALL {
+                let bytes = codec.encode(&message).unwrap();
+                let decoded = codec.decode::<ClusterMessage>(&bytes).unwrap();
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", message), "{:?}", codec);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_registration() {
+        let serializer = MessageSerializer::new();
+        let result = serializer.serialize(&Message::new(1u32), &target()).await;
+        assert!(matches!(result, Err(SerializationError::NoSerializerFound(name)) if name == "u32"));
+
+        let result = serializer.decode_payload("orders.PlaceOrder", &[]);
+        assert!(matches!(result, Err(SerializationError::NoSerializerFound(m)) if m == "orders.PlaceOrder"));
+    }
+
+    #[tokio::test]
+    async fn test_sender_round_trips_as_remote_ref() {
+        let (outbound, _sent) = tokio::sync::mpsc::unbounded_channel();
+        let sending = MessageSerializer::new().with_local_address("node-a:9000");
+        sending.register::<PlaceOrder, _>("orders.PlaceOrder", JsonCodec);
+        let receiving = sending.clone().with_remote_refs(RemoteRefs::new(outbound));
+
+        let (reply_to, _mailbox) = tokio::sync::mpsc::channel(1);
+        let order = PlaceOrder { id: 1, item: "book".to_string() };
+        let message = Message::with_sender(order, ActorRef::new("client".to_string(), reply_to));
+        let bytes = sending.serialize(&message, &target()).await.unwrap();
+
+        let (_, message) = receiving.deserialize_envelope(&bytes).await.unwrap();
+        let sender = message.sender.expect("sender dropped");
+        assert_eq!(sender.id(), "client");
+        assert_eq!(sender.address(), Some("node-a:9000"));
+    }
+
+    #[tokio::test]
+    async fn test_watch_messages_need_no_registration() {
+        let serializer = MessageSerializer::new();
+        let watch = SystemMessage::Watch(target(), target());
+        let bytes = serializer.serialize(&Message::new(watch.clone()), &target()).await.unwrap();
+
+        let (_, message) = serializer.deserialize_envelope(&bytes).await.unwrap();
+        assert_eq!(message.payload.downcast_ref::<SystemMessage>(), Some(&watch));
+    }
+
+    #[tokio::test]
+    async fn test_stored_format_ignores_connection_codec() {
+        let registry = MessageSerializer::new();
+        registry.register_serde::<PlaceOrder>("orders.PlaceOrder");
+
+        // Written by a serializer whose connection negotiated JSON, replayed by one using MessagePack
+        let writing = StoredMessageSerializer::new(registry.clone().with_codec(WireCodec::Json));
+        let reading = StoredMessageSerializer::new(registry.with_codec(WireCodec::MessagePack));
+        let bytes = writing.serialize(&Message::new(PlaceOrder { id: 3, item: "cup".to_string() })).unwrap();
+
+        let message = reading.deserialize(&bytes).await.unwrap();
+        assert_eq!(message.payload.downcast_ref::<PlaceOrder>().map(|o| o.id), Some(3));
+    }
+}
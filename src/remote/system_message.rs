@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use crate::process::Pid;
+
+/// 节点之间传输的系统消息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SystemMessage {
+    /// `watcher` 开始监视 `target`
+    Watch(Pid, Pid),
+    /// `watcher` 停止监视 `target`
+    Unwatch(Pid, Pid),
+    /// 被监视的 Actor 已终止，`address_terminated` 表示因所在节点失联而判定终止
+    Terminated { who: Pid, address_terminated: bool },
+    /// 停止目标 Actor
+    Stop(Pid),
+}
@@ -1,117 +1,309 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
 use async_trait::async_trait;
+use parking_lot::RwLock;
 use tokio::net::TcpStream;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use crate::{Message, Pid, SendError, RemoteError, Connection, MessageSerializer};
-use crate::remote::{MessageCompressor, FlowController};
-use crate::remote::batch::{BatchManager, BatchConfig};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::Mutex;
+use crate::{Message, Pid, SendError};
+use crate::dispatcher::BackpressureController;
+use crate::remote::{CompressorRegistry, FrameCompression, DEFAULT_MIN_COMPRESS_SIZE};
+use crate::remote::{CreditGrant, CreditGranter, FlowControlConfig, SendCredits};
+use crate::remote::protocol::{
+    client_handshake, read_frame, server_handshake, write_frame, Frame, FrameFlags, FrameType, Handshake,
+    Negotiated, DEFAULT_MAX_FRAME_SIZE,
+};
+use crate::remote::{HeartbeatManager, HeartbeatMessage, ProtocolError, WireCodec};
+use crate::remote::{Connection, MessageSerializer, RemoteError};
+use super::transport::InboundSender;
 
-pub struct TcpConnection {
-    stream: TcpStream,
-    serializer: MessageSerializer,
-    compressor: Box<dyn MessageCompressor>,
-    flow_controller: FlowController,
-    batch_manager: BatchManager,
+/// 新建连接使用的设置
+#[derive(Clone)]
+pub struct ConnectionSettings {
+    /// 与传输共享的载荷类型注册表，编码在握手后按协商结果设置
+    pub serializer: MessageSerializer,
+    /// 握手中声明的压缩算法
+    pub compressors: Arc<CompressorRegistry>,
+    /// 小于该长度的帧不压缩
+    pub min_compress_size: usize,
+    pub flow_control: FlowControlConfig,
+    /// 本地邮箱负载，决定回授多少信用；未设置时总是全额回授
+    pub backpressure: Option<Arc<BackpressureController>>,
+    pub max_frame_size: usize,
 }
 
-impl TcpConnection {
-    pub fn new(
-        stream: TcpStream,
-        compressor: Box<dyn MessageCompressor>,
-        messages_per_sec: usize,
-        bytes_per_sec: usize,
-        batch_config: BatchConfig,
-    ) -> Self {
+impl Default for ConnectionSettings {
+    fn default() -> Self {
         Self {
-            stream,
             serializer: MessageSerializer::new(),
-            compressor,
-            flow_controller: FlowController::new(messages_per_sec, bytes_per_sec),
-            batch_manager: BatchManager::new(batch_config),
+            compressors: Arc::new(CompressorRegistry::default()),
+            min_compress_size: DEFAULT_MIN_COMPRESS_SIZE,
+            flow_control: FlowControlConfig::default(),
+            backpressure: None,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
         }
     }
+}
 
-    pub async fn handle(&mut self) {
-        while let Ok(message) = self.receive().await {
-            // 处理接收到的消息
-            if let Err(e) = self.handle_message(message).await {
-                log::error!("Error handling message: {:?}", e);
-                break;
-            }
-        }
+// 对端地址及记录 Pong 的心跳管理器，连接加入连接池后才设置
+type HeartbeatSlot = Arc<RwLock<Option<(String, Arc<HeartbeatManager>)>>>;
+
+/// 握手完成的 TCP 连接的发送方
+///
+/// 读写分离：写半部由发送方和读取任务共享，读取任务处理对端发来的全部帧，
+/// 包括信用帧和心跳帧，因此只发送不接收消息的连接也能收到回授的信用。
+pub struct TcpConnection {
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    serializer: MessageSerializer,
+    compression: Option<Arc<FrameCompression>>,
+    credits: Arc<SendCredits>,
+    heartbeat: HeartbeatSlot,
+    negotiated: Negotiated,
+}
+
+impl TcpConnection {
+    /// 交换握手信息并拆分读写，`initiator` 表示本端发起了连接
+    ///
+    /// 声明的压缩算法取自 `settings.compressors`。返回的读取方由调用方运行读取任务。
+    pub async fn handshake(
+        mut stream: TcpStream,
+        local: &Handshake,
+        initiator: bool,
+        settings: &ConnectionSettings,
+    ) -> Result<(Self, FrameReader), RemoteError> {
+        let local = local.clone().with_compressors(settings.compressors.names());
+        let negotiated = if initiator {
+            client_handshake(&mut stream, &local).await?
+        } else {
+            server_handshake(&mut stream, &local).await?
+        };
+        log::debug!(
+            "Handshake with {} completed using {} / {}",
+            negotiated.remote_address, negotiated.serializer, negotiated.compressor
+        );
+
+        let codec = WireCodec::from_name(&negotiated.serializer).ok_or_else(|| {
+            RemoteError::HandshakeError(format!("unsupported serializer {}", negotiated.serializer))
+        })?;
+        let serializer = settings.serializer.clone()
+            .with_codec(codec)
+            .with_local_address(local.address.clone());
+        let compression = settings.compressors
+            .get(&negotiated.compressor)?
+            .map(|compressor| Arc::new(FrameCompression::new(compressor, settings.min_compress_size)));
+        let credits = Arc::new(SendCredits::new(&settings.flow_control, &negotiated.remote_address));
+        let heartbeat: HeartbeatSlot = Arc::new(RwLock::new(None));
+
+        let (read_half, write_half) = stream.into_split();
+        let writer = Arc::new(Mutex::new(write_half));
+        let reader = FrameReader {
+            half: read_half,
+            writer: Arc::clone(&writer),
+            serializer: serializer.clone(),
+            compression: compression.clone(),
+            credits: Arc::clone(&credits),
+            granter: CreditGranter::new(settings.flow_control.clone()),
+            backpressure: settings.backpressure.clone(),
+            heartbeat: Arc::clone(&heartbeat),
+            max_frame_size: settings.max_frame_size,
+            pending: VecDeque::new(),
+        };
+
+        let connection = Self {
+            writer,
+            serializer,
+            compression,
+            credits,
+            heartbeat,
+            negotiated,
+        };
+        Ok((connection, reader))
     }
 
-    async fn handle_message(&mut self, message: Message) -> Result<(), RemoteError> {
-        // 将消息转发给本地 actor 系统
-        Ok(())
+    pub fn negotiated(&self) -> &Negotiated {
+        &self.negotiated
     }
 
-    async fn read_message(&mut self) -> Result<Message, RemoteError> {
-        // 读取压缩数据的长度
-        let mut len_bytes = [0u8; 4];
-        self.stream.read_exact(&mut len_bytes).await?;
-        let len = u32::from_be_bytes(len_bytes) as usize;
+    async fn write_frame(&self, frame_type: FrameType, flags: FrameFlags, data: &[u8]) -> Result<(), RemoteError> {
+        // 压缩数据，过小或压缩无收益的帧原样发送
+        let compressed = match &self.compression {
+            Some(compression) => compression.compress(data).await?,
+            None => None,
+        };
+        let frame = match compressed {
+            Some(compressed) => Frame::new(frame_type, compressed).with_flags(flags.with(FrameFlags::COMPRESSED)),
+            None => Frame::new(frame_type, data.to_vec()).with_flags(flags),
+        };
+        write_frame(&mut *self.writer.lock().await, &frame).await?;
 
-        // 读取压缩数据
-        let mut buffer = vec![0u8; len];
-        self.stream.read_exact(&mut buffer).await?;
+        // 消息信用已在发送前扣除，字节信用按实际写出的长度扣除
+        self.credits.consume_bytes(frame.payload.len());
+        Ok(())
+    }
+}
 
-        // 解压数据
-        let decompressed = self.compressor.decompress(&buffer).await?;
+#[async_trait]
+impl Connection for TcpConnection {
+    async fn send(&self, target: &Pid, message: Message) -> Result<(), SendError> {
+        let data = self.serializer.encode_message(&message, target).map_err(|e| {
+            log::warn!("Failed to encode message for {}: {:?}", target.address, e);
+            SendError::ConnectionFailed
+        })?;
+        self.write_frame(FrameType::User, FrameFlags::empty(), &data).await.map_err(|e| {
+            log::warn!("Failed to write message to {}: {:?}", target.address, e);
+            SendError::ConnectionFailed
+        })
+    }
 
-        // 反序列化消息
-        self.serializer.deserialize(&decompressed).await
+    async fn send_batch(&self, messages: Vec<(Pid, Message)>) -> Result<(), SendError> {
+        // 批量合并由端点负责，这里只写出一个批量帧
+        let data = self.serializer.encode_batch(&messages).map_err(|e| {
+            log::warn!("Failed to encode batch of {} messages: {:?}", messages.len(), e);
+            SendError::ConnectionFailed
+        })?;
+        self.write_frame(FrameType::Batch, FrameFlags::BATCHED, &data).await.map_err(|e| {
+            log::warn!("Failed to write batch of {} messages: {:?}", messages.len(), e);
+            SendError::ConnectionFailed
+        })
     }
 
-    async fn write_message(&mut self, data: &[u8]) -> Result<(), RemoteError> {
-        // 检查流量限制
-        if !self.flow_controller.can_send(data.len()).await {
-            return Err(RemoteError::RateLimitExceeded);
-        }
+    fn credits(&self) -> Option<Arc<SendCredits>> {
+        Some(Arc::clone(&self.credits))
+    }
 
-        // 压缩数据
-        let compressed = self.compressor.compress(data).await?;
+    fn attach_heartbeat(&self, address: &str, manager: Arc<HeartbeatManager>) {
+        *self.heartbeat.write() = Some((address.to_string(), manager));
+    }
 
-        // 写入压缩后的长度
-        let len = compressed.len() as u32;
-        self.stream.write_all(&len.to_be_bytes()).await?;
+    async fn ping(&self) -> Result<(), RemoteError> {
+        let ping = match &*self.heartbeat.read() {
+            Some((address, manager)) => manager.ping(address),
+            None => HeartbeatMessage::Ping { sequence: 0 },
+        };
+        write_control(&self.writer, FrameType::Heartbeat, ping.encode()?).await
+    }
+}
 
-        // 写入压缩后的数据
-        self.stream.write_all(&compressed).await?;
-        self.stream.flush().await?;
+/// 连接的读取方，处理对端发来的消息、信用和心跳帧
+pub struct FrameReader {
+    half: OwnedReadHalf,
+    // 回复 Pong 与回授信用时使用
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    serializer: MessageSerializer,
+    compression: Option<Arc<FrameCompression>>,
+    credits: Arc<SendCredits>,
+    granter: CreditGranter,
+    backpressure: Option<Arc<BackpressureController>>,
+    heartbeat: HeartbeatSlot,
+    max_frame_size: usize,
+    // 批量帧中尚未交付的消息
+    pending: VecDeque<(Pid, Message)>,
+}
 
-        Ok(())
+impl FrameReader {
+    /// 读取帧直到连接关闭，消息转交给 `inbound`
+    pub async fn run(mut self, inbound: Option<InboundSender>) {
+        loop {
+            match self.receive().await {
+                Ok((target, message)) => match &inbound {
+                    Some(inbound) => {
+                        if inbound.send((target, message)).is_err() {
+                            break;
+                        }
+                    }
+                    None => log::debug!("Dropping remote message for {}", target.id),
+                },
+                Err(e) => {
+                    log::debug!("Connection closed: {:?}", e);
+                    break;
+                }
+            }
+        }
     }
 
-    async fn write_batch(&mut self, messages: &[Message]) -> Result<(), RemoteError> {
-        // 序列化整个批次
-        let batch_data = self.serializer.serialize_batch(messages).await?;
+    /// 读取下一条消息，期间处理信用帧和心跳帧
+    pub async fn receive(&mut self) -> Result<(Pid, Message), RemoteError> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Ok(message);
+            }
 
-        // 检查流量限制
-        if !self.flow_controller.can_send(batch_data.len()).await {
-            return Err(RemoteError::RateLimitExceeded);
-        }
+            let frame = read_frame(&mut self.half, self.max_frame_size).await?;
+            if frame.frame_type == FrameType::Handshake {
+                return Err(ProtocolError::UnexpectedFrame(frame.frame_type).into());
+            }
 
-        // 压缩数据
-        let compressed = self.compressor.compress(&batch_data).await?;
+            // 信用按线上长度计算，与发送方扣除的字节数一致
+            let wire_len = frame.payload.len();
 
-        // 写入压缩后的长度和数据
-        let len = compressed.len() as u32;
-        self.stream.write_all(&len.to_be_bytes()).await?;
-        self.stream.write_all(&compressed).await?;
-        self.stream.flush().await?;
+            // 解压数据
+            let payload = if frame.flags.contains(FrameFlags::COMPRESSED) {
+                match &self.compression {
+                    Some(compression) => compression.decompress(&frame.payload, self.max_frame_size).await?,
+                    None => return Err(ProtocolError::CompressionNotNegotiated.into()),
+                }
+            } else {
+                frame.payload
+            };
 
-        Ok(())
+            match frame.frame_type {
+                FrameType::User | FrameType::System => {
+                    let message = self.serializer.deserialize_envelope(&payload).await?;
+                    self.granter.record(1, wire_len);
+                    self.grant_credits().await?;
+                    return Ok(message);
+                }
+                FrameType::Batch => {
+                    // 按批内顺序交付，保持同一发送方的消息顺序
+                    let messages = self.serializer.deserialize_batch(&payload).await?;
+                    self.granter.record(messages.len() as u32, wire_len);
+                    self.pending.extend(messages);
+                    self.grant_credits().await?;
+                }
+                FrameType::Heartbeat => {
+                    self.handle_heartbeat(&payload).await?;
+                    // 发送方停顿时没有新消息到达，借心跳补发压力下降后的信用
+                    self.grant_credits().await?;
+                }
+                FrameType::Credit => {
+                    self.credits.grant(CreditGrant::decode(&payload)?);
+                }
+                FrameType::Handshake => unreachable!(),
+            }
+        }
     }
-}
 
-#[async_trait]
-impl Connection for TcpConnection {
-    async fn send(&mut self, target: &Pid, message: Message) -> Result<(), SendError> {
-        // 添加到批处理管理器
-        self.batch_manager.add_message(target, message).await
+    async fn handle_heartbeat(&mut self, payload: &[u8]) -> Result<(), RemoteError> {
+        match HeartbeatMessage::decode(payload)? {
+            HeartbeatMessage::Ping { sequence } => {
+                let pong = HeartbeatMessage::Pong { sequence };
+                write_control(&self.writer, FrameType::Heartbeat, pong.encode()?).await
+            }
+            HeartbeatMessage::Pong { sequence } => {
+                if let Some((address, manager)) = &*self.heartbeat.read() {
+                    if let Some(rtt) = manager.record_pong(address, sequence) {
+                        log::trace!("Heartbeat RTT to {}: {:?}", address, rtt);
+                    }
+                }
+                Ok(())
+            }
+        }
     }
 
-    async fn receive(&mut self) -> Result<Message, RemoteError> {
-        self.read_message().await
+    async fn grant_credits(&mut self) -> Result<(), RemoteError> {
+        let load = self.backpressure.as_ref().map_or(0.0, |bp| bp.current_load());
+        if let Some(grant) = self.granter.poll(load) {
+            write_control(&self.writer, FrameType::Credit, grant.encode()?).await?;
+        }
+        Ok(())
     }
-} 
\ No newline at end of file
+}
+
+// 心跳帧和信用帧不经过流量控制和压缩，避免繁忙时被误判为失联
+async fn write_control(
+    writer: &Mutex<OwnedWriteHalf>,
+    frame_type: FrameType,
+    payload: Vec<u8>,
+) -> Result<(), RemoteError> {
+    write_frame(&mut *writer.lock().await, &Frame::new(frame_type, payload)).await
+}
@@ -1,6 +1,17 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
 use async_trait::async_trait;
 use tokio::net::{TcpListener, TcpStream};
-use crate::{Message, Pid, SendError, RemoteConfig};
+use tokio::sync::mpsc;
+use crate::{Message, Pid, SendError};
+use crate::dispatcher::BackpressureController;
+use super::{
+    CompressorRegistry, ConnectionSettings, FlowControlConfig, Handshake, HeartbeatManager, MessageSerializer, RemoteConfig, RemoteError,
+    SendCredits, TcpConnection, WireCodec,
+};
+
+/// 接收到的远程消息及其目标
+pub type InboundSender = mpsc::UnboundedSender<(Pid, Message)>;
 
 #[async_trait]
 pub trait Transport: Send + Sync {
@@ -8,44 +19,305 @@ pub trait Transport: Send + Sync {
     async fn connect(&self, address: &str) -> Result<Box<dyn Connection>, SendError>;
 }
 
+/// 连接的发送方，对端发来的帧由传输启动的读取任务处理
 #[async_trait]
 pub trait Connection: Send + Sync {
-    async fn send(&mut self, target: &Pid, message: Message) -> Result<(), SendError>;
-    async fn receive(&mut self) -> Result<Message, RemoteError>;
+    async fn send(&self, target: &Pid, message: Message) -> Result<(), SendError>;
+    /// 以一个批量帧发送多条消息，接收方按原顺序交付
+    async fn send_batch(&self, messages: Vec<(Pid, Message)>) -> Result<(), SendError>;
+    /// 发送心跳帧，用于连接池的健康检查
+    async fn ping(&self) -> Result<(), RemoteError>;
+    /// 发送方的流量控制信用，不做流量控制的连接返回 None
+    ///
+    /// 调用方需在发送之前等待信用，接收方的信用帧由该连接的读取任务处理。
+    fn credits(&self) -> Option<Arc<SendCredits>>;
+    /// 收到的 Pong 交给 `manager` 测量到 `address` 的 RTT，不回复 Pong 的连接可忽略
+    fn attach_heartbeat(&self, _address: &str, _manager: Arc<HeartbeatManager>) {}
 }
 
+/// 基于 TCP 的传输
+///
+/// 发起和接受的连接都先完成握手再交给调用方，握手中以 `address` 标识本节点。
+/// 每个连接都有一个读取任务，发起的连接上也会处理对端回授的信用和 Pong。
+#[derive(Clone)]
 pub struct TcpTransport {
-    listener: Option<TcpListener>,
+    address: String,
+    settings: ConnectionSettings,
+    inbound: Option<InboundSender>,
 }
 
 impl TcpTransport {
-    pub fn new() -> Self {
-        Self { listener: None }
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            settings: ConnectionSettings::default(),
+            inbound: None,
+        }
     }
-}
 
-#[async_trait]
-impl Transport for TcpTransport {
-    async fn start(&self, config: &RemoteConfig) -> Result<(), RemoteError> {
-        let addr = format!("{}:{}", config.host, config.port);
-        let listener = TcpListener::bind(&addr).await?;
-        
+    /// 按配置中的地址、压缩、流量控制和背压设置创建
+    pub fn from_config(config: &RemoteConfig) -> Self {
+        let mut transport = Self::new(format!("{}:{}", config.host, config.port))
+            .with_compressors(Arc::clone(&config.compressors))
+            .with_min_compress_size(config.min_compress_size)
+            .with_flow_control(config.flow_control.clone());
+        if let Some(backpressure) = &config.backpressure {
+            transport = transport.with_backpressure(Arc::clone(backpressure));
+        }
+        transport
+    }
+
+    /// 所有连接共享的载荷类型注册表
+    pub fn with_serializer(mut self, serializer: MessageSerializer) -> Self {
+        self.settings.serializer = serializer;
+        self
+    }
+
+    /// 握手中声明的压缩算法
+    pub fn with_compressors(mut self, compressors: Arc<CompressorRegistry>) -> Self {
+        self.settings.compressors = compressors;
+        self
+    }
+
+    /// 小于该长度的帧不压缩
+    pub fn with_min_compress_size(mut self, min_compress_size: usize) -> Self {
+        self.settings.min_compress_size = min_compress_size;
+        self
+    }
+
+    pub fn with_flow_control(mut self, flow_control: FlowControlConfig) -> Self {
+        self.settings.flow_control = flow_control;
+        self
+    }
+
+    /// 按本地邮箱负载回授信用，负载越高发送方可用的窗口越小
+    pub fn with_backpressure(mut self, backpressure: Arc<BackpressureController>) -> Self {
+        self.settings.backpressure = Some(backpressure);
+        self
+    }
+
+    /// 收到的消息转交给 `inbound`，未设置时丢弃
+    pub fn with_inbound(mut self, inbound: InboundSender) -> Self {
+        self.inbound = Some(inbound);
+        self
+    }
+
+    /// 监听 `address` 并处理接受的连接，返回实际监听的地址
+    pub async fn bind(&self, address: &str) -> Result<SocketAddr, RemoteError> {
+        let listener = TcpListener::bind(address).await?;
+        let local_addr = listener.local_addr()?;
+        let transport = self.clone();
+
         tokio::spawn(async move {
-            while let Ok((stream, _)) = listener.accept().await {
-                let connection = TcpConnection::new(stream);
-                // Handle incoming connection
+            while let Ok((stream, peer)) = listener.accept().await {
+                let transport = transport.clone();
                 tokio::spawn(async move {
-                    connection.handle().await;
+                    let local = transport.handshake();
+                    match TcpConnection::handshake(stream, &local, false, &transport.settings).await {
+                        // 写半部由读取任务持有，用于回复 Pong 和回授信用
+                        Ok((_, reader)) => reader.run(transport.inbound.clone()).await,
+                        Err(e) => log::warn!("Handshake with {} failed: {:?}", peer, e),
+                    }
                 });
             }
         });
 
+        Ok(local_addr)
+    }
+
+    fn handshake(&self) -> Handshake {
+        Handshake::new(self.address.clone()).with_serializers(WireCodec::names())
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn start(&self, config: &RemoteConfig) -> Result<(), RemoteError> {
+        self.bind(&format!("{}:{}", config.host, config.port)).await?;
         Ok(())
     }
 
     async fn connect(&self, address: &str) -> Result<Box<dyn Connection>, SendError> {
         let stream = TcpStream::connect(address).await
             .map_err(|_| SendError::ConnectionFailed)?;
-        Ok(Box::new(TcpConnection::new(stream)))
+        let (connection, reader) = TcpConnection::handshake(stream, &self.handshake(), true, &self.settings)
+            .await
+            .map_err(|e| {
+                log::warn!("Handshake with {} failed: {:?}", address, e);
+                SendError::ConnectionFailed
+            })?;
+        tokio::spawn(reader.run(self.inbound.clone()));
+        Ok(Box::new(connection))
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+    use crate::dispatcher::BackpressureConfig;
+    use crate::remote::{ConnectionPool, GzipCompressor, PhiAccrualConfig};
+
+    fn serializer() -> MessageSerializer {
+        let serializer = MessageSerializer::new();
+        serializer.register_serde::<String>("test.String");
+        serializer
+    }
+
+    #[tokio::test]
+    async fn test_message_over_loopback() {
+        let (inbound, mut received) = mpsc::unbounded_channel();
+        let server = TcpTransport::new("node-a")
+            .with_serializer(serializer())
+            .with_inbound(inbound);
+        let address = server.bind("127.0.0.1:0").await.unwrap();
+
+        let client = TcpTransport::new("node-b").with_serializer(serializer());
+        let connection = client.connect(&address.to_string()).await.unwrap();
+        let target = Pid {
+            address: address.to_string(),
+            id: "echo".to_string(),
+        };
+        connection.send(&target, Message::new("hello".to_string())).await.unwrap();
+
+        let (to, message) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(to, target);
+        assert_eq!(message.payload.downcast_ref::<String>().map(String::as_str), Some("hello"));
+    }
+
+    #[tokio::test]
+    async fn test_large_message_is_compressed_over_loopback() {
+        let (inbound, mut received) = mpsc::unbounded_channel();
+        let server = TcpTransport::new("node-a")
+            .with_serializer(serializer())
+            .with_inbound(inbound);
+        let address = server.bind("127.0.0.1:0").await.unwrap();
+
+        let gzip_only = CompressorRegistry::new().register(Arc::new(GzipCompressor::default()));
+        let client = TcpTransport::new("node-b")
+            .with_serializer(serializer())
+            .with_compressors(Arc::new(gzip_only))
+            .with_min_compress_size(64);
+        let connection = client.connect(&address.to_string()).await.unwrap();
+        let target = Pid {
+            address: address.to_string(),
+            id: "echo".to_string(),
+        };
+        let payload = "compressible ".repeat(1024);
+        connection.send(&target, Message::new(payload.clone())).await.unwrap();
+
+        let (_, message) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.payload.downcast_ref::<String>(), Some(&payload));
+    }
+
+    fn small_window() -> FlowControlConfig {
+        FlowControlConfig {
+            message_window: 4,
+            ..FlowControlConfig::default()
+        }
+    }
+
+    fn pid(address: SocketAddr) -> Pid {
+        Pid {
+            address: address.to_string(),
+            id: "sink".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sender_continues_past_one_window() {
+        let (inbound, mut received) = mpsc::unbounded_channel();
+        let server = TcpTransport::new("node-a")
+            .with_serializer(serializer())
+            .with_flow_control(small_window())
+            .with_inbound(inbound);
+        let address = server.bind("127.0.0.1:0").await.unwrap();
+
+        let client = TcpTransport::new("node-b")
+            .with_serializer(serializer())
+            .with_flow_control(small_window());
+        let pool = ConnectionPool::new(1);
+        let connection = pool.add_connection(address.to_string(), client.connect(&address.to_string()).await.unwrap());
+
+        // 五倍窗口的消息只有在读取任务处理了回授的信用后才能发完
+        for i in 0..20 {
+            let message = Message::new(format!("message-{}", i));
+            tokio::time::timeout(Duration::from_secs(5), connection.send(&pid(address), message))
+                .await
+                .expect("sender stalled waiting for credits")
+                .unwrap();
+        }
+        for i in 0..20 {
+            let (_, message) = received.recv().await.unwrap();
+            assert_eq!(message.payload.downcast_ref::<String>(), Some(&format!("message-{}", i)));
+        }
+        assert_eq!(connection.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_grants_follow_receiver_mailbox_pressure() {
+        let backpressure = Arc::new(BackpressureController::new(BackpressureConfig {
+            max_queue_size: 1,
+            ..BackpressureConfig::default()
+        }));
+        let (inbound, _received) = mpsc::unbounded_channel();
+        let server = TcpTransport::new("node-a")
+            .with_serializer(serializer())
+            .with_flow_control(small_window())
+            .with_backpressure(Arc::clone(&backpressure))
+            .with_inbound(inbound);
+        let address = server.bind("127.0.0.1:0").await.unwrap();
+
+        let client = TcpTransport::new("node-b")
+            .with_serializer(serializer())
+            .with_flow_control(small_window());
+        let pool = ConnectionPool::new(1);
+        let connection = pool.add_connection(address.to_string(), client.connect(&address.to_string()).await.unwrap());
+
+        // 接收方邮箱已满，不回授信用，发送方用完窗口后停顿
+        backpressure.try_acquire();
+        for i in 0..4 {
+            connection.send(&pid(address), Message::new(format!("message-{}", i))).await.unwrap();
+        }
+        let stalled = tokio::time::timeout(
+            Duration::from_millis(200),
+            connection.send(&pid(address), Message::new("blocked".to_string())),
+        ).await;
+        assert!(stalled.is_err());
+
+        // 压力解除后，接收方借心跳补发信用
+        backpressure.release();
+        assert!(pool.check_health(&address.to_string()).await > 0);
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            connection.send(&pid(address), Message::new("resumed".to_string())),
+        ).await.expect("sender did not resume").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pong_updates_heartbeat_rtt() {
+        let server = TcpTransport::new("node-a").with_serializer(serializer());
+        let address = server.bind("127.0.0.1:0").await.unwrap().to_string();
+
+        let manager = Arc::new(HeartbeatManager::new(Duration::from_secs(1), PhiAccrualConfig::default()));
+        let pool = ConnectionPool::new(1).with_heartbeat(Arc::clone(&manager));
+        let client = TcpTransport::new("node-b").with_serializer(serializer());
+        pool.acquire(&address, &client).await.unwrap();
+
+        // 健康检查发出本轮 Ping，对端回复的 Pong 由读取任务记入心跳管理器
+        manager.begin_round(&address);
+        assert_eq!(pool.check_health(&address).await, 1);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while manager.rtt(&address).is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.expect("no pong recorded");
+        assert!(manager.is_available(&address));
+    }
+}
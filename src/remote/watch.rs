@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+use parking_lot::Mutex;
+use crate::process::Pid;
+
+/// 跨节点 DeathWatch 的监视关系
+///
+/// 同时记录两个方向：本地 Actor 监视的远程 Actor，以及远程 Actor 监视的本地 Actor。
+/// 节点失联时两个方向中与该节点相关的条目都会被清理。
+#[derive(Default)]
+pub struct RemoteWatchRegistry {
+    // 远程目标 -> 本地监视者
+    watching: Mutex<HashMap<Pid, HashSet<Pid>>>,
+    // 本地目标 -> 远程监视者
+    watched_by: Mutex<HashMap<Pid, HashSet<Pid>>>,
+}
+
+impl RemoteWatchRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 本地 `watcher` 开始监视远程 `target`，首次监视该目标时返回 true
+    pub fn watch(&self, watcher: Pid, target: Pid) -> bool {
+        let mut watching = self.watching.lock();
+        let watchers = watching.entry(target).or_default();
+        let first = watchers.is_empty();
+        watchers.insert(watcher);
+        first
+    }
+
+    /// 本地 `watcher` 停止监视远程 `target`，目标不再被任何本地 Actor 监视时返回 true
+    pub fn unwatch(&self, watcher: &Pid, target: &Pid) -> bool {
+        let mut watching = self.watching.lock();
+        match watching.get_mut(target) {
+            Some(watchers) => {
+                watchers.remove(watcher);
+                if watchers.is_empty() {
+                    watching.remove(target);
+                    return true;
+                }
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// 远程目标已终止，返回需要通知的本地监视者
+    pub fn remote_terminated(&self, target: &Pid) -> Vec<Pid> {
+        self.watching
+            .lock()
+            .remove(target)
+            .map(|watchers| watchers.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// 远程节点失联，返回该节点上被监视的目标及其本地监视者
+    ///
+    /// 同时移除该节点上的 Actor 对本地 Actor 的监视。
+    pub fn address_terminated(&self, address: &str) -> Vec<(Pid, Vec<Pid>)> {
+        for watchers in self.watched_by.lock().values_mut() {
+            watchers.retain(|watcher| watcher.address != address);
+        }
+        self.watched_by.lock().retain(|_, watchers| !watchers.is_empty());
+
+        let mut watching = self.watching.lock();
+        let targets: Vec<Pid> = watching.keys().filter(|t| t.address == address).cloned().collect();
+        targets
+            .into_iter()
+            .filter_map(|target| {
+                let watchers = watching.remove(&target)?;
+                Some((target, watchers.into_iter().collect()))
+            })
+            .collect()
+    }
+
+    /// 远程 `watcher` 开始监视本地 `target`
+    pub fn add_remote_watcher(&self, target: Pid, watcher: Pid) {
+        self.watched_by.lock().entry(target).or_default().insert(watcher);
+    }
+
+    /// 远程 `watcher` 停止监视本地 `target`
+    pub fn remove_remote_watcher(&self, target: &Pid, watcher: &Pid) {
+        let mut watched_by = self.watched_by.lock();
+        if let Some(watchers) = watched_by.get_mut(target) {
+            watchers.remove(watcher);
+            if watchers.is_empty() {
+                watched_by.remove(target);
+            }
+        }
+    }
+
+    /// 本地目标已终止，返回需要通知的远程监视者
+    pub fn local_terminated(&self, target: &Pid) -> Vec<Pid> {
+        self.watched_by
+            .lock()
+            .remove(target)
+            .map(|watchers| watchers.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn is_watching(&self, target: &Pid) -> bool {
+        self.watching.lock().contains_key(target)
+    }
+
+    pub fn is_watched(&self, target: &Pid) -> bool {
+        self.watched_by.lock().contains_key(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pid(address: &str, id: &str) -> Pid {
+        Pid {
+            address: address.to_string(),
+            id: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_terminated_notifies_each_watcher_once() {
+        let registry = RemoteWatchRegistry::new();
+        let target = pid("node-b", "worker");
+        assert!(registry.watch(pid("node-a", "w1"), target.clone()));
+        assert!(!registry.watch(pid("node-a", "w2"), target.clone()));
+        assert!(!registry.unwatch(&pid("node-a", "w2"), &target));
+
+        assert_eq!(registry.remote_terminated(&target), vec![pid("node-a", "w1")]);
+        assert!(registry.remote_terminated(&target).is_empty());
+    }
+
+    #[test]
+    fn test_address_terminated_cleans_both_directions() {
+        let registry = RemoteWatchRegistry::new();
+        registry.watch(pid("node-a", "w1"), pid("node-b", "worker"));
+        registry.watch(pid("node-a", "w1"), pid("node-c", "worker"));
+        registry.add_remote_watcher(pid("node-a", "local"), pid("node-b", "watcher"));
+
+        let lost = registry.address_terminated("node-b");
+        assert_eq!(lost, vec![(pid("node-b", "worker"), vec![pid("node-a", "w1")])]);
+        assert!(!registry.is_watched(&pid("node-a", "local")));
+        assert!(registry.is_watching(&pid("node-c", "worker")));
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+use tokio::sync::mpsc;
+use tokio::time::{timeout_at, Instant};
+use crate::actor::ActorRef;
+use crate::errors::SendError;
+use crate::message::Message;
+
+type PendingReply = BoxFuture<'static, (String, Option<Message>)>;
+
+/// 为一个 routee 创建临时回复地址，回复经由消息的 sender 返回
+fn reply_to(routee_id: &str) -> (ActorRef, mpsc::Receiver<Message>) {
+    let (sender, receiver) = mpsc::channel(1);
+    (ActorRef::new(format!("$ask/{}", routee_id), sender), receiver)
+}
+
+fn await_reply(routee_id: String, mut receiver: mpsc::Receiver<Message>) -> PendingReply {
+    async move {
+        let reply = receiver.recv().await;
+        (routee_id, reply)
+    }
+    .boxed()
+}
+
+/// 以 ask 方式发送给一个 routee，发送失败时不等待其回复
+async fn ask_routee(routee: &ActorRef, mut request: Message, pending: &mut FuturesUnordered<PendingReply>) -> bool {
+    let (reply_ref, receiver) = reply_to(routee.id());
+    request.sender = Some(reply_ref);
+
+    match routee.send(request).await {
+        Ok(()) => {
+            pending.push(await_reply(routee.id().to_string(), receiver));
+            true
+        }
+        Err(e) => {
+            log::warn!("Failed to ask routee {}: {:?}", routee.id(), e);
+            false
+        }
+    }
+}
+
+/// 等待最先到达的回复，返回回复的 routee 及回复消息
+async fn first_completed(
+    pending: &mut FuturesUnordered<PendingReply>,
+    deadline: Instant,
+) -> Result<(String, Message), SendError> {
+    loop {
+        match timeout_at(deadline, pending.next()).await {
+            Ok(Some((routee_id, Some(reply)))) => return Ok((routee_id, reply)),
+            // 该 routee 未回复就释放了回复地址
+            Ok(Some((_, None))) => continue,
+            Ok(None) => return Err(SendError::NoRoutee),
+            Err(_) => return Err(SendError::Timeout),
+        }
+    }
+}
+
+/// 发送给所有 routee，返回 `within` 内最先到达的回复
+pub(crate) async fn scatter_gather_first_completed(
+    routees: Vec<ActorRef>,
+    msg: Message,
+    within: Duration,
+) -> Result<(String, Message), SendError> {
+    if routees.is_empty() {
+        return Err(SendError::NoRoutee);
+    }
+
+    let deadline = Instant::now() + within;
+    let mut pending = FuturesUnordered::new();
+    for routee in &routees {
+        ask_routee(routee, msg.clone(), &mut pending).await;
+    }
+    first_completed(&mut pending, deadline).await
+}
+
+/// 先发送给一个 routee，每隔 `interval` 仍无回复就再发送给下一个，
+/// 返回 `within` 内最先到达的回复
+pub(crate) async fn tail_chopping(
+    routees: Vec<ActorRef>,
+    msg: Message,
+    within: Duration,
+    interval: Duration,
+) -> Result<(String, Message), SendError> {
+    if routees.is_empty() {
+        return Err(SendError::NoRoutee);
+    }
+
+    let deadline = Instant::now() + within;
+    let mut remaining = routees.into_iter().peekable();
+    let mut pending = FuturesUnordered::new();
+
+    loop {
+        while let Some(routee) = remaining.next() {
+            if ask_routee(&routee, msg.clone(), &mut pending).await {
+                break;
+            }
+        }
+
+        let has_more = remaining.peek().is_some();
+        let wait_until = if has_more {
+            (Instant::now() + interval).min(deadline)
+        } else {
+            deadline
+        };
+
+        match first_completed(&mut pending, wait_until).await {
+            Ok(reply) => return Ok(reply),
+            Err(_) if has_more && Instant::now() < deadline => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_first_completed_reports_responder() {
+        let mut pending = FuturesUnordered::new();
+        let (slow_ref, slow_rx) = reply_to("slow");
+        let (fast_ref, fast_rx) = reply_to("fast");
+        pending.push(await_reply("slow".to_string(), slow_rx));
+        pending.push(await_reply("fast".to_string(), fast_rx));
+
+        fast_ref.send(Message::new(42u32)).await.unwrap();
+        let (routee, reply) = first_completed(&mut pending, Instant::now() + Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(routee, "fast");
+        assert_eq!(reply.payload.downcast_ref::<u32>(), Some(&42));
+
+        // Slow routee never answers
+        let result = first_completed(&mut pending, Instant::now() + Duration::from_millis(10)).await;
+        assert!(matches!(result, Err(SendError::Timeout)));
+        drop(slow_ref);
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+/// 每个节点默认的虚拟节点数
+pub const DEFAULT_VIRTUAL_NODES: usize = 100;
+
+/// 一致性哈希环
+///
+/// 每个节点在环上放置多个虚拟节点，增删节点时只有约 1/N 的键需要迁移。
+/// 哈希函数与进程和构建无关，相同的键在不同节点上总是映射到同一位置。
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    ring: BTreeMap<u64, String>,
+    virtual_nodes: usize,
+}
+
+impl HashRing {
+    pub fn new() -> Self {
+        Self::with_virtual_nodes(DEFAULT_VIRTUAL_NODES)
+    }
+
+    pub fn with_virtual_nodes(virtual_nodes: usize) -> Self {
+        Self {
+            ring: BTreeMap::new(),
+            virtual_nodes: virtual_nodes.max(1),
+        }
+    }
+
+    pub fn add_node(&mut self, node: &str) {
+        for replica in 0..self.virtual_nodes {
+            let hash = Self::hash(format!("{}#{}", node, replica).as_bytes());
+            self.ring.insert(hash, node.to_string());
+        }
+    }
+
+    pub fn remove_node(&mut self, node: &str) {
+        self.ring.retain(|_, n| n != node);
+    }
+
+    /// 返回顺时针方向第一个虚拟节点所属的节点
+    pub fn get_node(&self, key: &[u8]) -> Option<&str> {
+        let hash = Self::hash(key);
+        self.ring
+            .range(hash..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .map(|(_, node)| node.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    pub fn virtual_nodes(&self) -> usize {
+        self.virtual_nodes
+    }
+
+    /// FNV-1a 加 fmix64 混淆，保证分布均匀且结果稳定
+    fn hash(bytes: &[u8]) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+        hash ^= hash >> 33;
+        hash
+    }
+}
+
+impl Default for HashRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring_with(nodes: usize) -> HashRing {
+        let mut ring = HashRing::new();
+        for i in 0..nodes {
+            ring.add_node(&format!("node-{}", i));
+        }
+        ring
+    }
+
+    fn assignments(ring: &HashRing, keys: &[String]) -> Vec<String> {
+        keys.iter()
+            .map(|key| ring.get_node(key.as_bytes()).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_same_key_same_node() {
+        let ring = ring_with(5);
+        let first = ring.get_node(b"order-42").unwrap().to_string();
+        for _ in 0..10 {
+            assert_eq!(ring.get_node(b"order-42"), Some(first.as_str()));
+        }
+        assert_eq!(ring_with(5).get_node(b"order-42"), Some(first.as_str()));
+    }
+
+    #[test]
+    fn test_adding_node_moves_about_one_nth_of_keys() {
+        let keys: Vec<String> = (0..10_000).map(|i| format!("key-{}", i)).collect();
+        let mut ring = ring_with(10);
+        let before = assignments(&ring, &keys);
+
+        ring.add_node("node-10");
+        let after = assignments(&ring, &keys);
+
+        // Only keys taken over by the new node move
+        let moved: Vec<_> = before.iter().zip(&after).filter(|(b, a)| b != a).collect();
+        assert!(moved.iter().all(|(_, a)| a.as_str() == "node-10"));
+        let ratio = moved.len() as f64 / keys.len() as f64;
+        assert!(ratio > 0.05 && ratio < 0.15, "moved ratio {}", ratio);
+    }
+
+    #[test]
+    fn test_removing_node_only_moves_its_keys() {
+        let keys: Vec<String> = (0..10_000).map(|i| format!("key-{}", i)).collect();
+        let mut ring = ring_with(10);
+        let before = assignments(&ring, &keys);
+
+        ring.remove_node("node-3");
+        let after = assignments(&ring, &keys);
+
+        for (b, a) in before.iter().zip(&after) {
+            if b != "node-3" {
+                assert_eq!(b, a);
+            }
+        }
+        assert!(after.iter().all(|a| a != "node-3"));
+    }
+}
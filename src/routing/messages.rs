@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use crate::actor::ActorRef;
+use crate::message::Message;
+use crate::process::Pid;
+
+/// 向路由器添加 routee
+#[derive(Debug, Clone)]
+pub struct AddRoutee(pub ActorRef);
+
+/// 从路由器移除 routee
+#[derive(Debug, Clone)]
+pub struct RemoveRoutee(pub Pid);
+
+/// 查询当前 routee，路由器以 `Routees` 回复发送方
+#[derive(Debug, Clone, Copy)]
+pub struct GetRoutees;
+
+/// 调整池路由器的 routee 数量，正数扩容、负数缩容
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdjustPoolSize(pub i32);
+
+/// 运行时调整 routee 在加权轮询中的权重
+#[derive(Debug, Clone)]
+pub struct SetRouteeWeight(pub Pid, pub u32);
+
+/// 替换 routee 的标签，用于按消息头标签路由
+#[derive(Debug, Clone)]
+pub struct SetRouteeLabels(pub Pid, pub HashMap<String, String>);
+
+/// `GetRoutees` 的回复
+#[derive(Debug, Clone)]
+pub struct Routees(pub Vec<ActorRef>);
+
+/// 路由管理消息只由路由器自身处理，不会转发给 routee
+pub fn is_management_message(msg: &Message) -> bool {
+    msg.payload.is::<AddRoutee>()
+        || msg.payload.is::<RemoveRoutee>()
+        || msg.payload.is::<GetRoutees>()
+        || msg.payload.is::<AdjustPoolSize>()
+        || msg.payload.is::<SetRouteeWeight>()
+        || msg.payload.is::<SetRouteeLabels>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_management_messages_are_recognized() {
+        assert!(is_management_message(&Message::new(GetRoutees)));
+        assert!(is_management_message(&Message::new(AdjustPoolSize(-1))));
+        assert!(!is_management_message(&Message::new("hello")));
+        assert!(!is_management_message(&Message::new(Routees(Vec::new()))));
+    }
+}
@@ -1,27 +1,41 @@
 use std::sync::Arc;
-use metrics::{Counter, Gauge, Histogram};
+use metrics::{register_counter, register_gauge, register_histogram, Counter, Gauge, Histogram};
 use dashmap::DashMap;
 
-#[derive(Debug)]
 pub struct RouterMetrics {
+    router_id: String,
     routee_count: Gauge,
     message_count: Counter,
     routing_time: Histogram,
     errors: Counter,
+    pool_size: Gauge,
+    resizes_up: Counter,
+    resizes_down: Counter,
     routee_mailbox_sizes: Arc<DashMap<String, Gauge>>,
+    routee_responses: Arc<DashMap<String, Counter>>,
 }
 
 impl RouterMetrics {
     pub fn new(router_id: &str) -> Self {
+        let router = router_id.to_string();
         Self {
-            routee_count: Gauge::new(&format!("router_{}_routee_count", router_id)),
-            message_count: Counter::new(&format!("router_{}_message_count", router_id)),
-            routing_time: Histogram::new(&format!("router_{}_routing_time", router_id)),
-            errors: Counter::new(&format!("router_{}_errors", router_id)),
+            routee_count: register_gauge!("router_routee_count", "router" => router.clone()),
+            message_count: register_counter!("router_message_count", "router" => router.clone()),
+            routing_time: register_histogram!("router_routing_time_seconds", "router" => router.clone()),
+            errors: register_counter!("router_errors", "router" => router.clone()),
+            pool_size: register_gauge!("router_pool_size", "router" => router.clone()),
+            resizes_up: register_counter!("router_resizes", "router" => router.clone(), "direction" => "up"),
+            resizes_down: register_counter!("router_resizes", "router" => router.clone(), "direction" => "down"),
             routee_mailbox_sizes: Arc::new(DashMap::new()),
+            routee_responses: Arc::new(DashMap::new()),
+            router_id: router,
         }
     }
 
+    pub fn router_id(&self) -> &str {
+        &self.router_id
+    }
+
     pub fn record_message(&self) {
         self.message_count.increment(1);
     }
@@ -38,10 +52,51 @@ impl RouterMetrics {
         self.routee_count.set(count as f64);
     }
 
+    /// 记录一次池大小调整
+    pub fn record_resize(&self, previous: usize, current: usize) {
+        if current > previous {
+            self.resizes_up.increment(1);
+        } else if current < previous {
+            self.resizes_down.increment(1);
+        }
+        self.pool_size.set(current as f64);
+    }
+
     pub fn update_routee_mailbox_size(&self, routee_id: &str, size: usize) {
+        let router = self.router_id.clone();
         self.routee_mailbox_sizes
             .entry(routee_id.to_string())
-            .or_insert_with(|| Gauge::new(&format!("routee_{}_mailbox_size", routee_id)))
+            .or_insert_with(|| register_gauge!(
+                "router_routee_mailbox_size",
+                "router" => router,
+                "routee" => routee_id.to_string()
+            ))
             .set(size as f64);
     }
-} 
\ No newline at end of file
+
+    /// 记录 ask 类路由中最先回复的 routee
+    pub fn record_responder(&self, routee_id: &str) {
+        let router = self.router_id.clone();
+        self.routee_responses
+            .entry(routee_id.to_string())
+            .or_insert_with(|| register_counter!(
+                "router_routee_responses",
+                "router" => router,
+                "routee" => routee_id.to_string()
+            ))
+            .increment(1);
+    }
+
+    pub fn remove_routee(&self, routee_id: &str) {
+        self.routee_mailbox_sizes.remove(routee_id);
+        self.routee_responses.remove(routee_id);
+    }
+}
+
+impl std::fmt::Debug for RouterMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouterMetrics")
+            .field("router_id", &self.router_id)
+            .finish()
+    }
+}
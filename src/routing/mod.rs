@@ -1,19 +1,96 @@
-use std::sync::Arc;
+mod ask;
+mod hash_ring;
+mod messages;
+mod metrics;
+mod resizer;
+mod router_actor;
+mod weighted;
+
+pub use hash_ring::{HashRing, DEFAULT_VIRTUAL_NODES};
+pub use messages::{
+    is_management_message, AddRoutee, AdjustPoolSize, GetRoutees, RemoveRoutee, Routees, SetRouteeLabels,
+    SetRouteeWeight,
+};
+pub use metrics::RouterMetrics;
+pub use resizer::{ElasticResizer, PoolResized, ResizerConfig};
+pub use router_actor::{RouterActor, RouterProps};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 use dashmap::DashMap;
+use futures::future::BoxFuture;
+use futures::FutureExt;
 use crate::{Actor, Context, Message, Pid, SendError};
+use crate::actor::ActorRef;
+use weighted::WeightedRoundRobin;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RoutingStrategy {
     RoundRobin,
     Random,
     ConsistentHash,
     LeastBusy,
     Broadcast,
+    /// 按 routee 权重轮询，权重可在运行时调整
+    WeightedRoundRobin,
+    /// 发送给所有 routee，转发 `within` 内最先到达的回复
+    ScatterGatherFirstCompleted { within: Duration },
+    /// 逐个发送给 routee，每隔 `interval` 无回复就尝试下一个
+    TailChopping { within: Duration, interval: Duration },
+}
+
+impl RoutingStrategy {
+    /// 是否需要等待 routee 回复
+    pub fn is_ask(&self) -> bool {
+        matches!(
+            self,
+            RoutingStrategy::ScatterGatherFirstCompleted { .. } | RoutingStrategy::TailChopping { .. }
+        )
+    }
+}
+
+/// ask 类路由等待回复并转发的过程，完成时返回最先回复的 routee
+pub(crate) type PendingAsk = BoxFuture<'static, Result<Option<String>, SendError>>;
+
+/// routee 的路由属性
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteeInfo {
+    /// 加权轮询中的权重，0 表示不再接收消息
+    pub weight: u32,
+    /// 与 `cluster::membership::Member` 相同形式的标签
+    pub labels: HashMap<String, String>,
+}
+
+impl Default for RouteeInfo {
+    fn default() -> Self {
+        Self {
+            weight: 1,
+            labels: HashMap::new(),
+        }
+    }
+}
+
+impl RouteeInfo {
+    /// routee 是否带有消息要求的全部标签
+    pub fn matches(&self, message: &Message) -> bool {
+        match message.message_header() {
+            Some(header) => header
+                .route_labels()
+                .all(|(key, value)| self.labels.get(key).map(String::as_str) == Some(value)),
+            None => true,
+        }
+    }
 }
 
 pub struct Router {
     strategy: RoutingStrategy,
-    routees: Arc<DashMap<String, Pid>>,
+    routees: Arc<DashMap<String, ActorRef>>,
+    routee_info: DashMap<String, RouteeInfo>,
+    weighted: Mutex<WeightedRoundRobin>,
     state: Arc<RouterState>,
+    ring: RwLock<HashRing>,
 }
 
 impl Router {
@@ -21,70 +98,196 @@ impl Router {
         Self {
             strategy,
             routees: Arc::new(DashMap::new()),
+            routee_info: DashMap::new(),
+            weighted: Mutex::new(WeightedRoundRobin::new()),
             state: Arc::new(RouterState::new()),
+            ring: RwLock::new(HashRing::new()),
+        }
+    }
+
+    /// 设置一致性哈希环上每个 routee 的虚拟节点数
+    pub fn with_virtual_nodes(self, virtual_nodes: usize) -> Self {
+        let mut ring = HashRing::with_virtual_nodes(virtual_nodes);
+        for routee in self.routees.iter() {
+            ring.add_node(routee.key());
+        }
+        *self.ring.write().unwrap() = ring;
+        self
+    }
+
+    pub fn add_routee(&self, routee: ActorRef) {
+        self.add_routee_with_info(routee, RouteeInfo::default());
+    }
+
+    pub fn add_routee_with_info(&self, routee: ActorRef, info: RouteeInfo) {
+        let id = routee.id().to_string();
+        self.ring.write().unwrap().add_node(&id);
+        self.routee_info.insert(id.clone(), info);
+        self.routees.insert(id, routee);
+    }
+
+    pub fn remove_routee(&self, routee_id: &str) {
+        self.ring.write().unwrap().remove_node(routee_id);
+        self.routees.remove(routee_id);
+        self.routee_info.remove(routee_id);
+        self.weighted.lock().unwrap().remove(routee_id);
+        self.state.remove(routee_id);
+    }
+
+    /// 调整 routee 的权重
+    pub fn set_weight(&self, pid: &Pid, weight: u32) {
+        if let Some(mut info) = self.routee_info.get_mut(&pid.id) {
+            info.weight = weight;
+        }
+    }
+
+    /// 替换 routee 的标签
+    pub fn set_labels(&self, pid: &Pid, labels: HashMap<String, String>) {
+        if let Some(mut info) = self.routee_info.get_mut(&pid.id) {
+            info.labels = labels;
         }
     }
 
-    pub fn add_routee(&self, pid: Pid) {
-        self.routees.insert(pid.id.clone(), pid);
+    pub fn routee_info(&self, pid: &Pid) -> Option<RouteeInfo> {
+        self.routee_info.get(&pid.id).map(|info| info.clone())
     }
 
-    pub fn remove_routee(&self, pid: &Pid) {
-        self.routees.remove(&pid.id);
+    /// 标签与消息头匹配的 routee
+    fn candidates(&self, msg: &Message) -> Vec<ActorRef> {
+        self.routees
+            .iter()
+            .filter(|r| self.routee_info.get(r.key()).map_or(true, |info| info.matches(msg)))
+            .map(|r| r.value().clone())
+            .collect()
+    }
+
+    /// 记录采样得到的 routee 邮箱深度，供 LeastBusy 路由使用
+    pub fn update_mailbox_size(&self, routee_id: &str, size: usize) {
+        if self.routees.contains_key(routee_id) {
+            self.state.update_mailbox_size(routee_id, size);
+        }
+    }
+
+    pub fn routee_count(&self) -> usize {
+        self.routees.len()
+    }
+
+    pub fn get_routees(&self) -> Vec<ActorRef> {
+        self.routees.iter().map(|r| r.value().clone()).collect()
+    }
+
+    pub fn strategy(&self) -> RoutingStrategy {
+        self.strategy
     }
 
     pub async fn route(&self, msg: Message) -> Result<(), SendError> {
-        match self.strategy {
+        self.route_with_responder(msg).await.map(|_| ())
+    }
+
+    /// 路由消息，ask 类策略返回回复的 routee
+    ///
+    /// 除一致性哈希外，所有策略只在标签与消息头匹配的 routee 中选择。
+    pub async fn route_with_responder(&self, msg: Message) -> Result<Option<String>, SendError> {
+        if self.strategy == RoutingStrategy::ConsistentHash {
+            return self.route_consistent_hash(msg).await.map(|_| None);
+        }
+
+        let candidates = self.candidates(&msg);
+        if candidates.is_empty() {
+            return Err(SendError::NoRoutee);
+        }
+
+        let result = match self.strategy {
+            RoutingStrategy::ScatterGatherFirstCompleted { .. } | RoutingStrategy::TailChopping { .. } => {
+                return self.ask(msg)?.await;
+            }
             RoutingStrategy::RoundRobin => {
-                self.route_round_robin(msg).await
+                self.route_round_robin(candidates, msg).await
             }
             RoutingStrategy::Random => {
-                self.route_random(msg).await
+                self.route_random(candidates, msg).await
             }
             RoutingStrategy::ConsistentHash => {
                 self.route_consistent_hash(msg).await
             }
             RoutingStrategy::LeastBusy => {
-                self.route_least_busy(msg).await
+                self.route_least_busy(candidates, msg).await
             }
             RoutingStrategy::Broadcast => {
-                self.route_broadcast(msg).await
+                self.route_broadcast(candidates, msg).await
+            }
+            RoutingStrategy::WeightedRoundRobin => {
+                self.route_weighted(candidates, msg).await
             }
+        };
+        result.map(|_| None)
+    }
+
+    /// ask 类策略的路由过程，返回的 future 不借用路由器，
+    /// 可在独立任务中等待并把最先到达的回复转发给原始发送方
+    pub(crate) fn ask(&self, msg: Message) -> Result<PendingAsk, SendError> {
+        let mut candidates = self.candidates(&msg);
+        if candidates.is_empty() {
+            return Err(SendError::NoRoutee);
         }
+
+        let sender = msg.sender.clone();
+        let reply = match self.strategy {
+            RoutingStrategy::ScatterGatherFirstCompleted { within } => {
+                ask::scatter_gather_first_completed(candidates, msg, within).boxed()
+            }
+            RoutingStrategy::TailChopping { within, interval } => {
+                // 每条消息从不同的 routee 开始尝试
+                let start = self.state.next_round_robin(candidates.len());
+                candidates.rotate_left(start);
+                ask::tail_chopping(candidates, msg, within, interval).boxed()
+            }
+            strategy => unreachable!("{:?} is not an ask strategy", strategy),
+        };
+        Ok(async move { forward_reply(sender, reply.await).await }.boxed())
     }
 
-    async fn route_round_robin(&self, msg: Message) -> Result<(), SendError> {
-        let next = self.state.next_round_robin(self.routees.len());
-        if let Some(routee) = self.routees.iter().nth(next) {
+    async fn route_round_robin(&self, candidates: Vec<ActorRef>, msg: Message) -> Result<(), SendError> {
+        let next = self.state.next_round_robin(candidates.len());
+        if let Some(routee) = candidates.get(next) {
             routee.send(msg).await
         } else {
             Err(SendError::NoRoutee)
         }
     }
 
-    async fn route_random(&self, msg: Message) -> Result<(), SendError> {
+    async fn route_random(&self, candidates: Vec<ActorRef>, msg: Message) -> Result<(), SendError> {
         use rand::seq::SliceRandom;
-        let routees: Vec<_> = self.routees.iter().collect();
-        if let Some(routee) = routees.choose(&mut rand::thread_rng()) {
-            routee.send(msg).await
-        } else {
-            Err(SendError::NoRoutee)
+        // 先选出 routee，避免线程局部的随机数生成器跨 await 持有
+        let routee = candidates.choose(&mut rand::thread_rng()).cloned();
+        match routee {
+            Some(routee) => routee.send(msg).await,
+            None => Err(SendError::NoRoutee),
         }
     }
 
     async fn route_consistent_hash(&self, msg: Message) -> Result<(), SendError> {
-        let hash = msg.hash();
-        let routees: Vec<_> = self.routees.iter().collect();
-        let idx = hash as usize % routees.len();
-        if let Some(routee) = routees.get(idx) {
-            routee.send(msg).await
-        } else {
-            Err(SendError::NoRoutee)
+        let key = match msg.hash_key() {
+            Some(key) => key,
+            None => {
+                log::warn!("Consistent hash routing requires a message hash key");
+                return Err(SendError::NoRoutee);
+            }
+        };
+
+        let routee_id = self.ring.read().unwrap()
+            .get_node(key.as_bytes())
+            .map(str::to_string);
+        let routee = routee_id
+            .and_then(|id| self.routees.get(&id).map(|r| r.value().clone()));
+        match routee {
+            Some(routee) => routee.send(msg).await,
+            None => Err(SendError::NoRoutee),
         }
     }
 
-    async fn route_least_busy(&self, msg: Message) -> Result<(), SendError> {
-        let least_busy = self.state.get_least_busy_routee(&self.routees);
+    async fn route_least_busy(&self, candidates: Vec<ActorRef>, msg: Message) -> Result<(), SendError> {
+        let least_busy = self.state.get_least_busy_routee(&candidates);
         if let Some(routee) = least_busy {
             routee.send(msg).await
         } else {
@@ -92,9 +295,9 @@ impl Router {
         }
     }
 
-    async fn route_broadcast(&self, msg: Message) -> Result<(), SendError> {
+    async fn route_broadcast(&self, candidates: Vec<ActorRef>, msg: Message) -> Result<(), SendError> {
         let mut errors = Vec::new();
-        for routee in self.routees.iter() {
+        for routee in candidates.iter() {
             if let Err(e) = routee.send(msg.clone()).await {
                 errors.push(e);
             }
@@ -105,4 +308,73 @@ impl Router {
             Err(SendError::BroadcastFailed(errors))
         }
     }
-} 
\ No newline at end of file
+
+    async fn route_weighted(&self, candidates: Vec<ActorRef>, msg: Message) -> Result<(), SendError> {
+        let weights: Vec<_> = candidates
+            .iter()
+            .map(|routee| {
+                let weight = self.routee_info.get(routee.id()).map_or(1, |info| info.weight);
+                (routee.id(), weight)
+            })
+            .collect();
+
+        let selected = self.weighted.lock().unwrap()
+            .select(&weights)
+            .map(str::to_string);
+        let routee = selected.and_then(|id| candidates.iter().find(|routee| routee.id() == id));
+        match routee {
+            Some(routee) => routee.send(msg).await,
+            None => Err(SendError::NoRoutee),
+        }
+    }
+}
+
+/// 将最先到达的回复转发给原始发送方
+async fn forward_reply(
+    sender: Option<ActorRef>,
+    reply: Result<(String, Message), SendError>,
+) -> Result<Option<String>, SendError> {
+    let (routee_id, reply) = reply?;
+    if let Some(sender) = sender {
+        sender.send(reply).await?;
+    }
+    Ok(Some(routee_id))
+}
+
+/// 路由器的运行时状态
+struct RouterState {
+    round_robin: AtomicUsize,
+    mailbox_sizes: DashMap<String, usize>,
+}
+
+impl RouterState {
+    fn new() -> Self {
+        Self {
+            round_robin: AtomicUsize::new(0),
+            mailbox_sizes: DashMap::new(),
+        }
+    }
+
+    fn next_round_robin(&self, len: usize) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        self.round_robin.fetch_add(1, Ordering::Relaxed) % len
+    }
+
+    fn update_mailbox_size(&self, routee_id: &str, size: usize) {
+        self.mailbox_sizes.insert(routee_id.to_string(), size);
+    }
+
+    fn remove(&self, routee_id: &str) {
+        self.mailbox_sizes.remove(routee_id);
+    }
+
+    /// 邮箱最浅的 routee，尚未采样的 routee 视为空闲
+    fn get_least_busy_routee(&self, routees: &[ActorRef]) -> Option<ActorRef> {
+        routees
+            .iter()
+            .min_by_key(|routee| self.mailbox_sizes.get(routee.id()).map(|s| *s).unwrap_or(0))
+            .cloned()
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+/// 弹性调整池大小的配置
+#[derive(Debug, Clone)]
+pub struct ResizerConfig {
+    /// 池的最小 routee 数
+    pub lower_bound: usize,
+    /// 池的最大 routee 数
+    pub upper_bound: usize,
+    /// 邮箱深度达到该值的 routee 视为繁忙
+    pub pressure_threshold: usize,
+    /// 所有 routee 都繁忙时按当前大小的该比例扩容
+    pub rampup_rate: f64,
+    /// 繁忙 routee 比例低于该值时缩容
+    pub backoff_threshold: f64,
+    /// 缩容时按当前大小的该比例减少
+    pub backoff_rate: f64,
+    /// 采样邮箱深度的间隔
+    pub sampling_interval: Duration,
+}
+
+impl Default for ResizerConfig {
+    fn default() -> Self {
+        Self {
+            lower_bound: 1,
+            upper_bound: 10,
+            pressure_threshold: 1,
+            rampup_rate: 0.2,
+            backoff_threshold: 0.3,
+            backoff_rate: 0.1,
+            sampling_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// 池大小调整时发布到事件流的事件
+#[derive(Debug, Clone)]
+pub struct PoolResized {
+    pub router: String,
+    pub previous: usize,
+    pub current: usize,
+    /// 采样时繁忙的 routee 数
+    pub busy: usize,
+}
+
+/// 触发一次采样的消息
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ResizeTick;
+
+/// 根据 routee 邮箱深度计算池大小的调整量
+pub struct ElasticResizer {
+    config: ResizerConfig,
+}
+
+impl ElasticResizer {
+    pub fn new(config: ResizerConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &ResizerConfig {
+        &self.config
+    }
+
+    /// 繁忙的 routee 数
+    pub fn pressure(&self, mailbox_sizes: &[usize]) -> usize {
+        mailbox_sizes
+            .iter()
+            .filter(|&&size| size >= self.config.pressure_threshold.max(1))
+            .count()
+    }
+
+    /// 返回调整量，结果总在上下界之内
+    pub fn resize(&self, mailbox_sizes: &[usize]) -> i32 {
+        let current = mailbox_sizes.len();
+        let pressure = self.pressure(mailbox_sizes);
+
+        let proposed = if current == 0 {
+            0
+        } else if pressure >= current {
+            (current as f64 * self.config.rampup_rate).ceil() as i64
+        } else if (pressure as f64 / current as f64) < self.config.backoff_threshold {
+            -((current as f64 * self.config.backoff_rate).floor() as i64)
+        } else {
+            0
+        };
+
+        let target = (current as i64 + proposed)
+            .max(self.config.lower_bound as i64)
+            .min(self.config.upper_bound.max(self.config.lower_bound) as i64);
+        (target - current as i64) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resizer() -> ElasticResizer {
+        ElasticResizer::new(ResizerConfig {
+            lower_bound: 2,
+            upper_bound: 6,
+            pressure_threshold: 10,
+            rampup_rate: 0.5,
+            backoff_threshold: 0.3,
+            backoff_rate: 0.5,
+            sampling_interval: Duration::from_millis(10),
+        })
+    }
+
+    #[test]
+    fn test_grows_when_all_routees_are_busy() {
+        let resizer = resizer();
+        assert_eq!(resizer.resize(&[10, 20, 15, 30]), 2);
+        // Never grows past the upper bound
+        assert_eq!(resizer.resize(&[10, 20, 15, 30, 12]), 1);
+        // Some idle routees, no change
+        assert_eq!(resizer.resize(&[10, 20, 0, 0]), 0);
+    }
+
+    #[test]
+    fn test_backs_off_within_bounds() {
+        let resizer = resizer();
+        assert_eq!(resizer.resize(&[0, 0, 0, 0, 0, 0]), -3);
+        assert_eq!(resizer.resize(&[0, 0, 0]), -1);
+        // Grows to the lower bound even without pressure
+        assert_eq!(resizer.resize(&[0]), 1);
+    }
+}
@@ -1,21 +1,71 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 use async_trait::async_trait;
-use crate::{Actor, Context, Message, Pid, Props, SendError};
-use super::{Router, RouterKind};
+use tokio::task::JoinHandle;
+use tokio::time::interval;
+use crate::{Actor, ActorSystem, Context, Message, Pid, SendError, SpawnError};
+use crate::actor::{ActorRef, Props};
+use crate::eventstream::EventStream;
+use crate::message::SystemMessage;
+use crate::supervision::{ChildStats, OneForOneStrategy, SupervisorDirective, SupervisorStrategy};
+use super::{Router, RoutingStrategy};
+use super::messages::{AddRoutee, AdjustPoolSize, GetRoutees, RemoveRoutee, Routees, SetRouteeLabels, SetRouteeWeight};
+use super::resizer::{ElasticResizer, PoolResized, ResizeTick};
 use crate::routing::metrics::RouterMetrics;
 
+/// 两次意外终止间隔超过该时长时，重新开始计算替换次数
+const REPLACEMENT_WINDOW: Duration = Duration::from_secs(60);
+/// 首次替换前的等待时间，之后每次加倍
+const REPLACEMENT_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_REPLACEMENT_BACKOFF: Duration = Duration::from_secs(10);
+
+/// 延迟到期后补足池中的 routee
+struct ReplaceRoutees;
+
+/// 池路由器配置，routee 由路由器自身创建并监督
+struct PoolConfig {
+    size: usize,
+    system: Arc<ActorSystem>,
+    props: Props,
+    supervisor: Arc<dyn SupervisorStrategy>,
+    resizer: Option<ElasticResizer>,
+    event_stream: Option<Arc<EventStream>>,
+}
+
 pub struct RouterActor {
     router: Router,
-    metrics: RouterMetrics,
+    metrics: Arc<RouterMetrics>,
+    pool: Option<Arc<PoolConfig>>,
+    // 池路由器的目标大小，可通过 AdjustPoolSize 调整
+    pool_size: usize,
+    children: HashMap<String, ActorRef>,
+    child_stats: HashMap<String, ChildStats>,
+    // 池中 routee 意外终止的统计，替换次数受监督策略限制
+    replacements: ChildStats,
+    // 已安排延迟替换，期间的终止不再重复安排
+    replacing: bool,
+    ticker: Option<JoinHandle<()>>,
 }
 
 impl RouterActor {
-    pub fn new(kind: RouterKind) -> Self {
+    pub fn new(kind: RoutingStrategy) -> Self {
         let router = Router::new(kind);
-        let metrics = RouterMetrics::new(&format!("router_{:?}", kind));
-        Self { router, metrics }
+        let metrics = Arc::new(RouterMetrics::new(&format!("router_{:?}", kind)));
+        Self {
+            router,
+            metrics,
+            pool: None,
+            pool_size: 0,
+            children: HashMap::new(),
+            child_stats: HashMap::new(),
+            replacements: ChildStats::default(),
+            replacing: false,
+            ticker: None,
+        }
     }
 
-    pub fn with_routees(kind: RouterKind, routees: Vec<Pid>) -> Self {
+    pub fn with_routees(kind: RoutingStrategy, routees: Vec<ActorRef>) -> Self {
         let mut actor = Self::new(kind);
         for routee in routees {
             actor.router.add_routee(routee);
@@ -23,66 +73,511 @@ impl RouterActor {
         actor.metrics.update_routee_count(actor.router.routee_count());
         actor
     }
-}
 
-#[async_trait]
-impl Actor for RouterActor {
-    async fn receive(&mut self, ctx: &Context, msg: Message) -> Result<(), SendError> {
-        let start = std::time::Instant::now();
-        
-        let result = match msg {
-            Message::AddRoutee(pid) => {
-                self.router.add_routee(pid);
-                self.metrics.update_routee_count(self.router.routee_count());
-                Ok(())
+    fn with_pool(kind: RoutingStrategy, pool: Arc<PoolConfig>) -> Self {
+        let mut actor = Self::new(kind);
+        actor.pool_size = pool.size;
+        actor.pool = Some(pool);
+        actor
+    }
+
+    fn is_pool(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    /// 在独立任务中等待 routee 回复，路由器继续处理后续消息
+    fn ask(&self, msg: Message) -> Result<(), SendError> {
+        let pending = match self.router.ask(msg) {
+            Ok(pending) => pending,
+            Err(e) => {
+                self.metrics.record_error();
+                return Err(e);
             }
-            Message::RemoveRoutee(pid) => {
-                self.router.remove_routee(&pid);
-                self.metrics.update_routee_count(self.router.routee_count());
-                Ok(())
+        };
+
+        let metrics = Arc::clone(&self.metrics);
+        tokio::spawn(async move {
+            match pending.await {
+                Ok(Some(routee_id)) => metrics.record_responder(&routee_id),
+                Ok(None) => {}
+                Err(e) => {
+                    log::warn!("Ask routing failed: {:?}", e);
+                    metrics.record_error();
+                }
             }
-            Message::GetRoutees => {
-                ctx.respond(self.router.get_routees());
+        });
+        Ok(())
+    }
+
+    /// 创建并监视一个新的 routee
+    async fn spawn_routee(&mut self, ctx: &Context) -> Result<(), SendError> {
+        let pool = match &self.pool {
+            Some(pool) => Arc::clone(pool),
+            None => return Ok(()),
+        };
+
+        let child = pool.system.spawn(&pool.props).map_err(|e| {
+            log::error!("Router {} failed to spawn a routee: {}", self.metrics.router_id(), e);
+            SendError::NoRoutee
+        })?;
+        ctx.watch(&child).await?;
+
+        self.router.add_routee(child.clone());
+        self.children.insert(child.id().to_string(), child);
+        Ok(())
+    }
+
+    /// 停止一个 routee，停止后不再替换
+    async fn stop_routee(&mut self, ctx: &Context, id: &str) {
+        if let Some(child) = self.children.remove(id) {
+            self.child_stats.remove(id);
+            self.router.remove_routee(id);
+            self.metrics.remove_routee(id);
+            let _ = ctx.unwatch(&child).await;
+            child.stop().await;
+        }
+    }
+
+    /// 补足或缩减 routee 至目标大小
+    async fn resize_pool(&mut self, ctx: &Context) -> Result<(), SendError> {
+        while self.children.len() < self.pool_size {
+            self.spawn_routee(ctx).await?;
+        }
+        while self.children.len() > self.pool_size {
+            let id = match self.children.keys().next() {
+                Some(id) => id.clone(),
+                None => break,
+            };
+            self.stop_routee(ctx, &id).await;
+        }
+        self.metrics.update_routee_count(self.router.routee_count());
+        Ok(())
+    }
+
+    /// 采样 routee 邮箱深度，并按弹性策略调整池大小
+    async fn sample_and_resize(&mut self, ctx: &Context) -> Result<(), SendError> {
+        let pool = match &self.pool {
+            Some(pool) => Arc::clone(pool),
+            None => return Ok(()),
+        };
+
+        let mut mailbox_sizes = Vec::with_capacity(self.children.len());
+        for (id, child) in &self.children {
+            let size = child.mailbox_size();
+            self.router.update_mailbox_size(id, size);
+            self.metrics.update_routee_mailbox_size(id, size);
+            mailbox_sizes.push(size);
+        }
+
+        let resizer = match &pool.resizer {
+            Some(resizer) => resizer,
+            None => return Ok(()),
+        };
+        let delta = resizer.resize(&mailbox_sizes);
+        if delta == 0 {
+            return Ok(());
+        }
+
+        let previous = self.pool_size;
+        self.pool_size = adjusted_pool_size(previous, delta);
+        self.resize_pool(ctx).await?;
+
+        log::debug!("Router {} resized from {} to {}", self.metrics.router_id(), previous, self.pool_size);
+        self.metrics.record_resize(previous, self.pool_size);
+        if let Some(event_stream) = &pool.event_stream {
+            event_stream.publish(PoolResized {
+                router: self.metrics.router_id().to_string(),
+                previous,
+                current: self.pool_size,
+                busy: resizer.pressure(&mailbox_sizes),
+            });
+        }
+        Ok(())
+    }
+
+    async fn handle_terminated(&mut self, ctx: &Context, routee: &ActorRef) -> Result<(), SendError> {
+        self.router.remove_routee(routee.id());
+        self.metrics.remove_routee(routee.id());
+        self.child_stats.remove(routee.id());
+
+        // 池中的 routee 意外终止时创建替代者
+        if self.children.remove(routee.id()).is_some() && !ctx.is_stopping() {
+            self.schedule_replacement(ctx, routee).await;
+        }
+        self.metrics.update_routee_count(self.router.routee_count());
+        Ok(())
+    }
+
+    /// 按监督策略决定是否替换意外终止的 routee，替换按退避延迟进行，
+    /// 持续失败的 routee 不会被立即反复创建
+    async fn schedule_replacement(&mut self, ctx: &Context, routee: &ActorRef) {
+        let pool = match &self.pool {
+            Some(pool) => Arc::clone(pool),
+            None => return,
+        };
+
+        let stats = &mut self.replacements;
+        if stats.last_failure.map_or(false, |at| at.elapsed() > REPLACEMENT_WINDOW) {
+            stats.reset();
+        }
+        stats.record_failure();
+        let directive = pool.supervisor
+            .handle_failure(ctx, &format!("routee {} terminated", routee.id()), stats.failure_count as usize)
+            .await;
+
+        match directive {
+            SupervisorDirective::Resume | SupervisorDirective::Restart => {
+                stats.record_restart();
+                if self.replacing {
+                    return;
+                }
+                self.replacing = true;
+                let delay = replacement_backoff(stats.restart_count);
+                log::info!("Routee {} terminated, spawning a replacement in {:?}", routee.id(), delay);
+                let self_ref = ctx.self_ref().clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let _ = self_ref.send(Message::new(ReplaceRoutees)).await;
+                });
+            }
+            SupervisorDirective::Stop | SupervisorDirective::Escalate => {
+                // 超出监督限制，池缩小到剩余的 routee
+                log::warn!(
+                    "Routee {} terminated too often, shrinking router {} to {}",
+                    routee.id(),
+                    self.metrics.router_id(),
+                    self.children.len()
+                );
+                self.pool_size = self.children.len();
+            }
+        }
+    }
+
+    async fn handle_child_failure(&mut self, ctx: &Context, child: &ActorRef, error: SendError) -> Result<(), SendError> {
+        let pool = match &self.pool {
+            Some(pool) if self.children.contains_key(child.id()) => Arc::clone(pool),
+            _ => return Err(error),
+        };
+
+        let stats = self.child_stats.entry(child.id().to_string()).or_default();
+        stats.record_failure();
+        let directive = pool.supervisor
+            .handle_failure(ctx, &format!("{:?}", error), stats.failure_count as usize)
+            .await;
+
+        match directive {
+            SupervisorDirective::Resume => child.send(Message::new(SystemMessage::Resume)).await,
+            SupervisorDirective::Restart => {
+                stats.record_restart();
+                child.send(Message::new(SystemMessage::Restart)).await
+            }
+            // 终止后由 DeathWatch 触发替换
+            SupervisorDirective::Stop => {
+                child.stop().await;
                 Ok(())
             }
-            _ => {
-                self.metrics.record_message();
-                let result = self.router.route(&msg).await;
-                if result.is_err() {
-                    self.metrics.record_error();
+            SupervisorDirective::Escalate => Err(error),
+        }
+    }
+
+    /// 处理系统消息
+    async fn handle_system(&mut self, ctx: &Context, system: SystemMessage, sender: Option<ActorRef>) -> Result<(), SendError> {
+        match system {
+            SystemMessage::Terminated(routee) => self.handle_terminated(ctx, &routee).await,
+            SystemMessage::Failure(error) => match sender {
+                Some(child) => self.handle_child_failure(ctx, &child, error).await,
+                None => Err(error),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// 处理路由管理命令
+    async fn handle_management(&mut self, ctx: &Context, command: Management) -> Result<(), SendError> {
+        match command {
+            Management::Add(routee) => self.router.add_routee(routee),
+            Management::Remove(pid) => {
+                self.router.remove_routee(&pid.id);
+                if self.children.contains_key(&pid.id) {
+                    self.pool_size = self.pool_size.saturating_sub(1);
+                    self.stop_routee(ctx, &pid.id).await;
                 }
-                result
             }
+            Management::Get(sender) => {
+                let routees = Routees(self.router.get_routees());
+                return match sender {
+                    Some(sender) => sender.send(Message::new(routees)).await,
+                    None => {
+                        log::warn!("GetRoutees received without a sender to reply to");
+                        Ok(())
+                    }
+                };
+            }
+            Management::SetWeight(pid, weight) => self.router.set_weight(&pid, weight),
+            Management::SetLabels(pid, labels) => self.router.set_labels(&pid, labels),
+            Management::Adjust(delta) => {
+                if !self.is_pool() {
+                    log::warn!("Ignoring AdjustPoolSize({}) on a group router", delta);
+                    return Ok(());
+                }
+                self.pool_size = adjusted_pool_size(self.pool_size, delta);
+                return self.resize_pool(ctx).await;
+            }
+        }
+
+        self.metrics.update_routee_count(self.router.routee_count());
+        Ok(())
+    }
+}
+
+/// 从管理消息中解析出的命令，不借用原消息，可以跨 await 持有
+enum Management {
+    Add(ActorRef),
+    Remove(Pid),
+    Get(Option<ActorRef>),
+    SetWeight(Pid, u32),
+    SetLabels(Pid, HashMap<String, String>),
+    Adjust(i32),
+}
+
+impl Management {
+    /// 不是管理消息时原样返回该消息
+    fn parse(msg: Message) -> Result<Self, Message> {
+        let payload = &msg.payload;
+        let command = if let Some(AddRoutee(routee)) = payload.downcast_ref::<AddRoutee>() {
+            Management::Add(routee.clone())
+        } else if let Some(RemoveRoutee(pid)) = payload.downcast_ref::<RemoveRoutee>() {
+            Management::Remove(pid.clone())
+        } else if payload.is::<GetRoutees>() {
+            Management::Get(msg.sender.clone())
+        } else if let Some(SetRouteeWeight(pid, weight)) = payload.downcast_ref::<SetRouteeWeight>() {
+            Management::SetWeight(pid.clone(), *weight)
+        } else if let Some(SetRouteeLabels(pid, labels)) = payload.downcast_ref::<SetRouteeLabels>() {
+            Management::SetLabels(pid.clone(), labels.clone())
+        } else if let Some(AdjustPoolSize(delta)) = payload.downcast_ref::<AdjustPoolSize>() {
+            Management::Adjust(*delta)
+        } else {
+            return Err(msg);
         };
+        Ok(command)
+    }
+}
 
+#[async_trait]
+impl Actor for RouterActor {
+    async fn receive(&mut self, ctx: &Context, msg: Message) -> Result<(), SendError> {
+        // 管理消息与系统消息由路由器自身处理，不转发给 routee
+        let msg = match Management::parse(msg) {
+            Ok(command) => return self.handle_management(ctx, command).await,
+            Err(msg) => msg,
+        };
+        let msg = match msg.payload.downcast::<SystemMessage>() {
+            Ok(system) => return self.handle_system(ctx, *system, msg.sender).await,
+            Err(payload) => Message { payload, ..msg },
+        };
+        if msg.payload.is::<ResizeTick>() {
+            return self.sample_and_resize(ctx).await;
+        }
+        if msg.payload.is::<ReplaceRoutees>() {
+            self.replacing = false;
+            if ctx.is_stopping() {
+                return Ok(());
+            }
+            return self.resize_pool(ctx).await;
+        }
+
+        let start = std::time::Instant::now();
+        self.metrics.record_message();
+        if self.router.strategy().is_ask() {
+            let result = self.ask(msg);
+            self.metrics.record_routing_time(start.elapsed());
+            return result;
+        }
+
+        let result = match self.router.route_with_responder(msg).await {
+            Ok(Some(routee_id)) => {
+                self.metrics.record_responder(&routee_id);
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            self.metrics.record_error();
+        }
         self.metrics.record_routing_time(start.elapsed());
         result
     }
+
+    async fn started(&mut self, ctx: &Context) -> Result<(), SendError> {
+        self.resize_pool(ctx).await?;
+
+        let sampling_interval = match self.pool.as_ref().and_then(|p| p.resizer.as_ref()) {
+            Some(resizer) => resizer.config().sampling_interval,
+            None => return Ok(()),
+        };
+        let self_ref = ctx.self_ref().clone();
+        self.ticker = Some(tokio::spawn(async move {
+            let mut interval = interval(sampling_interval);
+            loop {
+                interval.tick().await;
+                if self_ref.send(Message::new(ResizeTick)).await.is_err() {
+                    break;
+                }
+            }
+        }));
+        Ok(())
+    }
+
+    async fn stopping(&mut self, ctx: &Context) -> Result<(), SendError> {
+        if let Some(ticker) = self.ticker.take() {
+            ticker.abort();
+        }
+        let ids: Vec<_> = self.children.keys().cloned().collect();
+        for id in ids {
+            self.stop_routee(ctx, &id).await;
+        }
+        Ok(())
+    }
+}
+
+/// 第 `restarts` 次替换前的等待时间
+fn replacement_backoff(restarts: i32) -> Duration {
+    let exponent = restarts.saturating_sub(1).clamp(0, 16) as u32;
+    REPLACEMENT_BACKOFF.saturating_mul(1 << exponent).min(MAX_REPLACEMENT_BACKOFF)
+}
+
+fn adjusted_pool_size(current: usize, delta: i32) -> usize {
+    if delta >= 0 {
+        current.saturating_add(delta as usize)
+    } else {
+        current.saturating_sub(delta.unsigned_abs() as usize)
+    }
 }
 
 // Router Props 构建器
 pub struct RouterProps {
-    kind: RouterKind,
-    routees: Vec<Pid>,
+    kind: RoutingStrategy,
+    routees: Vec<ActorRef>,
+    pool: Option<(usize, Props)>,
+    supervisor: Arc<dyn SupervisorStrategy>,
+    resizer: Option<ElasticResizer>,
+    event_stream: Option<Arc<EventStream>>,
 }
 
 impl RouterProps {
-    pub fn new(kind: RouterKind) -> Self {
+    pub fn new(kind: RoutingStrategy) -> Self {
         Self {
             kind,
             routees: Vec::new(),
+            pool: None,
+            supervisor: Arc::new(OneForOneStrategy::new(10, Duration::from_secs(60), SupervisorDirective::Restart)),
+            resizer: None,
+            event_stream: None,
         }
     }
 
-    pub fn with_routees(mut self, routees: Vec<Pid>) -> Self {
+    /// 池路由器：路由器通过 `ActorSystem` 按 `props` 创建 `size` 个 routee 并监督它们，
+    /// 终止的 routee 在监督策略允许时被替换
+    pub fn pool(kind: RoutingStrategy, size: usize, props: Props) -> Self {
+        let mut router = Self::new(kind);
+        router.pool = Some((size, props));
+        router
+    }
+
+    pub fn with_routees(mut self, routees: Vec<ActorRef>) -> Self {
         self.routees = routees;
         self
     }
 
-    pub fn spawn(self, ctx: &Context) -> Result<Pid, SendError> {
-        let props = Props::new(move || {
-            Box::new(RouterActor::with_routees(self.kind, self.routees.clone()))
+    /// 设置池中 routee 的监督策略
+    pub fn with_supervisor(mut self, strategy: Arc<dyn SupervisorStrategy>) -> Self {
+        self.supervisor = strategy;
+        self
+    }
+
+    /// 按 routee 邮箱压力在上下界之间弹性调整池大小
+    pub fn with_resizer(mut self, resizer: ElasticResizer) -> Self {
+        self.resizer = Some(resizer);
+        self
+    }
+
+    /// 将池大小调整发布到事件流
+    pub fn with_event_stream(mut self, event_stream: Arc<EventStream>) -> Self {
+        self.event_stream = Some(event_stream);
+        self
+    }
+
+    pub fn spawn(self, system: &Arc<ActorSystem>) -> Result<ActorRef, SpawnError> {
+        let RouterProps { kind, routees, pool, supervisor, resizer, event_stream } = self;
+        let pool = pool.map(|(size, props)| {
+            // 初始大小同样受弹性上下界约束
+            let size = match &resizer {
+                Some(resizer) => size.clamp(
+                    resizer.config().lower_bound,
+                    resizer.config().upper_bound.max(resizer.config().lower_bound),
+                ),
+                None => size,
+            };
+            let system = Arc::clone(system);
+            Arc::new(PoolConfig { size, system, props, supervisor, resizer, event_stream })
         });
-        ctx.spawn(props)
+
+        let props = Props::new(move || match &pool {
+            Some(pool) => RouterActor::with_pool(kind, Arc::clone(pool)),
+            None => RouterActor::with_routees(kind, routees.clone()),
+        });
+        system.spawn(&props)
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_asks_do_not_block_router() {
+        let (routee_tx, mut routee_rx) = tokio::sync::mpsc::channel(8);
+        let routee = ActorRef::new("routee".to_string(), routee_tx);
+        let mut router = RouterActor::with_routees(
+            RoutingStrategy::ScatterGatherFirstCompleted { within: Duration::from_secs(5) },
+            vec![routee.clone()],
+        );
+        let (router_tx, _router_rx) = tokio::sync::mpsc::channel(8);
+        let ctx = Context::with_sender(ActorRef::new("router".to_string(), router_tx.clone()), None, router_tx);
+
+        let mut clients = Vec::new();
+        for (i, value) in [1u32, 2].into_iter().enumerate() {
+            let (client_tx, client_rx) = tokio::sync::mpsc::channel(1);
+            let mut ask = Message::new(value);
+            ask.sender = Some(ActorRef::new(format!("client-{}", i), client_tx));
+            router.receive(&ctx, ask).await.unwrap();
+            clients.push(client_rx);
+        }
+
+        // 两个请求都已到达 routee，之后才回复
+        let first = routee_rx.recv().await.unwrap();
+        let second = routee_rx.recv().await.unwrap();
+        second.sender.unwrap().send(Message::new(20u32)).await.unwrap();
+        first.sender.unwrap().send(Message::new(10u32)).await.unwrap();
+
+        let reply = clients[1].recv().await.unwrap();
+        assert_eq!(reply.payload.downcast_ref::<u32>(), Some(&20));
+        let reply = clients[0].recv().await.unwrap();
+        assert_eq!(reply.payload.downcast_ref::<u32>(), Some(&10));
+    }
+
+    #[test]
+    fn test_replacement_backoff_doubles_up_to_limit() {
+        assert_eq!(replacement_backoff(1), Duration::from_millis(100));
+        assert_eq!(replacement_backoff(3), Duration::from_millis(400));
+        assert_eq!(replacement_backoff(30), MAX_REPLACEMENT_BACKOFF);
+    }
+
+    #[test]
+    fn test_adjusted_pool_size() {
+        assert_eq!(adjusted_pool_size(5, 3), 8);
+        assert_eq!(adjusted_pool_size(5, -2), 3);
+        assert_eq!(adjusted_pool_size(2, -5), 0);
+    }
+}
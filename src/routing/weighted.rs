@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+/// 平滑加权轮询
+///
+/// 每次选择时各 routee 的当前值加上自身权重，选中当前值最大者并减去总权重，
+/// 使权重为 95:5 的两个 routee 在每 100 条消息中恰好分到 95 和 5 条且交错分布。
+#[derive(Debug, Default)]
+pub(crate) struct WeightedRoundRobin {
+    current: HashMap<String, i64>,
+}
+
+impl WeightedRoundRobin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从候选 routee 中选择一个，权重为 0 的 routee 不会被选中
+    pub fn select<'a>(&mut self, candidates: &[(&'a str, u32)]) -> Option<&'a str> {
+        let total: i64 = candidates.iter().map(|(_, weight)| *weight as i64).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut selected: Option<(&'a str, i64)> = None;
+        for &(id, weight) in candidates {
+            if weight == 0 {
+                continue;
+            }
+            let current = self.current.entry(id.to_string()).or_insert(0);
+            *current += weight as i64;
+            if selected.map_or(true, |(_, best)| *current > best) {
+                selected = Some((id, *current));
+            }
+        }
+
+        let (id, _) = selected?;
+        if let Some(current) = self.current.get_mut(id) {
+            *current -= total;
+        }
+        Some(id)
+    }
+
+    pub fn remove(&mut self, routee_id: &str) {
+        self.current.remove(routee_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canary_receives_its_share() {
+        let mut wrr = WeightedRoundRobin::new();
+        let routees = [("stable", 95), ("canary", 5)];
+        let canary = (0..100)
+            .filter(|_| wrr.select(&routees) == Some("canary"))
+            .count();
+        assert_eq!(canary, 5);
+    }
+
+    #[test]
+    fn test_weights_change_at_runtime() {
+        let mut wrr = WeightedRoundRobin::new();
+        assert_eq!(wrr.select(&[("a", 1), ("b", 0)]), Some("a"));
+        assert_eq!(wrr.select(&[("a", 0), ("b", 0)]), None);
+
+        let picks: Vec<_> = (0..4).filter_map(|_| wrr.select(&[("a", 1), ("b", 3)])).collect();
+        assert_eq!(picks.iter().filter(|id| **id == "b").count(), 3);
+    }
+}
@@ -1,23 +1,45 @@
 use std::sync::Arc;
+use dashmap::DashMap;
+use parking_lot::RwLock;
 use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
 use crate::config::SystemConfig;
-use crate::dispatcher::ThreadPoolDispatcher;
+use crate::actor::{ActorCell, ActorRef, Props};
+use crate::context::Context;
+use crate::dispatcher::{ActorMailbox, BackpressureController, Dispatcher, DispatcherRegistry, ThreadPoolDispatcher};
+use crate::errors::{SendError, SpawnError};
+use crate::eventstream::EventStream;
+use crate::message::Message;
+use crate::process::Pid;
+use crate::remote::RemoteContext;
 
 pub struct ActorSystem {
     config: SystemConfig,
     runtime: Arc<Runtime>,
     dispatcher: Arc<ThreadPoolDispatcher>,
+    dispatchers: Arc<DispatcherRegistry>,
+    pub(crate) event_stream: Arc<EventStream>,
+    // 运行中的顶层 Actor，按 id 查找，供远程消息投递
+    actors: Arc<DashMap<String, ActorRef>>,
+    remote: Arc<RwLock<Option<Arc<RemoteContext>>>>,
 }
 
 impl ActorSystem {
     pub fn new(config: SystemConfig) -> Self {
         let runtime = Arc::new(Runtime::new().unwrap());
         let dispatcher = Arc::new(config.dispatcher.clone());
-        
+
+        // 配置中的线程池作为默认调度器
+        let dispatchers = Arc::new(DispatcherRegistry::with_default(dispatcher.clone()));
+
         Self {
             config,
             runtime,
             dispatcher,
+            dispatchers,
+            event_stream: Arc::new(EventStream::new()),
+            actors: Arc::new(DashMap::new()),
+            remote: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -28,4 +50,233 @@ impl ActorSystem {
     pub fn runtime(&self) -> &Runtime {
         &self.runtime
     }
-} 
\ No newline at end of file
+
+    pub fn dispatchers(&self) -> &Arc<DispatcherRegistry> {
+        &self.dispatchers
+    }
+
+    /// 系统事件流，背压变化、死信等事件发布到这里
+    pub fn event_stream(&self) -> &Arc<EventStream> {
+        &self.event_stream
+    }
+
+    /// 启用远程通信后由 `RemoteContext` 注册
+    pub(crate) fn set_remote(&self, remote: Arc<RemoteContext>) {
+        *self.remote.write() = Some(remote);
+    }
+
+    /// 本地 Actor 终止时通过它通知远程监视者
+    pub fn remote(&self) -> Option<Arc<RemoteContext>> {
+        self.remote.read().clone()
+    }
+
+    /// 解析 Props 引用的调度器
+    pub fn resolve_dispatcher(&self, props: &Props) -> Result<Arc<dyn Dispatcher>, SpawnError> {
+        props.resolve_dispatcher(&self.dispatchers)
+    }
+
+    /// 创建顶层 Actor，调度器在创建前解析，名称未注册时返回 `SpawnError::DispatcherNotFound`
+    pub fn spawn(&self, props: &Props) -> Result<ActorRef, SpawnError> {
+        let dispatcher = self.resolve_dispatcher(props)?;
+
+        let (sender, receiver) = mpsc::channel(props.mailbox_size());
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut actor_ref = ActorRef::new(id.clone(), sender.clone());
+        // 发送方占用位置，处理完一条消息后归还
+        let backpressure = props.backpressure().map(|config| {
+            Arc::new(
+                BackpressureController::new(config.clone())
+                    .with_source(id.clone())
+                    .with_event_stream(Arc::clone(&self.event_stream)),
+            )
+        });
+        if let Some(controller) = &backpressure {
+            actor_ref = actor_ref.with_backpressure(Arc::clone(controller));
+        }
+        let context = Context::with_sender(actor_ref.clone(), None, sender);
+        let cell = Arc::new(ActorCell::new(props.create_actor(), context));
+        let mailbox = ActorMailbox::new(cell, receiver)
+            .with_backpressure(backpressure)
+            .with_batching(props.batch_size(), props.batch_max_wait())
+            .with_priority(props.priority());
+
+        let running = dispatcher.attach(mailbox);
+        self.actors.insert(id.clone(), actor_ref.clone());
+
+        // 邮箱循环退出即 Actor 终止，注销后通知远程监视者
+        let actors = Arc::clone(&self.actors);
+        let remote = Arc::clone(&self.remote);
+        self.runtime.spawn(async move {
+            let _ = running.await;
+            actors.remove(&id);
+            let remote = remote.read().clone();
+            if let Some(remote) = remote {
+                remote.notify_terminated(&remote.pid(id)).await;
+            }
+        });
+
+        Ok(actor_ref)
+    }
+
+    /// 向本地 Actor 投递消息，Actor 不存在或已终止时返回 `SendError::DeadLetter`
+    pub async fn send(&self, pid: &Pid, msg: Message) -> Result<(), SendError> {
+        let actor = match self.actors.get(&pid.id) {
+            Some(actor) => actor.clone(),
+            None => return Err(SendError::DeadLetter),
+        };
+        actor.send(msg).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use async_trait::async_trait;
+    use tokio::sync::Notify;
+    use crate::actor::Actor;
+    use crate::dispatcher::{BackpressureChanged, BackpressureConfig, BackpressureSignal, Priority, PriorityDispatcher};
+    use crate::errors::SendError;
+    use crate::message::Message;
+
+    struct Noop;
+
+    #[async_trait]
+    impl Actor for Noop {
+        async fn receive(&mut self, _ctx: &Context, _msg: Message) -> Result<(), SendError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_spawn_rejects_unknown_dispatcher() {
+        let system = ActorSystem::new(SystemConfig::default());
+        let props = Props::new(|| Noop).with_dispatcher("missing");
+
+        match system.spawn(&props) {
+            Err(SpawnError::DispatcherNotFound(name)) => assert_eq!(name, "missing"),
+            _ => panic!("expected DispatcherNotFound"),
+        }
+        assert!(system.spawn(&Props::new(|| Noop)).is_ok());
+    }
+
+    struct Batches {
+        batches: std::sync::mpsc::Sender<Vec<u32>>,
+    }
+
+    #[async_trait]
+    impl Actor for Batches {
+        async fn receive(&mut self, ctx: &Context, msg: Message) -> Result<(), SendError> {
+            self.receive_batch(ctx, vec![msg]).await
+        }
+
+        async fn receive_batch(&mut self, _ctx: &Context, msgs: Vec<Message>) -> Result<(), SendError> {
+            let values = msgs.iter().filter_map(|m| m.payload.downcast_ref::<u32>().copied()).collect();
+            let _ = self.batches.send(values);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_spawn_delivers_batches_to_receive_batch() {
+        let system = ActorSystem::new(SystemConfig::default());
+        let (batches, delivered) = std::sync::mpsc::channel();
+        let props = Props::new(move || Batches { batches: batches.clone() })
+            .with_batching(3, Duration::from_secs(5));
+        let actor = system.spawn(&props).unwrap();
+
+        system.runtime().block_on(async {
+            for i in 0..3u32 {
+                actor.send(Message::new(i)).await.unwrap();
+            }
+        });
+
+        // The batch fills up long before batch_max_wait expires
+        assert_eq!(delivered.recv_timeout(Duration::from_secs(1)).unwrap(), vec![0, 1, 2]);
+    }
+
+    struct Ordered {
+        name: &'static str,
+        order: std::sync::mpsc::Sender<&'static str>,
+        gate: Option<Arc<Notify>>,
+    }
+
+    #[async_trait]
+    impl Actor for Ordered {
+        async fn receive(&mut self, _ctx: &Context, _msg: Message) -> Result<(), SendError> {
+            let _ = self.order.send(self.name);
+            if let Some(gate) = &self.gate {
+                gate.notified().await;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_priority_dispatcher_runs_critical_mailbox_first() {
+        let system = ActorSystem::new(SystemConfig::default());
+        // 单个工作线程，邮箱的处理顺序只取决于优先级
+        let dispatcher = Arc::new(PriorityDispatcher::new(1, 100));
+        system.dispatchers().register("priority-test", dispatcher.clone());
+
+        let (order, processed) = std::sync::mpsc::channel();
+        let gate = Arc::new(Notify::new());
+        let spawn = |name: &'static str, priority: Priority, gate: Option<Arc<Notify>>| {
+            let order = order.clone();
+            let props = Props::new(move || Ordered { name, order: order.clone(), gate: gate.clone() })
+                .with_dispatcher("priority-test")
+                .with_priority(priority);
+            system.spawn(&props).unwrap()
+        };
+        let blocker = spawn("blocker", Priority::Normal, Some(Arc::clone(&gate)));
+        let background = spawn("background", Priority::Background, None);
+        let critical = spawn("critical", Priority::Critical, None);
+
+        system.runtime().block_on(async {
+            blocker.send(Message::new(())).await.unwrap();
+            assert_eq!(processed.recv_timeout(Duration::from_secs(5)).unwrap(), "blocker");
+
+            // 工作线程忙于 blocker 时，后到的 Background 与 Critical 邮箱都进入运行队列
+            background.send(Message::new(())).await.unwrap();
+            critical.send(Message::new(())).await.unwrap();
+            while dispatcher.runnable() < 2 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            gate.notify_one();
+        });
+
+        assert_eq!(processed.recv_timeout(Duration::from_secs(5)).unwrap(), "critical");
+        assert_eq!(processed.recv_timeout(Duration::from_secs(5)).unwrap(), "background");
+    }
+
+    #[test]
+    fn test_spawned_actor_publishes_backpressure_changes() {
+        let system = ActorSystem::new(SystemConfig::default());
+        let mut events = system.event_stream().subscribe::<BackpressureChanged>();
+
+        let (order, _processed) = std::sync::mpsc::channel();
+        let gate = Arc::new(Notify::new());
+        let actor_gate = Arc::clone(&gate);
+        let props = Props::new(move || Ordered { name: "slow", order: order.clone(), gate: Some(actor_gate.clone()) })
+            .with_backpressure(BackpressureConfig {
+                max_queue_size: 4,
+                high_watermark: 0.5,
+                low_watermark: 0.25,
+                pressure_window: Duration::from_millis(0),
+                sampling_interval: Duration::from_millis(10),
+            });
+        let actor = system.spawn(&props).unwrap();
+
+        // 第一条消息卡在 Actor 中，占用的位置在处理完之前不会归还
+        assert_eq!(actor.try_send(Message::new(1u32)).unwrap(), BackpressureSignal::Ok);
+        assert_eq!(actor.try_send(Message::new(2u32)).unwrap(), BackpressureSignal::SlowDown);
+
+        let event = events.try_recv().unwrap();
+        let event = event.downcast_ref::<BackpressureChanged>().unwrap();
+        assert_eq!(event.source, actor.id());
+        assert_eq!(event.previous, BackpressureSignal::Ok);
+        assert_eq!(event.current, BackpressureSignal::SlowDown);
+
+        gate.notify_one();
+    }
+}